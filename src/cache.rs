@@ -1,59 +1,624 @@
 use std::{
-    fs,
+    collections::{BTreeSet, HashMap},
+    env,
+    fs::{self, File, Metadata},
+    hash::{Hash, Hasher},
+    io::Write,
     path::{Path, PathBuf},
-    time::UNIX_EPOCH,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+// Bump whenever the layout below changes, older caches are discarded and rebuilt
+const CACHE_VERSION: u32 = 5;
+const CACHE_MAGIC: &[u8; 8] = b"FUZZIT\0\0";
+
+// Every folder visited during discovery, so only folders whose mtime changed are searched again
+#[derive(Debug, Default)]
+pub struct DirCache {
+    dirs: HashMap<PathBuf, CachedDir>,
+}
+
+#[derive(Debug, Default)]
+pub struct CachedDir {
+    pub mtime: Option<u128>,
+    pub is_repo: bool,
+    pub children: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub mtime: Option<u128>, // None for folders that could not be searched, always retried
+    pub is_repo: bool,
+}
+
+impl DirCache {
+    pub fn get(&self, path: &Path) -> Option<&CachedDir> {
+        self.dirs.get(path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+
+    fn from_entries(entries: &[CacheEntry]) -> Self {
+        let mut dirs: HashMap<PathBuf, CachedDir> = HashMap::with_capacity(entries.len());
+
+        for entry in entries {
+            let cached_dir = dirs.entry(entry.path.clone()).or_default();
+            cached_dir.mtime = entry.mtime;
+            cached_dir.is_repo = entry.is_repo;
+
+            if let Some(parent) = entry.path.parent() {
+                dirs.entry(parent.to_path_buf())
+                    .or_default()
+                    .children
+                    .push(entry.path.clone());
+            }
+        }
+
+        Self { dirs }
+    }
+}
+
+// Git output of a repo, reused while its fingerprint stays the same
+#[derive(Debug, Clone)]
+pub struct CachedStatus {
+    pub fingerprint: u64,
+    pub status: String,
+    pub diff_stat: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Cache {
+    pub dirs: DirCache,
+    pub statuses: HashMap<PathBuf, CachedStatus>,
+    pub scan_options: u64, // ScanOptions::cache_key the folders were searched with
+}
+
+// FNV-1a, unlike DefaultHasher its output is guaranteed to stay the same across Rust versions
+#[derive(Debug)]
+struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv64 {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv64::default();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+// $XDG_CACHE_HOME/fuzzit, falling back to ~/.cache/fuzzit on every OS
+pub fn get_cache_dir() -> Option<PathBuf> {
+    let mut cache_dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|xdg_cache_home| xdg_cache_home.is_absolute()) // Relative paths are invalid per the spec
+        .or_else(|| Some(dirs::home_dir()?.join(".cache")))?;
+    cache_dir.push("fuzzit");
+
+    if !cache_dir.exists() {
+        fs::create_dir_all(&cache_dir).ok()?;
+    }
+
+    Some(cache_dir)
+}
+
+// Base and repo paths are stored canonical, so ./dev and /home/me/dev share cached statuses
+pub fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or(path.to_path_buf())
+}
+
+// Named after a hash of the canonical base path, so ~/dev, ./dev and /a_b vs /a/b don't mix up
 fn get_cache_path(base_path: &Path) -> Option<PathBuf> {
-    let mut cache_path = dirs::home_dir()?;
-    let cache_file_name = base_path
+    let mut cache_path = get_cache_dir()?;
+    cache_path.push(format!(
+        "{:016x}.cache",
+        checksum(&path_to_bytes(&canonical_path(base_path)))
+    ));
+
+    Some(cache_path)
+}
+
+// Caches from before the versioned format were named after the flattened base path
+fn remove_legacy_cache(base_path: &Path) {
+    let Some(cache_dir) = get_cache_dir() else {
+        return;
+    };
+    let legacy_file_name = base_path
         .display()
         .to_string()
         .replace("/", "_")
         .replace("\\", "_")
         .replace(":", "_");
 
-    cache_path.push(".cache");
-    cache_path.push("fuzzit");
+    fs::remove_file(cache_dir.join(&legacy_file_name)).ok();
+    fs::remove_file(cache_dir.join(format!("{legacy_file_name}.status"))).ok();
+}
+
+/*
+Layout (integers little endian, byte strings prefixed with their u64 length):
+    magic (8 bytes), version (u32), checksum of everything after it (u64)
+    canonical base path, scan options key (u64)
+    folder count (u64), per folder: path, kind (0 folder, 1 repo, 2 failed), mtime (u128)
+    status count (u64), per repo: canonical path, fingerprint (u64), status, diff stat
+*/
+pub fn write_cache(
+    base_path: &Path,
+    scan_options: u64,
+    entries: &[CacheEntry],
+    statuses: &[(&Path, &CachedStatus)],
+) -> Option<()> {
+    let cache_path = get_cache_path(base_path)?;
+
+    let mut payload = Encoder::default();
+    payload.bytes(&path_to_bytes(&canonical_path(base_path)));
+    payload.u64(scan_options);
 
-    if !cache_path.exists() {
-        fs::create_dir_all(&cache_path).ok()?;
+    payload.u64(entries.len() as u64);
+    for entry in entries {
+        payload.bytes(&path_to_bytes(&entry.path));
+        payload.u8(match (entry.mtime, entry.is_repo) {
+            (Some(_), false) => 0,
+            (Some(_), true) => 1,
+            (None, _) => 2,
+        });
+        payload.u128(entry.mtime.unwrap_or_default());
     }
 
-    cache_path.push(cache_file_name);
-    Some(cache_path)
+    payload.u64(statuses.len() as u64);
+    for (repo_path, cached_status) in statuses {
+        payload.bytes(&path_to_bytes(&canonical_path(repo_path)));
+        payload.u64(cached_status.fingerprint);
+        payload.bytes(cached_status.status.as_bytes());
+        payload.bytes(cached_status.diff_stat.as_bytes());
+    }
+
+    let mut content = Encoder::default();
+    content.0.extend_from_slice(CACHE_MAGIC);
+    content.u32(CACHE_VERSION);
+    content.u64(checksum(&payload.0));
+    content.0.extend_from_slice(&payload.0);
+
+    // Concurrent runs take turns writing, and readers only ever see a complete file after the rename
+    let lock_file = File::create(cache_path.with_extension("lock")).ok()?;
+    lock_file.lock().ok()?;
+
+    let temp_path = cache_path.with_extension(format!("tmp{}", std::process::id()));
+    let written = File::create(&temp_path)
+        .and_then(|mut temp_file| {
+            temp_file.write_all(&content.0)?;
+            temp_file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, &cache_path));
+
+    if written.is_err() {
+        fs::remove_file(&temp_path).ok();
+    }
+    lock_file.unlock().ok()?;
+
+    remove_legacy_cache(base_path);
+    written.ok()
 }
 
-pub fn create_cache(base_path: &Path, repo_paths: &[PathBuf]) -> Option<()> {
+// Missing, outdated or corrupt caches are ignored (and replaced on the next write)
+pub fn read_cache(base_path: &Path) -> Option<Cache> {
     let cache_path = get_cache_path(base_path)?;
+    let content = fs::read(&cache_path).ok()?;
+    let (cached_base_path, cache) = decode_cache(&content)?;
 
-    let mtime = fs::metadata(base_path).ok()?.modified().ok()?;
-    let mtime_secs = mtime.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    // Guards against hash collisions between base paths
+    if cached_base_path != canonical_path(base_path) {
+        return None;
+    }
+
+    Some(cache)
+}
+
+fn decode_cache(content: &[u8]) -> Option<(PathBuf, Cache)> {
+    let mut decoder = Decoder(content);
+    if decoder.take(CACHE_MAGIC.len())? != CACHE_MAGIC || decoder.u32()? != CACHE_VERSION {
+        return None;
+    }
 
-    let mut content = format!("{mtime_secs}\n");
-    for path in repo_paths {
-        content.push_str(&format!("{}\n", path.display()));
+    let expected_checksum = decoder.u64()?;
+    if checksum(decoder.0) != expected_checksum {
+        return None;
     }
 
-    fs::write(&cache_path, &content).ok()?;
-    Some(())
+    let base_path = path_from_bytes(decoder.bytes()?)?;
+    let scan_options = decoder.u64()?;
+
+    let entry_count = decoder.u64()?;
+    let mut entries = Vec::new();
+    for _ in 0..entry_count {
+        let path = path_from_bytes(decoder.bytes()?)?;
+        let kind = decoder.u8()?;
+        let mtime = decoder.u128()?;
+
+        entries.push(CacheEntry {
+            path,
+            mtime: (kind != 2).then_some(mtime),
+            is_repo: kind == 1,
+        });
+    }
+
+    let status_count = decoder.u64()?;
+    let mut statuses = HashMap::new();
+    for _ in 0..status_count {
+        let repo_path = path_from_bytes(decoder.bytes()?)?;
+        let fingerprint = decoder.u64()?;
+        let status = String::from_utf8(decoder.bytes()?.to_vec()).ok()?;
+        let diff_stat = String::from_utf8(decoder.bytes()?.to_vec()).ok()?;
+
+        statuses.insert(
+            repo_path,
+            CachedStatus {
+                fingerprint,
+                status,
+                diff_stat,
+            },
+        );
+    }
+
+    let cache = Cache {
+        dirs: DirCache::from_entries(&entries),
+        statuses,
+        scan_options,
+    };
+
+    Some((base_path, cache))
 }
 
-// Check if mtime (modified time) of base path in cache is the same as current
-pub fn mtime_matches_cache(base_path: &Path) -> Option<Vec<PathBuf>> {
-    let cache_path = get_cache_path(base_path)?;
+#[derive(Debug)]
+pub struct CacheInfo {
+    pub cache_path: PathBuf,
+    pub base_path: Option<PathBuf>, // None for outdated or corrupt caches
+    pub repo_count: usize,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+pub fn list_caches() -> Vec<CacheInfo> {
+    let Some(entries) = get_cache_dir().and_then(|cache_dir| fs::read_dir(cache_dir).ok()) else {
+        return Vec::new();
+    };
+
+    let mut caches = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|cache_path| {
+            cache_path
+                .extension()
+                .is_some_and(|extension| extension == "cache")
+        })
+        .map(|cache_path| {
+            let metadata = fs::metadata(&cache_path).ok();
+            let decoded = fs::read(&cache_path)
+                .ok()
+                .and_then(|content| decode_cache(&content));
+
+            CacheInfo {
+                base_path: decoded.as_ref().map(|(base_path, _)| base_path.clone()),
+                repo_count: decoded.map_or(0, |(_, cache)| {
+                    cache.dirs.dirs.values().filter(|dir| dir.is_repo).count()
+                }),
+                size: metadata.as_ref().map_or(0, |metadata| metadata.len()),
+                modified: metadata.and_then(|metadata| metadata.modified().ok()),
+                cache_path,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    caches.sort_by(|a, b| a.base_path.cmp(&b.base_path));
+    caches
+}
+
+fn remove_cache(cache_path: &Path) -> bool {
+    fs::remove_file(cache_path.with_extension("lock")).ok();
+    fs::remove_file(cache_path).is_ok()
+}
+
+// Removes the cache of one base path, or everything in the cache folder (including legacy caches)
+pub fn clear_caches(base_path: Option<&Path>) -> usize {
+    if let Some(base_path) = base_path {
+        remove_legacy_cache(base_path);
+        return get_cache_path(base_path)
+            .map_or(0, |cache_path| usize::from(remove_cache(&cache_path)));
+    }
 
-    let current_mtime = fs::metadata(base_path).ok()?.modified().ok()?;
-    let current_mtime_secs = current_mtime.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let Some(entries) = get_cache_dir().and_then(|cache_dir| fs::read_dir(cache_dir).ok()) else {
+        return 0;
+    };
 
-    let content = fs::read_to_string(&cache_path).ok()?;
-    let mut lines = content.lines();
-    let cached_mtime: u64 = lines.next()?.parse().ok()?;
-    let cached_repo_paths: Vec<PathBuf> = lines.map(PathBuf::from).collect();
+    let mut removed = 0;
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_file()
+            && fs::remove_file(&path).is_ok()
+            && path.extension().is_none_or(|extension| extension != "lock")
+        {
+            removed += 1;
+        }
+    }
+
+    removed
+}
+
+// Removes caches of base paths that no longer exist, and caches that can't be read anymore
+pub fn prune_caches() -> Vec<CacheInfo> {
+    list_caches()
+        .into_iter()
+        .filter(|cache| {
+            cache
+                .base_path
+                .as_ref()
+                .is_none_or(|base_path| !base_path.exists())
+        })
+        .filter(|cache| remove_cache(&cache.cache_path))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u128(&mut self, value: u128) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+#[derive(Debug)]
+struct Decoder<'content>(&'content [u8]);
+
+impl<'content> Decoder<'content> {
+    fn take(&mut self, len: usize) -> Option<&'content [u8]> {
+        if len > self.0.len() {
+            return None;
+        }
+
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
 
-    if cached_mtime == current_mtime_secs {
-        Some(cached_repo_paths)
-    } else {
-        None
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
     }
+
+    fn u128(&mut self) -> Option<u128> {
+        Some(u128::from_le_bytes(self.take(16)?.try_into().ok()?))
+    }
+
+    fn bytes(&mut self) -> Option<&'content [u8]> {
+        let len = self.u64()?;
+        self.take(usize::try_from(len).ok()?)
+    }
+}
+
+// Raw bytes so non UTF-8 paths (and paths with newlines) survive the round trip
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> Option<PathBuf> {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    Some(PathBuf::from(OsStr::from_bytes(bytes)))
+}
+
+#[cfg(windows)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::windows::ffi::OsStrExt;
+
+    path.as_os_str()
+        .encode_wide()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+#[cfg(windows)]
+fn path_from_bytes(bytes: &[u8]) -> Option<PathBuf> {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt};
+
+    let units = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect::<Vec<_>>();
+
+    Some(PathBuf::from(OsString::from_wide(&units)))
+}
+
+// Cheap stand-in for running git: changes when HEAD, the index, refs or config change, or the
+// stat of a tracked file or of a folder holding them (where untracked files show up). Like git
+// status only the files in the index are looked at, so ignored trees (ex: node_modules) aren't.
+pub fn repo_fingerprint(repo_path: &Path) -> Option<u64> {
+    let git_dir = git_dir(repo_path)?;
+    let common_dir = fs::read_to_string(git_dir.join("commondir"))
+        .map(|common_dir| git_dir.join(common_dir.trim())) // Linked worktrees share refs
+        .unwrap_or_else(|_| git_dir.clone());
+
+    let mut hasher = Fnv64::default();
+    fs::read(git_dir.join("HEAD")).ok()?.hash(&mut hasher);
+
+    for path in [
+        git_dir.join("index"),
+        common_dir.join("packed-refs"),
+        common_dir.join("config"),
+    ] {
+        if let Ok(metadata) = fs::metadata(&path) {
+            (mtime_nanos(&metadata), metadata.len()).hash(&mut hasher);
+        }
+    }
+
+    newest_mtime(&common_dir.join("refs")).hash(&mut hasher);
+
+    // Repos without an index yet only have untracked files, listed by the folder they're in
+    let mut dirs = BTreeSet::from([Vec::new()]);
+    if let Ok(index) = fs::read(git_dir.join("index")) {
+        let config = fs::read_to_string(common_dir.join("config")).unwrap_or_default();
+        let sha256 = config
+            .lines()
+            .any(|line| line.trim_start().starts_with("objectformat") && line.contains("sha256"));
+
+        for path in tracked_paths(&index, if sha256 { 32 } else { 20 })? {
+            stat_hash(&repo_path.join(String::from_utf8_lossy(&path).as_ref())).hash(&mut hasher);
+            for (i, _) in path.iter().enumerate().filter(|(_, byte)| **byte == b'/') {
+                dirs.insert(path[..i].to_vec());
+            }
+        }
+    }
+    for dir in dirs {
+        stat_hash(&repo_path.join(String::from_utf8_lossy(&dir).as_ref())).hash(&mut hasher);
+    }
+
+    Some(hasher.finish())
+}
+
+// Mtime and size, or nothing once the path is gone
+fn stat_hash(path: &Path) -> Option<(u128, u64)> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    Some((mtime_nanos(&metadata), metadata.len()))
+}
+
+// Paths of the files in a git index (versions 2 to 4), None when it can't be read
+fn tracked_paths(index: &[u8], hash_len: usize) -> Option<Vec<Vec<u8>>> {
+    let be_u32 = |at: usize| Some(u32::from_be_bytes(index.get(at..at + 4)?.try_into().ok()?));
+    let version = be_u32(4)?;
+    if index.get(..4)? != b"DIRC" || !(2..=4).contains(&version) {
+        return None;
+    }
+
+    let count = usize::try_from(be_u32(8)?).ok()?;
+    let mut paths: Vec<Vec<u8>> = Vec::with_capacity(count);
+    let mut pos = 12;
+    for _ in 0..count {
+        let entry_start = pos;
+        // ctime, mtime, dev, ino, mode, uid, gid and size, then the object id
+        pos += 40 + hash_len;
+        let flags = u16::from_be_bytes(index.get(pos..pos + 2)?.try_into().ok()?);
+        pos += 2;
+        if version >= 3 && flags & 0x4000 != 0 {
+            pos += 2; // Extended flags
+        }
+
+        let path = if version == 4 {
+            // Bytes to drop from the end of the previous path, then the rest of this one
+            let (drop, varint_len) = index_varint(index.get(pos..)?)?;
+            pos += varint_len;
+            let suffix_len = index.get(pos..)?.iter().position(|&byte| byte == 0)?;
+            let previous = paths.last().map_or(&[][..], Vec::as_slice);
+            let mut path = previous.get(..previous.len().checked_sub(drop)?)?.to_vec();
+            path.extend_from_slice(&index[pos..pos + suffix_len]);
+            pos += suffix_len + 1;
+            path
+        } else {
+            let name_len = index.get(pos..)?.iter().position(|&byte| byte == 0)?;
+            let name = index[pos..pos + name_len].to_vec();
+            // Entries are padded with 1 to 8 NULs to a multiple of 8 bytes
+            pos = entry_start + (pos + name_len - entry_start + 8) / 8 * 8;
+            name
+        };
+        paths.push(path);
+    }
+
+    Some(paths)
+}
+
+// Varint of index version 4, returning the value and how many bytes it took
+fn index_varint(bytes: &[u8]) -> Option<(usize, usize)> {
+    let mut value = usize::from(*bytes.first()? & 0x7f);
+    let mut len = 1;
+    while bytes[len - 1] & 0x80 != 0 {
+        let byte = *bytes.get(len)?;
+        value = ((value + 1) << 7) + usize::from(byte & 0x7f);
+        len += 1;
+    }
+
+    Some((value, len))
+}
+
+// .git is a file pointing elsewhere for worktrees and submodules
+fn git_dir(repo_path: &Path) -> Option<PathBuf> {
+    let git_path = repo_path.join(".git");
+
+    if git_path.is_dir() {
+        return Some(git_path);
+    }
+
+    let content = fs::read_to_string(&git_path).ok()?;
+    let git_dir = content.trim().strip_prefix("gitdir:")?.trim();
+
+    Some(repo_path.join(git_dir))
+}
+
+fn mtime_nanos(metadata: &Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+        .map(|mtime| mtime.as_nanos())
+        .unwrap_or_default()
+}
+
+// Newest mtime of a folder and everything in it (without following symlinks), only used for refs
+fn newest_mtime(path: &Path) -> u128 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    let mut newest = mtime_nanos(&metadata);
+
+    if metadata.is_dir()
+        && let Ok(entries) = fs::read_dir(path)
+    {
+        for entry in entries.flatten() {
+            newest = newest.max(newest_mtime(&entry.path()));
+        }
+    }
+
+    newest
 }
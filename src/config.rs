@@ -0,0 +1,239 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{Result, eyre};
+use serde::Deserialize;
+
+use crate::{
+    git_data::{DiffAlgorithm, DiffOptions, RenameDetection, ScanOptions, expand_home},
+    keymap::{KeyBindings, Keymap},
+    theme::{Theme, ThemeConfig},
+};
+
+// Loaded from ~/.config/fuzzit/config.toml (or FUZZIT_CONFIG), every field is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub commands: Commands,
+    pub layout: LayoutConfig,
+    pub diff: DiffConfig,
+    pub keys: HashMap<String, KeyBindings>, // Keyed by action name, replacing its default keys
+    pub theme: ThemeConfig,
+    pub scan: HashMap<PathBuf, ScanConfig>, // Keyed by base path (ex: [scan."~/dev"])
+    #[serde(skip)]
+    pub keymap: Keymap,   // Built from keys once loaded
+    #[serde(skip)]
+    pub styles: Theme,    // Built from theme once loaded
+}
+
+// Templates run from the repo folder, {path}, {remote_url} and {branch} are filled in per repo
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Commands {
+    pub editor: String,
+    pub shell: String,
+    pub git_client: String,
+    pub browser: String,
+}
+
+// Discovery limits of one base path, flags take priority
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanConfig {
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: Option<bool>,
+    pub one_file_system: Option<bool>,
+}
+
+// Starting pane layout of the diff TUI, changed while it runs with [ ] L z f
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    pub orientation: Orientation,
+    pub split: u16,       // Share of the path list in percent
+    pub stack_below: u16, // Terminal width auto orientation stacks the panes below
+    pub footer: bool,
+}
+
+// Diffs are loaded when shown, up to max_size bytes (0 loads them whole), and whole when searched.
+// Flags take priority over the other options, which can be toggled while the diff TUI runs.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiffConfig {
+    pub max_size: usize,
+    pub ignore_whitespace: bool,
+    pub ignore_blank_lines: bool,
+    pub context: Option<u32>,
+    pub renames: RenameDetection,
+    pub word_diff: bool,
+    pub algorithm: DiffAlgorithm,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    #[default]
+    Auto, // Side by side, stacked on narrow terminals
+    Horizontal,
+    Vertical,
+}
+
+impl Orientation {
+    pub fn next(self) -> Self {
+        match self {
+            Orientation::Auto => Orientation::Horizontal,
+            Orientation::Horizontal => Orientation::Vertical,
+            Orientation::Vertical => Orientation::Auto,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Orientation::Auto => "auto",
+            Orientation::Horizontal => "side by side",
+            Orientation::Vertical => "stacked",
+        }
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            orientation: Orientation::Auto,
+            split: 50,
+            stack_below: 100,
+            footer: true,
+        }
+    }
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 1_000_000,
+            ignore_whitespace: false,
+            ignore_blank_lines: false,
+            context: None,
+            renames: RenameDetection::default(),
+            word_diff: false,
+            algorithm: DiffAlgorithm::default(),
+        }
+    }
+}
+
+impl DiffConfig {
+    pub fn max_size(&self) -> Option<usize> {
+        (self.max_size > 0).then_some(self.max_size)
+    }
+
+    pub fn options(&self) -> DiffOptions {
+        DiffOptions {
+            ignore_whitespace: self.ignore_whitespace,
+            ignore_blank_lines: self.ignore_blank_lines,
+            context: self.context,
+            renames: self.renames,
+            word_diff: self.word_diff,
+            algorithm: self.algorithm,
+        }
+    }
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+
+        let shell = if cfg!(windows) {
+            env::var("COMSPEC").unwrap_or_else(|_| "cmd".to_string())
+        } else {
+            env::var("SHELL").unwrap_or_else(|_| "sh".to_string())
+        };
+
+        let opener = if cfg!(target_os = "macos") {
+            "open"
+        } else if cfg!(windows) {
+            "cmd /C start"
+        } else {
+            "xdg-open"
+        };
+
+        Self {
+            editor: format!("{editor} {{path}}"),
+            shell,
+            git_client: "lazygit -p {path}".to_string(),
+            browser: format!("{opener} {{remote_url}}"),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(config_path: Option<PathBuf>) -> Result<Self> {
+        let Some(config_path) = config_path.or_else(default_config_path) else {
+            return Ok(Self::default());
+        };
+
+        if !config_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&config_path).map_err(|e| {
+            eyre!(format!(
+                "Config {} could not be read: {e}",
+                config_path.display()
+            ))
+        })?;
+
+        let mut config: Self = toml::from_str(&content).map_err(|e| {
+            eyre!(format!(
+                "Config {} could not be parsed: {e}",
+                config_path.display()
+            ))
+        })?;
+
+        config.keymap = Keymap::new(&config.keys).map_err(|e| {
+            eyre!(format!(
+                "Config {} has invalid [keys]: {e}",
+                config_path.display()
+            ))
+        })?;
+
+        config.styles = Theme::new(&config.theme).map_err(|e| {
+            eyre!(format!(
+                "Config {} has an invalid [theme]: {e}",
+                config_path.display()
+            ))
+        })?;
+
+        Ok(config)
+    }
+
+    pub fn scan_options(&self, base_path: &Path) -> ScanOptions {
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or(path.to_path_buf());
+        let base_path = canonical(base_path);
+
+        let Some(scan_config) = self.scan.iter().find_map(|(path, scan_config)| {
+            let path = expand_home(path).ok()?;
+            (canonical(&path) == base_path).then_some(scan_config)
+        }) else {
+            return ScanOptions::default();
+        };
+
+        ScanOptions {
+            max_depth: scan_config.max_depth,
+            follow_symlinks: scan_config.follow_symlinks.unwrap_or_default(),
+            one_file_system: scan_config.one_file_system.unwrap_or_default(),
+        }
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let mut config_path = dirs::config_dir()?;
+    config_path.push("fuzzit");
+    config_path.push("config.toml");
+
+    Some(config_path)
+}
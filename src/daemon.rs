@@ -0,0 +1,553 @@
+// Background daemon keeping git data of base paths in memory, served over a Unix domain socket
+//
+// Protocol (version 3): newline delimited JSON, one request per line and one response line per
+// request, connections can be reused for several requests.
+//
+// Requests: {"version": 3, "command": "<command>", "base_path": "~/dev"}
+//   ping     Check the daemon is running, answers with its pid
+//   repos    Git data of every repo under base_path, scanned first if the daemon doesn't know it yet,
+//            repos whose fingerprint changed since git last ran for them are read again first
+//   refresh  Same as repos, but always scans base_path again before answering
+//   stop     Shut the daemon down
+//
+// Responses: {"version": 3, "ok": true, ...} or {"version": 3, "ok": false, "error": "<message>"}
+//   ping           "pid": 1234
+//   repos/refresh  "base_path": "/home/me/dev",
+//                  "age_ms": 1200 (time since the last scan, which finds added and removed repos),
+//                  "scan_stats": {"duration_ms", "cache_hit", "rescanned_folders",
+//                                 "status_cache_hits", "repo_count"},
+//                  "repos": [{"name": "work/api", "path": "/home/me/dev/work/api",
+//                             "state": "clean|unstaged|staged|unpushed|conflicted|unknown|error|
+//                                       timeout",
+//                             "status": "<git status>",
+//                             "diff_stat": "<git diff --numstat against upstream>",
+//                             "error": null or {"message": "...", "code": 128 or null,
+//                                               "timed_out": false},
+//                             "modified": unix seconds or null}]
+//
+// Requests with another version are answered with an error, clients should then scan directly.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{Result, eyre};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::get_cache_dir,
+    config::Config,
+    git_data::{
+        DEFAULT_REPO_TIMEOUT, GitData, GitError, Repo, Repos, ScanStats, collect_repo_data,
+        expand_home, fingerprint_within,
+    },
+    scan::{Scan, ScanResult},
+};
+
+pub const PROTOCOL_VERSION: u32 = 3;
+
+// Scanning a base path the daemon doesn't know yet takes as long as a normal run
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    version: u32,
+    command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Response {
+    version: u32,
+    ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    age_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scan_stats: Option<ScanInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repos: Option<Vec<RepoInfo>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScanInfo {
+    duration_ms: u64,
+    cache_hit: bool,
+    rescanned_folders: usize,
+    status_cache_hits: usize,
+    repo_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RepoInfo {
+    name: String,
+    path: PathBuf,
+    state: String,
+    status: String,
+    diff_stat: String,
+    error: Option<ErrorInfo>,
+    modified: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ErrorInfo {
+    message: String,
+    code: Option<i32>,
+    #[serde(default)]
+    timed_out: bool,
+}
+
+impl Response {
+    fn ok() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            ok: true,
+            ..Self::default()
+        }
+    }
+
+    fn error(message: String) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            error: Some(message),
+            ..Self::default()
+        }
+    }
+
+    fn from_snapshot(base_path: &Path, snapshot: &Snapshot) -> Self {
+        let stats = &snapshot.scan_stats;
+        let repos = snapshot
+            .repos
+            .iter()
+            .map(
+                |Repo {
+                     name,
+                     data: git_data,
+                 }| RepoInfo {
+                    name: name.clone(),
+                    path: git_data.path.clone(),
+                    state: git_data.state().short_label().to_string(),
+                    status: git_data.status.clone(),
+                    diff_stat: git_data.diff_stat.clone(),
+                    error: git_data.error.as_ref().map(|error| ErrorInfo {
+                        message: error.message.clone(),
+                        code: error.code,
+                        timed_out: error.timed_out,
+                    }),
+                    modified: git_data.modified,
+                },
+            )
+            .collect();
+
+        Self {
+            base_path: Some(base_path.to_path_buf()),
+            age_ms: Some(millis(snapshot.refreshed.elapsed())),
+            scan_stats: Some(ScanInfo {
+                duration_ms: millis(stats.duration),
+                cache_hit: stats.cache_hit,
+                rescanned_folders: stats.rescanned_folders,
+                status_cache_hits: stats.status_cache_hits,
+                repo_count: stats.repo_count,
+            }),
+            repos: Some(repos),
+            ..Self::ok()
+        }
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+// $XDG_RUNTIME_DIR/fuzzit.sock, or the cache folder when there is no runtime folder (macOS)
+pub fn socket_path() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .map(|runtime_dir| runtime_dir.join("fuzzit.sock"))
+        .or_else(|| Some(get_cache_dir()?.join("daemon.sock")))
+}
+
+#[derive(Debug)]
+struct Snapshot {
+    repos: Repos,
+    scan_stats: ScanStats,
+    refreshed: Instant,
+    fingerprints: HashMap<PathBuf, u64>, // Per repo path, of the repo as git last read it
+}
+
+type Snapshots = Arc<Mutex<HashMap<PathBuf, Snapshot>>>;
+
+fn scan(base_path: &Path, config: &Config) -> Result<Snapshot> {
+    let scan_options = config.scan_options(base_path);
+    let ScanResult {
+        repos,
+        stats: scan_stats,
+        ..
+    } = Scan::new(base_path).options(scan_options).run()?;
+
+    // Taken after git ran, so a change made meanwhile is only seen with the next scan. Repos
+    // without a fingerprint are only read again by scans.
+    let fingerprints = repos
+        .par_iter()
+        .filter_map(|repo| {
+            let fingerprint = fingerprint_within(&repo.data.path, Some(DEFAULT_REPO_TIMEOUT));
+            Some((repo.data.path.clone(), fingerprint.ok()??))
+        })
+        .collect();
+
+    Ok(Snapshot {
+        repos,
+        scan_stats,
+        refreshed: Instant::now(),
+        fingerprints,
+    })
+}
+
+// Runs git again for the repos of base_path whose fingerprint changed, so answers follow commits
+// and edits made since the last scan
+fn refresh_changed_repos(base_path: &Path, snapshots: &Snapshots) {
+    let Some(fingerprints) = lock(snapshots)
+        .get(base_path)
+        .map(|snapshot| snapshot.fingerprints.clone())
+    else {
+        return;
+    };
+
+    // Read without holding the lock so other clients are still answered meanwhile
+    let changed = fingerprints
+        .into_par_iter()
+        .filter_map(|(repo_path, fingerprint)| {
+            let start = Instant::now();
+            let new_fingerprint =
+                fingerprint_within(&repo_path, Some(DEFAULT_REPO_TIMEOUT)).ok()??;
+            if new_fingerprint == fingerprint {
+                return None;
+            }
+
+            let git_timeout = DEFAULT_REPO_TIMEOUT.saturating_sub(start.elapsed());
+            let git_data = collect_repo_data(&repo_path, Some(git_timeout));
+            Some((repo_path, new_fingerprint, git_data))
+        })
+        .collect::<Vec<_>>();
+    if changed.is_empty() {
+        return;
+    }
+
+    let mut snapshots = lock(snapshots);
+    let Some(snapshot) = snapshots.get_mut(base_path) else {
+        return;
+    };
+    for (repo_path, fingerprint, git_data) in changed {
+        if let Some(repo) = snapshot
+            .repos
+            .iter_mut()
+            .find(|repo| repo.data.path == repo_path)
+        {
+            repo.data = git_data;
+            snapshot.fingerprints.insert(repo_path, fingerprint);
+        }
+    }
+}
+
+// Runs in the foreground until stopped, base paths are added as clients ask for them
+pub fn run(preload: Option<PathBuf>, interval: Duration, config: Config) -> Result<()> {
+    let socket_path =
+        socket_path().ok_or_else(|| eyre!("Daemon socket path could not be determined"))?;
+
+    if UnixStream::connect(&socket_path).is_ok() {
+        Err(eyre!(format!(
+            "A daemon is already listening on {}",
+            socket_path.display()
+        )))?
+    }
+    fs::remove_file(&socket_path).ok(); // Left behind by a daemon that didn't shut down cleanly
+
+    let listener = UnixListener::bind(&socket_path).map_err(|e| {
+        eyre!(format!(
+            "Daemon socket {} could not be created: {e}",
+            socket_path.display()
+        ))
+    })?;
+    fs::set_permissions(&socket_path, fs::Permissions::from_mode(0o600))?;
+
+    let snapshots: Snapshots = Arc::default();
+    let stopping = Arc::new(AtomicBool::new(false));
+    let config = Arc::new(config);
+
+    if let Some(base_path) = preload {
+        let base_path = expand_home(&base_path)?;
+        let snapshot = scan(&base_path, &config)?;
+        writeln!(
+            io::stderr(),
+            "Polling {} ({} repos)",
+            base_path.display(),
+            snapshot.repos.len()
+        )
+        .ok();
+        lock(&snapshots).insert(base_path, snapshot);
+    }
+    writeln!(io::stderr(), "Listening on {}", socket_path.display()).ok();
+
+    {
+        let snapshots = Arc::clone(&snapshots);
+        let config = Arc::clone(&config);
+        thread::spawn(move || refresh_loop(&snapshots, interval, &config));
+    }
+
+    for stream in listener.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let snapshots = Arc::clone(&snapshots);
+        let stopping = Arc::clone(&stopping);
+        let config = Arc::clone(&config);
+        let socket_path = socket_path.clone();
+
+        thread::spawn(move || {
+            if handle_client(stream, &snapshots, &config) {
+                stopping.store(true, Ordering::SeqCst);
+                UnixStream::connect(socket_path).ok(); // Wake up the listener so it sees the stop
+            }
+        });
+    }
+
+    fs::remove_file(&socket_path).ok();
+    writeln!(io::stderr(), "Stopped").ok();
+
+    Ok(())
+}
+
+fn lock(snapshots: &Snapshots) -> std::sync::MutexGuard<'_, HashMap<PathBuf, Snapshot>> {
+    snapshots.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Polls for repos that were added or removed rather than watching the file system, since watching
+// every folder below the base paths runs into inotify limits. Changes to repos that are already
+// known are picked up before each answer by refresh_changed_repos instead.
+fn refresh_loop(snapshots: &Snapshots, interval: Duration, config: &Config) {
+    loop {
+        thread::sleep(interval);
+
+        let base_paths = lock(snapshots).keys().cloned().collect::<Vec<_>>();
+        for base_path in base_paths {
+            match scan(&base_path, config) {
+                Ok(snapshot) => {
+                    lock(snapshots).insert(base_path, snapshot);
+                }
+                Err(e) => {
+                    writeln!(
+                        io::stderr(),
+                        "{} could not be refreshed: {e}",
+                        base_path.display()
+                    )
+                    .ok();
+                    lock(snapshots).remove(&base_path);
+                }
+            }
+        }
+    }
+}
+
+// Answers requests until the client disconnects, returns whether the daemon should stop
+fn handle_client(stream: UnixStream, snapshots: &Snapshots, config: &Config) -> bool {
+    let Ok(reader_stream) = stream.try_clone() else {
+        return false;
+    };
+    let mut writer = stream;
+
+    for line in BufReader::new(reader_stream).lines() {
+        let Ok(line) = line else {
+            return false;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (response, stop) = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => handle_request(request, snapshots, config),
+            Err(e) => (Response::error(format!("Invalid request: {e}")), false),
+        };
+
+        let mut response = serde_json::to_string(&response).unwrap_or_else(|e| {
+            serde_json::to_string(&Response::error(format!("Invalid response: {e}")))
+                .unwrap_or_default()
+        });
+        response.push('\n');
+
+        if writer.write_all(response.as_bytes()).is_err() || stop {
+            return stop;
+        }
+    }
+
+    false
+}
+
+fn handle_request(request: Request, snapshots: &Snapshots, config: &Config) -> (Response, bool) {
+    if request.version != PROTOCOL_VERSION {
+        return (
+            Response::error(format!(
+                "Unsupported protocol version {}, this daemon speaks version {PROTOCOL_VERSION}",
+                request.version
+            )),
+            false,
+        );
+    }
+
+    match request.command.as_str() {
+        "ping" => (
+            Response {
+                pid: Some(std::process::id()),
+                ..Response::ok()
+            },
+            false,
+        ),
+        "stop" => (Response::ok(), true),
+        command @ ("repos" | "refresh") => {
+            let Some(base_path) = request.base_path else {
+                return (
+                    Response::error(format!("{command} needs a base_path")),
+                    false,
+                );
+            };
+            let base_path = match expand_home(&base_path) {
+                Ok(base_path) => base_path,
+                Err(e) => return (Response::error(format!("{e:#}")), false),
+            };
+
+            if command == "repos" && lock(snapshots).contains_key(&base_path) {
+                refresh_changed_repos(&base_path, snapshots);
+                if let Some(snapshot) = lock(snapshots).get(&base_path) {
+                    return (Response::from_snapshot(&base_path, snapshot), false);
+                }
+            }
+
+            // Scanned without holding the lock so other clients are still answered meanwhile
+            match scan(&base_path, config) {
+                Ok(snapshot) => {
+                    let response = Response::from_snapshot(&base_path, &snapshot);
+                    writeln!(
+                        io::stderr(),
+                        "Polling {} ({} repos)",
+                        base_path.display(),
+                        snapshot.repos.len()
+                    )
+                    .ok();
+                    lock(snapshots).insert(base_path, snapshot);
+                    (response, false)
+                }
+                Err(e) => (Response::error(format!("{e:#}")), false),
+            }
+        }
+        command => (Response::error(format!("Unknown command {command}")), false),
+    }
+}
+
+fn send(request: &Request, timeout: Duration) -> Option<Response> {
+    let mut stream = UnixStream::connect(socket_path()?).ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+
+    let mut request = serde_json::to_string(request).ok()?;
+    request.push('\n');
+    stream.write_all(request.as_bytes()).ok()?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).ok()?;
+
+    serde_json::from_str(&response).ok()
+}
+
+// Asks a running daemon for the repos under base_path, None means scan directly instead
+pub fn query_repos(base_path: &Path) -> Option<(Repos, ScanStats)> {
+    let response = send(
+        &Request {
+            version: PROTOCOL_VERSION,
+            command: "repos".to_string(),
+            base_path: Some(base_path.to_path_buf()),
+        },
+        CLIENT_TIMEOUT,
+    )?;
+    if !response.ok || response.version != PROTOCOL_VERSION {
+        return None;
+    }
+
+    let scan_info = response.scan_stats?;
+    let scan_stats = ScanStats {
+        duration: Duration::from_millis(scan_info.duration_ms),
+        cache_hit: scan_info.cache_hit,
+        rescanned_folders: scan_info.rescanned_folders,
+        status_cache_hits: scan_info.status_cache_hits,
+        repo_count: scan_info.repo_count,
+        daemon_age: response.age_ms.map(Duration::from_millis),
+        ..ScanStats::default() // Timings stay with the daemon
+    };
+
+    let repos = response
+        .repos?
+        .into_iter()
+        .map(|repo| {
+            let git_data = GitData {
+                path: repo.path,
+                status: repo.status,
+                diff_stat: repo.diff_stat,
+                diff: None,
+                error: repo.error.map(|error| GitError {
+                    message: error.message,
+                    code: error.code,
+                    timed_out: error.timed_out,
+                }),
+                git_duration: None,
+                last_commit: None,
+                modified: repo.modified,
+            };
+            Repo {
+                name: repo.name,
+                data: git_data,
+            }
+        })
+        .collect();
+
+    Some((repos, scan_stats))
+}
+
+pub fn stop() -> Result<()> {
+    let request = Request {
+        version: PROTOCOL_VERSION,
+        command: "stop".to_string(),
+        base_path: None,
+    };
+
+    match send(&request, Duration::from_secs(5)) {
+        Some(response) if response.ok => {
+            println!("Daemon stopped");
+            Ok(())
+        }
+        Some(response) => Err(eyre!(format!(
+            "Daemon could not be stopped: {}",
+            response.error.unwrap_or_default()
+        ))),
+        None => Err(eyre!("No daemon is running")),
+    }
+}
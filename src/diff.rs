@@ -1,25 +1,68 @@
 //! Started off from https://github.com/ratatui/ratatui/blob/2b0a044cedfc3f58c99ef8ac21f83d20432c2144/examples/apps/todo-list/src/main.rs
 
+use std::{
+    collections::HashSet,
+    io, mem,
+    ops::Range,
+    panic,
+    path::PathBuf,
+    slice,
+    sync::{
+        Once,
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    thread,
+    time::Duration,
+};
+
 use color_eyre::eyre::Result;
 use ratatui::{
     DefaultTerminal, Frame,
-    crossterm::event::{self, Event, KeyCode, KeyEventKind},
-    layout::{Constraint, Layout, Rect},
-    style::{Color, Modifier, Style, Stylize},
+    crossterm::{
+        event::{
+            self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind,
+            MouseButton, MouseEvent, MouseEventKind,
+        },
+        execute,
+        terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    },
+    layout::{Constraint, Direction, Layout, Position, Rect},
+    style::{Modifier, Style, Stylize},
     symbols::border,
     text::{Line, Span, Text},
     widgets::{
-        Block, BorderType, Borders, HighlightSpacing, List, ListItem, ListState, Padding,
+        Block, BorderType, Borders, Clear, HighlightSpacing, List, ListItem, ListState, Padding,
         Paragraph, StatefulWidget, Widget, Wrap,
     },
 };
+use regex::Regex;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    config::{Config, DiffConfig, Orientation},
+    filter::{ContentSearch, Filters, StateFilter, smart_case_regex},
+    git_data::{
+        DEFAULT_REPO_TIMEOUT, Diff, GitData, Repo, ScanStats, collect_repo_data, load_diff,
+        load_diffs, load_last_commits,
+    },
+    keymap::{Action, Lookup, keys_label},
+    open::{OpenTarget, open_repo},
+    sort::{GroupBy, SortBy, group_repos, sort_repos},
+    stage::{self, ChangeAction, Changes, Section},
+    summary::Summary,
+    theme::Theme,
+};
 
-use crate::git_data::GitData;
+// Lines scrolled per mouse wheel tick
+const SCROLL_LINES: u16 = 3;
+// The split can't be moved closer to the edges than this, in percent
+const MIN_SPLIT: u16 = 10;
+const MAX_SPLIT: u16 = 90;
+const SPLIT_STEP: u16 = 5;
+// How often a diff loading in the background is checked for while no keys are pressed
+const DIFF_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
-const KEYBINDS: [&str; 2] = [
-    "(↑/k) move up | (↓/j) move down | (←/h) move left | (→/l) move right",
-    "(g/G) to go top/bottom | (Esc/q) quit",
-];
+static MOUSE_PANIC_HOOK: Once = Once::new();
 
 #[derive(Debug)]
 pub struct App {
@@ -28,7 +71,98 @@ pub struct App {
     max_diff_scroll: u16,
     focused_window: FocusedWindow,
     base_path: String,
-    items: Vec<(String, GitData)>,
+    items: Vec<Repo>,
+    sort_by: SortBy,
+    group_by: GroupBy,
+    filters: Filters,
+    summary: Summary, // Of every repo, computed again when one is refreshed
+    config: Config,
+    repo_timeout: Option<Duration>, // For git commands run while the TUI is open
+    message: Option<String>,
+    groups: Vec<(String, Vec<usize>)>,
+    collapsed_groups: HashSet<String>,
+    rows: Vec<Row>,
+    split: u16, // Share of the path list in percent
+    dragging_split: bool,
+    orientation: Orientation,
+    stacked: bool, // Whether the last frame stacked the panes
+    show_footer: bool,
+    zoomed: bool,               // Diff preview takes up the whole screen
+    pending_keys: Vec<KeyCode>, // Start of a multi-key binding, ex: the first g of gg
+    show_help: bool,
+    help_scroll: u16,
+    search_input: Option<String>, // Pattern being typed after /
+    search: Option<Regex>,
+    search_error: Option<String>,
+    current_match: Option<usize>,
+    grep_input: Option<String>, // Pattern being typed after F, filtering repos as it changes
+    grep_file_names: bool,
+    grep_error: Option<String>,
+    changes: Option<Changes>, // Staged and unstaged changes of the selected repo, while reviewing them
+    selected_hunk: usize,
+    confirm: Option<(ChangeAction, bool)>, // Discard waiting for y, of the whole file or a hunk
+    commit_input: Option<String>,
+    diff_jumps: Vec<DiffJump>, // Resolved on the next render, which knows how lines wrap
+    diff: Option<(PathBuf, Diff)>, // Diff of the selected repo, loaded once it's shown
+    loading_diff: Option<(PathBuf, Receiver<Diff>)>, // Diff being loaded in the background
+    background_diffs: bool,    // Only while the TUI runs interactively
+    show_diff_stat: bool,      // Changed files with bars instead of the diff
+    pane: Option<Pane>,
+    // Where the last frame drew each pane, to know what the mouse points at
+    content_area: Rect,
+    list_area: Rect,
+    diff_area: Rect,
+}
+
+// Lines of the diff pane styled and wrapped once, instead of on every frame. Rebuilt when the
+// selection or what it shows changes, rewrapped when the width does.
+#[derive(Debug, Default)]
+struct Pane {
+    key: (Option<Row>, bool, bool), // Selected row, whether its changes or diffstat were shown
+    lines: Vec<(String, Style)>,
+    marks: Vec<Mark>,
+    width: u16,
+    rows: Vec<usize>, // Row each line starts on once wrapped, followed by the total
+    search: Option<String>, // Pattern the matches were found with
+    matches: Vec<(usize, Range<usize>)>,
+}
+
+// Part of a line of the pane styled on top of it, ex: a diffstat bar
+type Mark = (usize, Range<usize>, Style);
+
+// Rows of the path list, indexing into groups and items
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Row {
+    Group(usize),
+    Repo(usize),
+}
+
+// Key presses that leave the app or suspend it, handled by whoever owns the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyAction {
+    Quit,
+    Open(OpenTarget),
+}
+
+#[derive(Debug, Clone)]
+enum DiffJump {
+    NextMatch,
+    PreviousMatch,
+    NextHunk,
+    PreviousHunk,
+    NextFile,
+    PreviousFile,
+    Line(usize),
+    Hit(DiffHit),
+}
+
+// Hit of a search in the diff it searched, found again in the shown diff by its file and hunk
+// since that one can have other lines (ex: with words diffed)
+#[derive(Debug, Clone)]
+struct DiffHit {
+    file: usize,
+    hunk: Option<usize>, // None for hits on the file name
+    regex: Regex,
 }
 
 #[derive(Debug, PartialEq)]
@@ -38,21 +172,562 @@ enum FocusedWindow {
 }
 
 impl App {
-    pub fn new(base_path: String, git_data: Vec<(String, GitData)>) -> Self {
-        Self {
+    pub fn new(
+        base_path: String,
+        git_data: Vec<Repo>,
+        sort_by: SortBy,
+        group_by: GroupBy,
+        filters: Filters,
+        scan_stats: ScanStats,
+        config: Config,
+    ) -> Self {
+        let layout = config.layout.clone();
+        let summary = Summary::new(&git_data, scan_stats);
+        let mut app = Self {
             state: ListState::default().with_selected(Some(0)),
             diff_scroll: 0,
             max_diff_scroll: 0,
             focused_window: FocusedWindow::PathList,
             base_path,
             items: git_data,
+            sort_by,
+            group_by,
+            filters,
+            summary,
+            config,
+            repo_timeout: Some(DEFAULT_REPO_TIMEOUT),
+            message: None,
+            groups: Vec::new(),
+            collapsed_groups: HashSet::new(),
+            rows: Vec::new(),
+            split: layout.split.clamp(MIN_SPLIT, MAX_SPLIT),
+            dragging_split: false,
+            orientation: layout.orientation,
+            stacked: false,
+            show_footer: layout.footer,
+            zoomed: false,
+            pending_keys: Vec::new(),
+            show_help: false,
+            help_scroll: 0,
+            search_input: None,
+            search: None,
+            search_error: None,
+            current_match: None,
+            grep_input: None,
+            grep_file_names: false,
+            grep_error: None,
+            changes: None,
+            selected_hunk: 0,
+            confirm: None,
+            commit_input: None,
+            diff_jumps: Vec::new(),
+            diff: None,
+            loading_diff: None,
+            background_diffs: false,
+            show_diff_stat: false,
+            pane: None,
+            content_area: Rect::default(),
+            list_area: Rect::default(),
+            diff_area: Rect::default(),
+        };
+
+        app.update_rows();
+        app.commit_grep();
+        app
+    }
+
+    // Same as the scan's --repo-timeout, None waits on git for as long as it takes
+    pub fn with_repo_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.repo_timeout = timeout;
+        self
+    }
+
+    // Re-sort and re-group items, keeping the same repo or group selected
+    fn update_rows(&mut self) {
+        let selected = self.state.selected().and_then(|i| match self.rows.get(i)? {
+            Row::Group(group_i) => Some((true, self.groups[*group_i].0.clone())),
+            Row::Repo(repo_i) => Some((false, self.items[*repo_i].name.clone())),
+        });
+
+        if self.sort_by == SortBy::Commit {
+            load_last_commits(&mut self.items, self.repo_timeout);
+        }
+        sort_repos(&mut self.items, self.sort_by);
+        self.groups = group_repos(&self.items, self.group_by, &self.filters);
+        self.pane = None; // Rows index into the sorted items and groups
+
+        self.rows.clear();
+        for (group_i, (label, indices)) in self.groups.iter().enumerate() {
+            if self.group_by != GroupBy::None {
+                self.rows.push(Row::Group(group_i));
+
+                if self.collapsed_groups.contains(label) {
+                    continue;
+                }
+            }
+
+            self.rows.extend(indices.iter().map(|&i| Row::Repo(i)));
+        }
+
+        if let Some((is_group, label)) = selected
+            && let Some(i) = self.rows.iter().position(|row| match row {
+                Row::Group(group_i) => is_group && self.groups[*group_i].0 == label,
+                Row::Repo(repo_i) => !is_group && self.items[*repo_i].name == label,
+            })
+        {
+            self.state.select(Some(i));
+        }
+    }
+
+    fn selected_row(&self) -> Option<Row> {
+        self.state
+            .selected()
+            .and_then(|i| self.rows.get(i).copied())
+    }
+
+    fn cycle_sort(&mut self) {
+        self.sort_by = self.sort_by.next();
+        self.update_rows();
+    }
+
+    fn cycle_group(&mut self) {
+        self.group_by = self.group_by.next();
+        self.collapsed_groups.clear();
+        self.update_rows();
+    }
+
+    fn toggle_filter(&mut self, filter: StateFilter) {
+        self.filters.toggle(filter);
+        self.update_rows();
+    }
+
+    fn toggle_hide_clean(&mut self) {
+        self.filters.hide_clean = !self.filters.hide_clean;
+        self.update_rows();
+    }
+
+    fn clear_filters(&mut self) {
+        self.filters.clear();
+        self.update_rows();
+    }
+
+    fn start_grep(&mut self) {
+        load_diffs(
+            &mut self.items,
+            &self.config.diff.options(),
+            self.repo_timeout,
+        );
+        let input = self.filters.grep.as_ref().map(|grep| {
+            self.grep_file_names = grep.file_names;
+            grep.regex.as_str().to_string()
+        });
+        self.grep_input = Some(input.unwrap_or_default());
+    }
+
+    // Keys typed after F, repos are filtered as you type and Tab switches to file names
+    fn edit_grep(&mut self, code: KeyCode) {
+        let Some(input) = &mut self.grep_input else {
+            return;
+        };
+
+        match code {
+            KeyCode::Enter => {
+                self.grep_input = None;
+                self.grep_error = None;
+                self.commit_grep();
+                return;
+            }
+            KeyCode::Esc => {
+                self.clear_grep();
+                return;
+            }
+            KeyCode::Tab => self.grep_file_names = !self.grep_file_names,
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => return,
+        }
+
+        let pattern = input.clone();
+        self.grep_error = None;
+        self.filters.grep = if pattern.is_empty() {
+            None
+        } else {
+            match ContentSearch::new(&pattern, self.grep_file_names) {
+                Ok(grep) => Some(grep),
+                Err(e) => {
+                    self.grep_error = Some(e);
+                    None
+                }
+            }
+        };
+        self.update_rows();
+        self.select_row(self.state.selected().unwrap_or_default());
+        self.diff_scroll = 0;
+    }
+
+    // Highlights the search in the diff and scrolls the preview to the first hit
+    fn commit_grep(&mut self) {
+        if let Some(grep) = &self.filters.grep {
+            self.search = Some(grep.regex.clone());
+            self.search_error = None;
+            self.current_match = None;
+        }
+        self.jump_to_hit();
+    }
+
+    fn jump_to_hit(&mut self) {
+        if let Some(grep) = &self.filters.grep
+            && let Some(Row::Repo(i)) = self.selected_row()
+            && let Some(line) = grep.first_hit(&self.items[i].data).and_then(|hit| hit.line)
+            && let Some(diff) = &self.items[i].data.diff
+        {
+            let mut file = 0_usize;
+            let mut hunk = None;
+            for diff_line in diff.text.lines().take(line + 1) {
+                if diff_line.starts_with("diff --git") {
+                    file += 1;
+                    hunk = None;
+                } else if diff_line.starts_with("@@") {
+                    hunk = Some(hunk.map_or(0, |hunk| hunk + 1));
+                }
+            }
+
+            self.diff_jumps.push(DiffJump::Hit(DiffHit {
+                file: file.saturating_sub(1),
+                hunk,
+                regex: grep.regex.clone(),
+            }));
+        }
+    }
+
+    fn clear_grep(&mut self) {
+        self.grep_input = None;
+        self.grep_error = None;
+        self.filters.grep = None;
+        self.clear_search();
+        self.update_rows();
+    }
+
+    // Suspend the TUI while the command runs, then refresh the repo it was opened in
+    fn open_selected(&mut self, terminal: &mut DefaultTerminal, target: OpenTarget) {
+        let Some(Row::Repo(i)) = self.selected_row() else {
+            return;
+        };
+        let repo_path = self.items[i].data.path.clone();
+
+        suspend_terminal();
+        let res = open_repo(target, &repo_path, &self.config.commands);
+        resume_terminal(terminal);
+
+        self.message = res.err().map(|e| e.to_string());
+        self.refresh_repo(i);
+    }
+
+    // Reads the repo again after it was changed, along with the changes being reviewed
+    fn refresh_repo(&mut self, i: usize) {
+        let repo_path = self.items[i].data.path.clone();
+        self.items[i].data = collect_repo_data(&repo_path, self.repo_timeout);
+        self.summary = Summary::new(&self.items, self.summary.scan_stats.clone());
+        if self.filters.grep.is_some() {
+            load_diffs(
+                slice::from_mut(&mut self.items[i]),
+                &self.config.diff.options(),
+                self.repo_timeout,
+            );
+        }
+        if self
+            .diff
+            .as_ref()
+            .is_some_and(|(diff_path, _)| *diff_path == repo_path)
+        {
+            self.diff = None;
+        }
+        self.update_rows();
+
+        // The review stays on its repo, and ends once the repo isn't listed anymore (ex: hidden
+        // once clean) instead of moving on to whichever row took its place
+        if let Some(changes) = &self.changes {
+            let repo_path = changes.repo_path.clone();
+            let listed = self.rows.iter().any(
+                |row| matches!(row, Row::Repo(repo_i) if self.items[*repo_i].data.path == repo_path),
+            );
+            self.load_changes_of(listed.then_some(repo_path));
+            self.select_hunk(Some(self.selected_hunk));
+        }
+    }
+
+    // Diff of the repo, taken from a search that loaded all of it already (searches never diff
+    // words) or loaded up to the max size
+    fn repo_diff(&mut self, i: usize) -> &Diff {
+        let git_data = &self.items[i].data;
+        if self
+            .diff
+            .as_ref()
+            .is_some_and(|(diff_path, _)| *diff_path != git_data.path)
+        {
+            self.diff = None;
+        }
+
+        if self.diff.is_none() {
+            let repo_path = git_data.path.clone();
+            match git_data
+                .diff
+                .clone()
+                .filter(|_| !self.config.diff.word_diff)
+            {
+                Some(searched) => self.diff = Some((repo_path, searched)),
+                None => self.load_repo_diff(repo_path, self.config.diff.max_size()),
+            }
+        }
+
+        let (_, diff) = self.diff.get_or_insert_default();
+        diff
+    }
+
+    // The interactive TUI loads diffs on another thread and shows them once they're received,
+    // frames drawn on their own (--render-once, tests) wait for them instead
+    fn load_repo_diff(&mut self, repo_path: PathBuf, max_size: Option<usize>) {
+        let options = self.config.diff.options();
+        let timeout = self.repo_timeout;
+        let load_path = repo_path.clone();
+        let load = move || {
+            load_diff(&load_path, max_size, &options, timeout).unwrap_or_else(|e| Diff {
+                text: format!("Could not load the diff:\n\n{e}"),
+                truncated: false,
+            })
+        };
+
+        self.pane = None;
+        if !self.background_diffs {
+            self.diff = Some((repo_path, load()));
+            return;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || sender.send(load()));
+        self.loading_diff = Some((repo_path.clone(), receiver));
+        let loading = Diff {
+            text: "Loading the diff...".to_string(),
+            truncated: false,
+        };
+        self.diff = Some((repo_path, loading));
+    }
+
+    // Swaps the loading placeholder for the diff once it's there, returns whether it was
+    fn receive_diff(&mut self) -> bool {
+        let Some((repo_path, receiver)) = &self.loading_diff else {
+            return false;
+        };
+
+        let diff = match receiver.try_recv() {
+            Ok(diff) => diff,
+            Err(TryRecvError::Empty) => return false,
+            // The loading thread panicked, the placeholder would be shown forever otherwise
+            Err(TryRecvError::Disconnected) => Diff {
+                text: "Could not load the diff:\n\nLoading it stopped unexpectedly".to_string(),
+                truncated: false,
+            },
+        };
+
+        // Diffs of repos no longer selected are dropped
+        if self
+            .diff
+            .as_ref()
+            .is_some_and(|(diff_path, _)| diff_path == repo_path)
+        {
+            self.diff = Some((repo_path.clone(), diff));
+            self.pane = None;
+        }
+        self.loading_diff = None;
+        true
+    }
+
+    // Loads the diff of the selected repo again without cutting it off
+    fn load_full_diff(&mut self) {
+        if let Some(Row::Repo(i)) = self.selected_row()
+            && self.changes.is_none()
+            && self.repo_diff(i).truncated
+        {
+            let repo_path = self.items[i].data.path.clone();
+            self.load_repo_diff(repo_path, None);
+        }
+    }
+
+    // Diffs are loaded again with the changed options, along with the ones searched
+    fn change_diff_options(&mut self, change: impl FnOnce(&mut DiffConfig)) {
+        change(&mut self.config.diff);
+        self.diff = None;
+        self.pane = None;
+        self.diff_jumps.clear();
+
+        if self.filters.grep.is_some() {
+            for repo in &mut self.items {
+                repo.data.diff = None;
+            }
+            load_diffs(
+                &mut self.items,
+                &self.config.diff.options(),
+                self.repo_timeout,
+            );
+            self.update_rows();
+        }
+    }
+
+    fn review_changes(&mut self) {
+        self.diff_scroll = 0;
+        if self.changes.take().is_some() {
+            return;
+        }
+
+        self.selected_hunk = 0;
+        self.load_changes();
+        if self.changes.is_some() {
+            self.hover_diff_preview();
+        }
+    }
+
+    // Changes of the selected repo, reviewing stops once a group is selected
+    fn load_changes(&mut self) {
+        let repo_path = match self.selected_row() {
+            Some(Row::Repo(i)) => Some(self.items[i].data.path.clone()),
+            _ => None,
+        };
+        self.load_changes_of(repo_path);
+    }
+
+    fn load_changes_of(&mut self, repo_path: Option<PathBuf>) {
+        self.diff_jumps.clear(); // Lines of the old changes
+        self.pane = None;
+        self.changes =
+            repo_path.and_then(
+                |repo_path| match Changes::load(&repo_path, self.repo_timeout) {
+                    Ok(changes) => Some(changes),
+                    Err(e) => {
+                        self.message = Some(e);
+                        None
+                    }
+                },
+            );
+
+        let count = self
+            .changes
+            .as_ref()
+            .map_or(0, |changes| changes.hunks.len());
+        self.selected_hunk = self.selected_hunk.min(count.saturating_sub(1));
+    }
+
+    fn select_hunk(&mut self, hunk_i: Option<usize>) {
+        if let Some(changes) = &self.changes
+            && let Some(hunk_i) = hunk_i
+            && hunk_i < changes.hunks.len()
+        {
+            let line = changes.start(hunk_i);
+            self.selected_hunk = hunk_i;
+            self.jump_diff(DiffJump::Line(line));
+        }
+    }
+
+    // Stages, unstages or discards the selected hunk (or its file), discards are confirmed first.
+    // Outside of the review the changes are shown first, to see what the keys act on.
+    fn change(&mut self, action: ChangeAction, whole_file: bool) {
+        let Some(changes) = &self.changes else {
+            self.review_changes();
+            return;
+        };
+
+        match changes.check(self.selected_hunk, action) {
+            Ok(_) if action == ChangeAction::Discard => self.confirm = Some((action, whole_file)),
+            Ok(_) => self.apply_change(action, whole_file),
+            Err(e) => self.message = Some(e),
+        }
+    }
+
+    fn apply_change(&mut self, action: ChangeAction, whole_file: bool) {
+        if let Some(changes) = &self.changes {
+            let res = changes.apply(self.selected_hunk, action, whole_file, self.repo_timeout);
+            self.message = Some(res.unwrap_or_else(|e| e));
+
+            let repo_path = &changes.repo_path;
+            if let Some(i) = self
+                .items
+                .iter()
+                .position(|repo| repo.data.path == *repo_path)
+            {
+                self.refresh_repo(i);
+            }
+        }
+    }
+
+    fn start_commit(&mut self) {
+        if let Some(Row::Repo(_)) = self.selected_row() {
+            self.commit_input = Some(String::new());
+        }
+    }
+
+    // Keys typed into the commit message, which is kept if the commit fails
+    fn edit_commit(&mut self, code: KeyCode) {
+        let Some(input) = &mut self.commit_input else {
+            return;
+        };
+
+        match code {
+            KeyCode::Tab => {
+                let message = mem::take(input);
+                self.commit_input = None;
+                if let Some(Row::Repo(i)) = self.selected_row() {
+                    match stage::commit(&self.items[i].data.path, &message, self.repo_timeout) {
+                        Ok(summary) => self.message = Some(format!("Committed {summary}")),
+                        Err(e) => {
+                            self.message = Some(e);
+                            self.commit_input = Some(message);
+                        }
+                    }
+                    self.refresh_repo(i);
+                }
+            }
+            KeyCode::Esc => self.commit_input = None,
+            KeyCode::Enter => input.push('\n'),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
+    }
+
+    fn toggle_group(&mut self) {
+        if let Some(Row::Group(group_i)) = self.selected_row() {
+            let label = &self.groups[group_i].0;
+
+            if !self.collapsed_groups.remove(label) {
+                self.collapsed_groups.insert(label.clone());
+            }
+            self.update_rows();
+        }
+    }
+
+    // Selections are clamped right away instead of on the next render, since keys can be
+    // pressed without rendering in between (--render-keys, zoomed diff)
+    fn select_row(&mut self, i: usize) {
+        self.state
+            .select(Some(i.min(self.rows.len().saturating_sub(1))));
+        self.current_match = None;
+        self.diff_jumps.clear();
+
+        if self.changes.is_some() {
+            self.selected_hunk = 0;
+            self.load_changes();
+        } else {
+            self.jump_to_hit();
         }
     }
 
     fn select_next(&mut self) {
         match self.focused_window {
             FocusedWindow::PathList => {
-                self.state.select_next();
+                self.select_row(self.state.selected().map_or(0, |i| i.saturating_add(1)));
                 self.diff_scroll = 0; // Reset diff scroll when changing selection
             }
             FocusedWindow::DiffPreview => {
@@ -64,7 +739,7 @@ impl App {
     fn select_previous(&mut self) {
         match self.focused_window {
             FocusedWindow::PathList => {
-                self.state.select_previous();
+                self.select_row(self.state.selected().map_or(0, |i| i.saturating_sub(1)));
                 self.diff_scroll = 0;
             }
             FocusedWindow::DiffPreview => {
@@ -76,7 +751,7 @@ impl App {
     fn select_first(&mut self) {
         match self.focused_window {
             FocusedWindow::PathList => {
-                self.state.select_first();
+                self.select_row(0);
                 self.diff_scroll = 0;
             }
             FocusedWindow::DiffPreview => {
@@ -88,7 +763,7 @@ impl App {
     fn select_last(&mut self) {
         match self.focused_window {
             FocusedWindow::PathList => {
-                self.state.select_last();
+                self.select_row(usize::MAX);
                 self.diff_scroll = 0;
             }
             FocusedWindow::DiffPreview => {
@@ -105,102 +780,511 @@ impl App {
         self.focused_window = FocusedWindow::DiffPreview;
     }
 
+    fn resize_split(&mut self, grow: bool) {
+        self.split = if grow {
+            self.split.saturating_add(SPLIT_STEP)
+        } else {
+            self.split.saturating_sub(SPLIT_STEP)
+        }
+        .clamp(MIN_SPLIT, MAX_SPLIT);
+    }
+
+    // Keys typed after /, the diff is searched as you type
+    fn edit_search(&mut self, code: KeyCode) {
+        let Some(input) = &mut self.search_input else {
+            return;
+        };
+
+        match code {
+            KeyCode::Enter => {
+                self.search_input = None;
+                self.current_match = None;
+                self.jump_diff(DiffJump::NextMatch);
+                return;
+            }
+            KeyCode::Esc => {
+                self.clear_search();
+                return;
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => return,
+        }
+
+        let pattern = input.clone();
+        self.set_search(&pattern);
+    }
+
+    fn set_search(&mut self, pattern: &str) {
+        self.current_match = None;
+        self.search_error = None;
+        self.search = None;
+        if pattern.is_empty() {
+            return;
+        }
+
+        match smart_case_regex(pattern) {
+            Ok(search) => self.search = Some(search),
+            Err(e) => self.search_error = Some(e),
+        }
+    }
+
+    fn clear_search(&mut self) {
+        self.search_input = None;
+        self.set_search("");
+    }
+
+    fn jump_diff(&mut self, jump: DiffJump) {
+        self.hover_diff_preview();
+        self.diff_jumps.push(jump);
+    }
+
+    fn toggle_zoom(&mut self) {
+        self.zoomed = !self.zoomed;
+        if self.zoomed {
+            self.hover_diff_preview();
+        }
+    }
+
     pub fn run(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        self.background_diffs = true;
         loop {
             terminal.draw(|frame| self.render(frame))?;
 
-            if let Event::Key(event) = event::read()?
-                && let KeyEventKind::Press = event.kind
-            {
-                match event.code {
-                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
-                    KeyCode::Char('j') | KeyCode::Down => self.select_next(),
-                    KeyCode::Char('k') | KeyCode::Up => self.select_previous(),
-                    KeyCode::Char('h') | KeyCode::Left => self.hover_path_list(),
-                    KeyCode::Char('l') | KeyCode::Right => self.hover_diff_preview(),
-                    KeyCode::Char('g') | KeyCode::PageUp => self.select_first(),
-                    KeyCode::Char('G') | KeyCode::PageDown => self.select_last(),
-                    _ => {}
+            // Diffs loading in the background are drawn as soon as they're received
+            while self.loading_diff.is_some() && !event::poll(DIFF_POLL_INTERVAL)? {
+                if self.receive_diff() {
+                    terminal.draw(|frame| self.render(frame))?;
+                }
+            }
+
+            match event::read()? {
+                Event::Key(event) if event.kind == KeyEventKind::Press => {
+                    match self.handle_key(event.code) {
+                        Some(KeyAction::Quit) => return Ok(()),
+                        Some(KeyAction::Open(target)) => self.open_selected(terminal, target),
+                        None => {}
+                    }
+                }
+                Event::Mouse(event) => self.handle_mouse(event),
+                _ => {}
+            }
+        }
+    }
+
+    // Clicks select rows and focus panes, the wheel scrolls whatever is under the pointer, and
+    // the border between the panes can be dragged. Positions come from the last rendered frame.
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        let position = Position::new(event.column, event.row);
+
+        if self.show_help {
+            if let MouseEventKind::Down(_) = event.kind {
+                self.show_help = false;
+            }
+            return;
+        }
+
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let on_split = if self.stacked {
+                    event.row == self.diff_area.y
+                } else {
+                    event.column.abs_diff(self.diff_area.x) <= 1
+                };
+
+                if !self.zoomed && self.content_area.contains(position) && on_split {
+                    self.dragging_split = true;
+                } else if self.list_area.contains(position) {
+                    self.hover_path_list();
+                    self.click_row(event.row - self.list_area.y);
+                } else if self.diff_area.contains(position) {
+                    self.hover_diff_preview();
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging_split => {
+                let content = self.content_area;
+                let (offset, size) = if self.stacked {
+                    (event.row.saturating_sub(content.y), content.height)
+                } else {
+                    (event.column.saturating_sub(content.x), content.width)
+                };
+
+                if size > 0 {
+                    let split = u32::from(offset) * 100 / u32::from(size);
+                    self.split = u16::try_from(split)
+                        .unwrap_or(MAX_SPLIT)
+                        .clamp(MIN_SPLIT, MAX_SPLIT);
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.dragging_split = false,
+            MouseEventKind::ScrollDown if self.diff_area.contains(position) => {
+                self.diff_scroll = self
+                    .diff_scroll
+                    .saturating_add(SCROLL_LINES)
+                    .min(self.max_diff_scroll);
+            }
+            MouseEventKind::ScrollUp if self.diff_area.contains(position) => {
+                self.diff_scroll = self.diff_scroll.saturating_sub(SCROLL_LINES);
+            }
+            MouseEventKind::ScrollDown if self.list_area.contains(position) => {
+                self.select_row(self.state.selected().map_or(0, |i| i.saturating_add(1)));
+                self.diff_scroll = 0;
+            }
+            MouseEventKind::ScrollUp if self.list_area.contains(position) => {
+                self.select_row(self.state.selected().map_or(0, |i| i.saturating_sub(1)));
+                self.diff_scroll = 0;
+            }
+            _ => {}
+        }
+    }
+
+    // Selects the row shown on a line of the path list, clicking a selected group collapses it
+    fn click_row(&mut self, line: u16) {
+        let i = self.state.offset() + usize::from(line);
+        if i >= self.rows.len() {
+            return;
+        }
+
+        if self.state.selected() == Some(i) {
+            self.toggle_group();
+        } else {
+            self.select_row(i);
+            self.diff_scroll = 0;
+        }
+    }
+
+    // Updates the app for a key press, returning what needs the terminal to be done by the caller.
+    // Keys are collected until they match a binding of the keymap or can't start one anymore.
+    pub fn handle_key(&mut self, code: KeyCode) -> Option<KeyAction> {
+        self.message = None;
+
+        if self.show_help {
+            match code {
+                KeyCode::Char('j') | KeyCode::Down => {
+                    self.help_scroll = self.help_scroll.saturating_add(1);
                 }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    self.help_scroll = self.help_scroll.saturating_sub(1);
+                }
+                _ => self.show_help = false,
+            }
+            return None;
+        }
+
+        if self.search_input.is_some() {
+            self.edit_search(code);
+            return None;
+        }
+        if self.grep_input.is_some() {
+            self.edit_grep(code);
+            return None;
+        }
+        if self.commit_input.is_some() {
+            self.edit_commit(code);
+            return None;
+        }
+        if let Some((action, whole_file)) = self.confirm.take() {
+            if code == KeyCode::Char('y') {
+                self.apply_change(action, whole_file);
+            }
+            return None;
+        }
+
+        if code == KeyCode::Esc && !self.pending_keys.is_empty() {
+            self.pending_keys.clear();
+            return None;
+        }
+        // The search highlighting a committed grep goes away with it
+        let grep_search = self
+            .filters
+            .grep
+            .as_ref()
+            .zip(self.search.as_ref())
+            .is_some_and(|(grep, search)| grep.regex.as_str() == search.as_str());
+        if code == KeyCode::Esc
+            && (self.search.is_some() || self.search_error.is_some())
+            && !grep_search
+        {
+            self.clear_search();
+            return None;
+        }
+        if code == KeyCode::Esc && self.filters.grep.is_some() {
+            self.clear_grep();
+            return None;
+        }
+        if code == KeyCode::Esc && self.changes.is_some() {
+            self.review_changes();
+            return None;
+        }
+        if code == KeyCode::Esc && self.zoomed {
+            self.zoomed = false;
+            return None;
+        }
+
+        self.pending_keys.push(code);
+        match self.config.keymap.lookup(&self.pending_keys) {
+            Lookup::Action(action) => {
+                self.pending_keys.clear();
+                self.run_action(action)
+            }
+            Lookup::Pending => None,
+            Lookup::None => {
+                // The key didn't continue the binding, but may be one on its own
+                let retry = self.pending_keys.len() > 1;
+                self.pending_keys.clear();
+                if retry { self.handle_key(code) } else { None }
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: Action) -> Option<KeyAction> {
+        match action {
+            Action::Quit => return Some(KeyAction::Quit),
+            Action::SelectNext => self.select_next(),
+            Action::SelectPrevious => self.select_previous(),
+            Action::FocusList => self.hover_path_list(),
+            Action::FocusDiff => self.hover_diff_preview(),
+            Action::SelectFirst => self.select_first(),
+            Action::SelectLast => self.select_last(),
+            Action::CycleSort => self.cycle_sort(),
+            Action::CycleGroup => self.cycle_group(),
+            Action::ToggleGroup => self.toggle_group(),
+            Action::Filter(filter) => self.toggle_filter(filter),
+            Action::ToggleHideClean => self.toggle_hide_clean(),
+            Action::ClearFilters => self.clear_filters(),
+            Action::SearchRepos => self.start_grep(),
+            Action::ShrinkSplit => self.resize_split(false),
+            Action::GrowSplit => self.resize_split(true),
+            Action::CycleOrientation => self.orientation = self.orientation.next(),
+            Action::ToggleZoom => self.toggle_zoom(),
+            Action::ToggleFooter => self.show_footer = !self.show_footer,
+            Action::SearchDiff => {
+                self.hover_diff_preview();
+                self.search_input = Some(String::new());
+            }
+            Action::NextMatch => self.jump_diff(DiffJump::NextMatch),
+            Action::PreviousMatch => self.jump_diff(DiffJump::PreviousMatch),
+            Action::NextHunk if self.changes.is_some() => {
+                self.select_hunk(self.selected_hunk.checked_add(1));
+            }
+            Action::PreviousHunk if self.changes.is_some() => {
+                self.select_hunk(self.selected_hunk.checked_sub(1));
+            }
+            Action::NextFile | Action::PreviousFile if let Some(changes) = &self.changes => {
+                let hunk_i = changes.file_after(self.selected_hunk, action == Action::NextFile);
+                self.select_hunk(hunk_i);
             }
+            Action::NextHunk => self.jump_diff(DiffJump::NextHunk),
+            Action::PreviousHunk => self.jump_diff(DiffJump::PreviousHunk),
+            Action::NextFile => self.jump_diff(DiffJump::NextFile),
+            Action::PreviousFile => self.jump_diff(DiffJump::PreviousFile),
+            Action::LoadFullDiff => self.load_full_diff(),
+            Action::ToggleIgnoreWhitespace => self.change_diff_options(|diff| {
+                diff.ignore_whitespace = !diff.ignore_whitespace;
+            }),
+            Action::ToggleIgnoreBlankLines => self.change_diff_options(|diff| {
+                diff.ignore_blank_lines = !diff.ignore_blank_lines;
+            }),
+            // Counted from git's default of 3 lines
+            Action::MoreContext => self.change_diff_options(|diff| {
+                diff.context = Some(diff.context.unwrap_or(3) + 1);
+            }),
+            Action::LessContext => self.change_diff_options(|diff| {
+                diff.context = Some(diff.context.unwrap_or(3).saturating_sub(1));
+            }),
+            Action::CycleRenames => self.change_diff_options(|diff| {
+                diff.renames = diff.renames.next();
+            }),
+            Action::ToggleWordDiff => self.change_diff_options(|diff| {
+                diff.word_diff = !diff.word_diff;
+            }),
+            Action::CycleDiffAlgorithm => self.change_diff_options(|diff| {
+                diff.algorithm = diff.algorithm.next();
+            }),
+            Action::ToggleDiffStat => {
+                self.show_diff_stat = !self.show_diff_stat;
+                self.diff_scroll = 0;
+            }
+            Action::ReviewChanges => self.review_changes(),
+            Action::StageHunk => self.change(ChangeAction::Stage, false),
+            Action::StageFile => self.change(ChangeAction::Stage, true),
+            Action::UnstageHunk => self.change(ChangeAction::Unstage, false),
+            Action::UnstageFile => self.change(ChangeAction::Unstage, true),
+            Action::DiscardHunk => self.change(ChangeAction::Discard, false),
+            Action::DiscardFile => self.change(ChangeAction::Discard, true),
+            Action::Commit => self.start_commit(),
+            Action::Help => {
+                self.show_help = true;
+                self.help_scroll = 0;
+            }
+            Action::OpenEditor => return Some(KeyAction::Open(OpenTarget::Editor)),
+            Action::OpenShell => return Some(KeyAction::Open(OpenTarget::Shell)),
+            Action::OpenGitClient => return Some(KeyAction::Open(OpenTarget::GitClient)),
+            Action::OpenBrowser => return Some(KeyAction::Open(OpenTarget::Browser)),
         }
+
+        None
     }
 
-    fn render(&mut self, frame: &mut Frame<'_>) {
+    pub fn render(&mut self, frame: &mut Frame<'_>) {
         let rect = frame.area();
+
+        if self.zoomed {
+            self.content_area = rect;
+            self.list_area = Rect::default();
+            self.diff_area = rect;
+            self.render_diff_window(frame, rect);
+            self.render_help(frame, rect);
+            self.render_prompt(frame, rect);
+            return;
+        }
+
+        let footer_lines = self.footer_lines(rect.width.saturating_sub(2), rect.height / 4);
+        let footer_height = if self.show_footer {
+            footer_lines.len() as u16 + 2
+        } else {
+            0
+        };
         let layout = Layout::vertical([
-            Constraint::Length(3), // Header
-            Constraint::Fill(1),   // Content
-            Constraint::Length(4), // Footer
+            Constraint::Length(4),             // Header
+            Constraint::Fill(1),               // Content
+            Constraint::Length(footer_height), // Footer
         ])
         .split(rect);
 
-        let list_layout = Layout::horizontal([
-            Constraint::Percentage(50), // Path list
-            Constraint::Percentage(50), // Diff preview
-        ])
+        self.stacked = match self.orientation {
+            Orientation::Auto => rect.width < self.config.layout.stack_below,
+            Orientation::Horizontal => false,
+            Orientation::Vertical => true,
+        };
+        let direction = if self.stacked {
+            Direction::Vertical
+        } else {
+            Direction::Horizontal
+        };
+        let list_layout = Layout::new(
+            direction,
+            [
+                Constraint::Percentage(self.split),       // Path list
+                Constraint::Percentage(100 - self.split), // Diff preview
+            ],
+        )
         .split(layout[1]);
+        self.content_area = layout[1];
+        self.list_area = list_layout[0];
+        self.diff_area = list_layout[1];
 
         self.render_header(frame, layout[0]);
         self.render_list(frame, list_layout[0]);
         self.render_diff_window(frame, list_layout[1]);
-        self.render_footer(frame, layout[2]);
+        if self.show_footer {
+            self.render_footer(frame, layout[2], footer_lines);
+        }
+        self.render_help(frame, rect);
+        self.render_prompt(frame, rect);
     }
 
     fn render_header(&mut self, frame: &mut Frame<'_>, area: Rect) {
-        let header = Paragraph::new(format!("Viewing git repos in {}", self.base_path))
-            .centered()
-            .block(Block::bordered().border_type(BorderType::Rounded))
-            .wrap(Wrap { trim: false });
+        let shown = self
+            .groups
+            .iter()
+            .map(|(_, indices)| indices.len())
+            .sum::<usize>();
+        let summary = &self.summary;
+
+        let mut counts = Line::raw(format!("Showing {shown}/{}: ", summary.total));
+        for (state, count) in &summary.state_counts {
+            counts.push_span(Span::styled(
+                format!("{count} {}", state.short_label()),
+                self.config.styles.state(*state),
+            ));
+            counts.push_span(" | ");
+        }
+        counts.push_span(summary.totals());
+
+        let header = Paragraph::new(vec![
+            Line::raw(format!(
+                "Viewing git repos in {} (sorted by {}, grouped by {}, filtered by {})",
+                self.base_path,
+                self.sort_by.label(),
+                self.group_by.label(),
+                self.filters.label()
+            )),
+            counts,
+        ])
+        .centered();
+
+        let mut block = Block::bordered().border_type(BorderType::Rounded);
+        if let Some(input) = &self.grep_input {
+            let (target, other) = if self.grep_file_names {
+                ("file names", "changes")
+            } else {
+                ("changes", "file names")
+            };
+            let error = self
+                .grep_error
+                .as_ref()
+                .map(|e| format!(" ({e})"))
+                .unwrap_or_default();
+            block = block.title_bottom(
+                Line::raw(format!(" Search {target}: {input}█{error} (Tab: {other}) ")).centered(),
+            );
+        }
+        let header = header.block(block).wrap(Wrap { trim: false });
 
         frame.render_widget(header, area);
     }
 
     fn render_list(&mut self, frame: &mut Frame<'_>, area: Rect) {
+        let indent = if self.group_by == GroupBy::None {
+            ""
+        } else {
+            "  "
+        };
         let list_items = self
-            .items
+            .rows
             .iter()
-            .map(|(repo_path, git_data)| {
-                let mut text = Text::raw(format!("{repo_path} .. "));
-                if git_data.status.contains("nothing to commit")
-                    && !git_data.status.contains("branch is ahead")
-                {
-                    if git_data.diff.is_empty() {
-                        text.push_span(Span::styled(
-                            "CLEAN",
-                            Style::new().fg(Color::Green).add_modifier(Modifier::ITALIC),
-                        ))
+            .map(|row| match row {
+                Row::Group(group_i) => {
+                    let (label, indices) = &self.groups[*group_i];
+                    let symbol = if self.collapsed_groups.contains(label) {
+                        "▸"
                     } else {
-                        // edge case: no upstream branch, but there are local commits not pushed
+                        "▾"
+                    };
+
+                    ListItem::new(Line::styled(
+                        format!("{symbol} {label} ({})", indices.len()),
+                        Style::new().add_modifier(Modifier::BOLD),
+                    ))
+                }
+                Row::Repo(repo_i) => {
+                    let Repo {
+                        name: repo_path,
+                        data: git_data,
+                    } = &self.items[*repo_i];
+                    let state = git_data.state();
+
+                    let mut text = Text::raw(format!("{indent}{repo_path} .. "));
+                    text.push_span(Span::styled(state.label(), self.config.styles.state(state)));
+                    if !git_data.diff_stat.is_empty() {
+                        // Diffstat badge, ex: +120 −34
+                        text.push_span(" ");
+                        text.push_span(Span::styled(
+                            format!("+{}", git_data.insertions()),
+                            self.config.styles.diff_add,
+                        ));
+                        text.push_span(" ");
                         text.push_span(Span::styled(
-                            "DIRTY (changes committed, not pushed)",
-                            Style::new().fg(Color::LightRed).bold(),
-                        ))
+                            format!("−{}", git_data.deletions()),
+                            self.config.styles.diff_del,
+                        ));
                     }
-                } else if git_data.status.contains("no changes added to commit")
-                    || git_data.status.contains("untracked")
-                {
-                    text.push_span(Span::styled(
-                        "DIRTY (changes not added)",
-                        Style::new().fg(Color::Rgb(255, 184, 108)), // orange
-                    ))
-                } else if git_data.status.contains("Changes to be committed") {
-                    text.push_span(Span::styled(
-                        "DIRTY (changes added, not committed)",
-                        Style::new().fg(Color::Red),
-                    ))
-                } else if git_data.status.contains("branch is ahead")
-                    || git_data.status.contains("diverged")
-                {
-                    text.push_span(Span::styled(
-                        "DIRTY (changes committed, not pushed)",
-                        Style::new().fg(Color::LightRed).bold(),
-                    ))
-                } else {
-                    text.push_span(Span::styled("UNKNOWN", Style::new().fg(Color::Yellow)))
-                };
 
-                ListItem::new(text)
+                    ListItem::new(text)
+                }
             })
             .collect::<Vec<_>>();
 
@@ -215,61 +1299,754 @@ impl App {
         StatefulWidget::render(list, area, frame.buffer_mut(), &mut self.state);
     }
 
+    // Lines of what the diff pane shows for the selection, with the style of each
+    fn pane_lines(&mut self) -> Vec<(String, Style)> {
+        let plain = |text: &str| {
+            text.lines()
+                .map(|line| (line.to_string(), Style::default()))
+                .collect()
+        };
+
+        match self.selected_row() {
+            Some(Row::Repo(i)) => {
+                if let Some(error) = &self.items[i].data.error {
+                    return plain(&format!("Could not read repo:\n\n{error}"));
+                }
+                let theme = self.config.styles.clone();
+
+                if let Some(changes) = &self.changes {
+                    let title = Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+                    return changes
+                        .lines
+                        .iter()
+                        .enumerate()
+                        .map(|(i, line)| {
+                            let style = if changes.titles.contains(&i) {
+                                title
+                            } else {
+                                diff_line_style(line, &theme)
+                            };
+                            (line.clone(), style)
+                        })
+                        .collect();
+                }
+
+                let load_full_diff = self.config.keymap.label(Action::LoadFullDiff);
+                let max_size = self.config.diff.max_size;
+                let diff = self.repo_diff(i);
+                let mut lines = diff
+                    .text
+                    .lines()
+                    .map(|line| (line.to_string(), diff_line_style(line, &theme)))
+                    .collect::<Vec<_>>();
+
+                if diff.truncated {
+                    let load = if load_full_diff.is_empty() {
+                        format!("bind {} to load all of it", Action::LoadFullDiff.name())
+                    } else {
+                        format!("press {load_full_diff} to load all of it")
+                    };
+                    lines.push((
+                        format!("Diff cut off at {max_size} bytes, {load}"),
+                        Style::new().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+                    ));
+                }
+                lines
+            }
+            Some(Row::Group(i)) => {
+                let (label, indices) = &self.groups[i];
+                let summary = indices.iter().fold(
+                    format!("{label} ({} repos)\n", indices.len()),
+                    |summary, &repo_i| {
+                        let Repo {
+                            name: repo_path,
+                            data: git_data,
+                        } = &self.items[repo_i];
+                        format!("{summary}\n{repo_path} .. {}", git_data.state().label())
+                    },
+                );
+                plain(&summary)
+            }
+            None => plain("Nothing selected..."),
+        }
+    }
+
+    // Pane for the current selection at this width, built again only when something changed
+    fn take_pane(&mut self, width: u16) -> Pane {
+        let diff_stat = match self.selected_row() {
+            Some(Row::Repo(i))
+                if self.show_diff_stat
+                    && self.changes.is_none()
+                    && self.items[i].data.error.is_none() =>
+            {
+                Some(i)
+            }
+            _ => None,
+        };
+        let key = (
+            self.selected_row(),
+            self.changes.is_some(),
+            diff_stat.is_some(),
+        );
+        let mut pane = match self.pane.take() {
+            // Bars are scaled to the width
+            Some(pane) if pane.key == key && (diff_stat.is_none() || pane.width == width) => pane,
+            _ => {
+                let (lines, marks) = match diff_stat {
+                    Some(i) => diff_stat_lines(&self.items[i].data, width, &self.config.styles),
+                    None => {
+                        let lines = self.pane_lines();
+                        let marks = match self.selected_row() {
+                            Some(Row::Repo(_))
+                                if self.config.diff.word_diff && self.changes.is_none() =>
+                            {
+                                word_diff_marks(&lines, &self.config.styles)
+                            }
+                            _ => Vec::new(),
+                        };
+                        (lines, marks)
+                    }
+                };
+                Pane {
+                    key,
+                    lines,
+                    marks,
+                    ..Pane::default()
+                }
+            }
+        };
+
+        if pane.width != width || pane.rows.is_empty() {
+            pane.width = width;
+            pane.rows = Vec::with_capacity(pane.lines.len() + 1);
+            let mut row = 0;
+            for (line, _) in &pane.lines {
+                pane.rows.push(row);
+                // Most lines fit, wrapping them is only worked out for the ones that don't
+                row += if line.width() <= usize::from(width) {
+                    1
+                } else {
+                    Paragraph::new(line.as_str())
+                        .wrap(Wrap { trim: false })
+                        .line_count(width)
+                        .max(1)
+                };
+            }
+            pane.rows.push(row);
+        }
+
+        let search = self
+            .search
+            .as_ref()
+            .map(|search| search.as_str().to_string());
+        if pane.search != search {
+            pane.matches = self.search.as_ref().map_or_else(Vec::new, |search| {
+                pane.lines
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, (line, _))| search.find_iter(line).map(move |m| (i, m.range())))
+                    .collect()
+            });
+            pane.search = search;
+        }
+
+        pane
+    }
+
     fn render_diff_window(&mut self, frame: &mut Frame<'_>, area: Rect) {
-        let diff_str = if let Some(i) = self.state.selected() {
-            self.max_diff_scroll = self.items[i].1.diff.lines().count() as u16;
-            &self.items[i].1.diff
-        } else {
-            "Nothing selected..."
+        let width = area.width.saturating_sub(4); // Borders and padding
+        let pane = self.take_pane(width);
+        let rows = &pane.rows;
+        let total_rows = rows.last().copied().unwrap_or_default();
+        // Line at the top of the pane, the last line if it's scrolled past the end
+        let top_line = |scroll: u16| {
+            rows.partition_point(|&row| row <= usize::from(scroll))
+                .saturating_sub(1)
         };
 
-        let colored_diff = {
-            let lines: Vec<Line<'_>> = diff_str
-                .lines()
-                .map(|line| {
-                    let style = match line.chars().next() {
-                        Some('+') => Style::default().fg(Color::Green),
-                        Some('-') => Style::default().fg(Color::Red),
-                        Some('@') => Style::default().add_modifier(Modifier::ITALIC),
-                        Some('d') if line.starts_with("diff --git") => {
-                            Style::default().add_modifier(Modifier::BOLD)
-                        }
-                        Some('i') if line.starts_with("index") => {
-                            Style::default().add_modifier(Modifier::BOLD)
-                        }
-                        _ => Style::default(),
+        if !self.diff_jumps.is_empty() {
+            let lines = pane
+                .lines
+                .iter()
+                .map(|(line, _)| line.as_str())
+                .collect::<Vec<_>>();
+
+            for jump in mem::take(&mut self.diff_jumps) {
+                let top = top_line(self.diff_scroll);
+                let target = jump_target(jump, &lines, &pane.matches, top, &mut self.current_match);
+
+                if let Some(target) = target {
+                    self.diff_scroll = u16::try_from(rows[target]).unwrap_or(u16::MAX);
+                }
+            }
+        }
+
+        // Only the lines in view are turned into spans
+        let theme = &self.config.styles;
+        let selected_hunk = self
+            .changes
+            .as_ref()
+            .and_then(|changes| changes.hunks.get(self.selected_hunk));
+        let top = top_line(self.diff_scroll);
+        let bottom = rows
+            .partition_point(|&row| row < usize::from(self.diff_scroll) + usize::from(area.height))
+            .min(pane.lines.len());
+        let first_match = pane.matches.partition_point(|(line_i, _)| *line_i < top);
+        let mut matches = pane.matches.iter().enumerate().skip(first_match).peekable();
+        let first_mark = pane.marks.partition_point(|(line_i, _, _)| *line_i < top);
+        let mut marks = pane.marks[first_mark..].iter().peekable();
+        let visible_lines = (top..bottom)
+            .map(|i| {
+                let (line, style) = &pane.lines[i];
+                let style = match selected_hunk {
+                    Some(hunk) if hunk.range().contains(&i) => style.patch(theme.selected_hunk),
+                    _ => *style,
+                };
+
+                // Marks and then matches are split out of the line into spans styled on top
+                let mut parts = Vec::new();
+                while let Some((_, range, mark)) = marks.next_if(|(line_i, _, _)| *line_i == i) {
+                    parts.push((range.clone(), *mark));
+                }
+                while let Some((match_i, (_, range))) =
+                    matches.next_if(|(_, (line_i, _))| *line_i == i)
+                {
+                    let highlight = if self.current_match == Some(match_i) {
+                        theme.search_current
+                    } else {
+                        theme.search_match
                     };
+                    parts.push((range.clone(), highlight));
+                }
 
-                    Line::from(Span::styled(line.to_string(), style))
-                })
-                .collect();
+                Line::from(styled_spans(line, style, &parts))
+            })
+            .collect::<Text<'_>>();
+        let match_count = pane.matches.len();
+        let skip_rows = usize::from(self.diff_scroll)
+            .saturating_sub(rows.get(top).copied().unwrap_or_default());
+
+        let search_status = if let Some(input) = &self.search_input {
+            let error = self
+                .search_error
+                .as_ref()
+                .map(|error| format!(" ({error})"))
+                .unwrap_or_default();
+            format!(" /{input}█{error} ")
+        } else if let Some(error) = &self.search_error {
+            format!(" Invalid search: {error} ")
+        } else if let Some(search) = &self.search {
+            match (self.current_match, match_count) {
+                (_, 0) => format!(" /{} no matches ", search.as_str()),
+                (Some(current), count) => format!(" /{} {}/{count} ", search.as_str(), current + 1),
+                (None, count) => format!(" /{} {count} matches ", search.as_str()),
+            }
+        } else {
+            String::new()
+        };
+
+        let paragraph = Paragraph::new(visible_lines).wrap(Wrap { trim: false });
+        self.max_diff_scroll = u16::try_from(total_rows).unwrap_or(u16::MAX);
 
-            Text::from(lines)
+        // What the stage keys act on, or how the last action went
+        let status = if let Some(message) = &self.message {
+            format!(" {message} ")
+        } else if let Some(changes) = &self.changes
+            && let Some(hunk) = selected_hunk
+        {
+            let kind = if hunk.lines.is_empty() {
+                "file"
+            } else {
+                "hunk of"
+            };
+            format!(
+                " {} {kind} {} ({}/{}) ",
+                hunk.section.label(),
+                hunk.file,
+                self.selected_hunk + 1,
+                changes.hunks.len()
+            )
+        } else {
+            String::new()
+        };
+        // Options the diff was loaded with, ex: Diff Preview (-w -U5)
+        let options = self.config.diff.options().args();
+        let title = if self.changes.is_some() {
+            " Changes ".to_string()
+        } else if pane.key.2 {
+            " Diffstat ".to_string()
+        } else if options.is_empty() {
+            " Diff Preview ".to_string()
+        } else {
+            format!(" Diff Preview ({}) ", options.join(" "))
         };
 
-        Paragraph::new(colored_diff)
+        paragraph
             .block(
                 Block::new()
-                    .title(Line::raw(" Diff Preview ").centered().add_modifier(
-                        match self.focused_window {
-                            FocusedWindow::PathList => Modifier::HIDDEN,
-                            FocusedWindow::DiffPreview => Modifier::BOLD,
-                        },
-                    ))
+                    .title(
+                        Line::raw(title)
+                            .centered()
+                            .add_modifier(match self.focused_window {
+                                FocusedWindow::PathList => Modifier::HIDDEN,
+                                FocusedWindow::DiffPreview => Modifier::BOLD,
+                            }),
+                    )
+                    .title_bottom(Line::raw(search_status).bold())
+                    .title_bottom(Line::raw(status).right_aligned())
                     .borders(Borders::ALL)
                     .border_set(border::ROUNDED)
                     .padding(Padding::horizontal(1)),
             )
-            .scroll((self.diff_scroll, 0))
-            .wrap(Wrap { trim: false })
+            .scroll((u16::try_from(skip_rows).unwrap_or(u16::MAX), 0))
             .render(area, frame.buffer_mut());
+
+        self.pane = Some(pane);
+    }
+
+    // Key hints of the keymap packed into as few lines as fit the width, at most max_lines
+    fn footer_lines(&self, width: u16, max_lines: u16) -> Vec<String> {
+        let mut hints = self.config.keymap.hints();
+        hints.insert(
+            hints.len().saturating_sub(1),
+            "(mouse) click/scroll/drag".to_string(),
+        );
+
+        loop {
+            let mut lines: Vec<String> = Vec::new();
+            for hint in &hints {
+                match lines.last_mut() {
+                    Some(line) if line.width() + hint.width() + 3 <= usize::from(width) => {
+                        line.push_str(" | ");
+                        line.push_str(hint);
+                    }
+                    _ => lines.push(hint.clone()),
+                }
+            }
+
+            if lines.len() <= usize::from(max_lines.max(1)) || hints.len() < 2 {
+                return lines;
+            }
+            hints.remove(hints.len() - 2);
+        }
     }
 
-    fn render_footer(&mut self, frame: &mut Frame<'_>, area: Rect) {
-        let footer = Paragraph::new(Text::from_iter(KEYBINDS))
+    fn render_footer(&mut self, frame: &mut Frame<'_>, area: Rect, lines: Vec<String>) {
+        let scan = format!(" {} ", self.summary.scan_stats);
+        let mut block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title_bottom(Line::raw(scan).right_aligned().dim());
+        if !self.pending_keys.is_empty() {
+            let pending = keys_label(&self.pending_keys);
+            block = block.title(Line::raw(format!(" {pending}… ")).bold());
+        }
+
+        let footer = Paragraph::new(Text::from_iter(lines))
             .centered()
-            .block(Block::bordered().border_type(BorderType::Rounded));
+            .block(block);
 
         frame.render_widget(footer, area);
     }
+
+    // Confirmation of a discard or the commit message editor, drawn over the panes
+    fn render_prompt(&self, frame: &mut Frame<'_>, area: Rect) {
+        let (title, text, hint) = if let Some(input) = &self.commit_input {
+            (
+                " Commit message ",
+                format!("{input}█"),
+                " Enter: new line | Tab: commit | Esc: cancel ",
+            )
+        } else if let Some((_, whole_file)) = self.confirm
+            && let Some(changes) = &self.changes
+            && let Some(hunk) = changes.hunks.get(self.selected_hunk)
+        {
+            let question = if hunk.section == Section::Untracked {
+                format!("Delete the untracked file {}?", hunk.file)
+            } else if whole_file || hunk.lines.is_empty() {
+                format!("Discard all unstaged changes of {}?", hunk.file)
+            } else {
+                format!("Discard this hunk of {}?", hunk.file)
+            };
+            (
+                " Discard ",
+                format!("{question} This can't be undone."),
+                " y: discard | any other key: keep ",
+            )
+        } else {
+            return;
+        };
+
+        let width = area.width.min(76);
+        let paragraph = Paragraph::new(text).wrap(Wrap { trim: false });
+        let height = u16::try_from(paragraph.line_count(width.saturating_sub(4)))
+            .unwrap_or(u16::MAX)
+            .saturating_add(2);
+        let popup = area.clamp(Rect::new(
+            area.x + area.width.saturating_sub(width) / 2,
+            area.y + area.height.saturating_sub(height) / 2,
+            width,
+            height,
+        ));
+
+        frame.render_widget(Clear, popup);
+        frame.render_widget(
+            paragraph.block(
+                Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .title(Line::raw(title).centered().bold())
+                    .title_bottom(Line::raw(hint).centered().dim())
+                    .padding(Padding::horizontal(1)),
+            ),
+            popup,
+        );
+    }
+
+    // Every action with its current keys, drawn over the panes
+    fn render_help(&mut self, frame: &mut Frame<'_>, area: Rect) {
+        if !self.show_help {
+            return;
+        }
+
+        let keymap = &self.config.keymap;
+        let keys = Action::ALL
+            .iter()
+            .map(|&action| {
+                let label = keymap.label(action);
+                if label.is_empty() {
+                    "-".to_string()
+                } else {
+                    label
+                }
+            })
+            .collect::<Vec<_>>();
+        let keys_width = keys.iter().map(|keys| keys.width()).max().unwrap_or(0);
+
+        let lines = Action::ALL
+            .iter()
+            .zip(&keys)
+            .map(|(action, keys)| {
+                let padding = " ".repeat(keys_width - keys.width());
+                Line::from(vec![
+                    Span::styled(format!("{padding}{keys}"), Style::new().bold()),
+                    Span::raw(format!("  {}", action.description())),
+                    Span::styled(format!(" ({})", action.name()), Style::new().dim()),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        let width = lines
+            .iter()
+            .map(Line::width)
+            .max()
+            .unwrap_or(0)
+            .saturating_add(4);
+        let popup = area.clamp(Rect::new(
+            area.x + area.width.saturating_sub(width as u16) / 2,
+            area.y + area.height.saturating_sub(lines.len() as u16 + 2) / 2,
+            width as u16,
+            lines.len() as u16 + 2,
+        ));
+
+        let max_scroll = (lines.len() as u16 + 2).saturating_sub(popup.height);
+        self.help_scroll = self.help_scroll.min(max_scroll);
+
+        let help = Paragraph::new(lines)
+            .block(
+                Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .title(Line::raw(" Keys ").centered().bold())
+                    .title_bottom(
+                        Line::raw(" (j/k) scroll, any other key closes ")
+                            .centered()
+                            .dim(),
+                    )
+                    .padding(Padding::horizontal(1)),
+            )
+            .scroll((self.help_scroll, 0));
+
+        frame.render_widget(Clear, popup);
+        frame.render_widget(help, popup);
+    }
+}
+
+// Line a jump scrolls to from the top line of the pane, n/N continue from the current match
+fn jump_target(
+    jump: DiffJump,
+    lines: &[&str],
+    matches: &[(usize, Range<usize>)],
+    top: usize,
+    current_match: &mut Option<usize>,
+) -> Option<usize> {
+    match jump {
+        DiffJump::NextMatch | DiffJump::PreviousMatch if !matches.is_empty() => {
+            let count = matches.len();
+            let current = match (*current_match, &jump) {
+                (Some(i), DiffJump::NextMatch) if i < count => (i + 1) % count,
+                (Some(i), _) if i < count => (i + count - 1) % count,
+                (_, DiffJump::NextMatch) => matches
+                    .iter()
+                    .position(|(line, _)| *line >= top)
+                    .unwrap_or(0),
+                _ => matches
+                    .iter()
+                    .rposition(|(line, _)| *line < top)
+                    .unwrap_or(count - 1),
+            };
+            *current_match = Some(current);
+            Some(matches[current].0)
+        }
+        DiffJump::NextHunk => next_line(lines, top, "@@"),
+        DiffJump::PreviousHunk => previous_line(lines, top, "@@"),
+        DiffJump::NextFile => next_line(lines, top, "diff --git"),
+        DiffJump::PreviousFile => previous_line(lines, top, "diff --git"),
+        DiffJump::Line(line) if line < lines.len() => {
+            *current_match = matches
+                .iter()
+                .position(|(match_line, _)| *match_line >= line);
+            Some(line)
+        }
+        DiffJump::Hit(hit) => {
+            let line = hit_line(&hit, lines)?;
+            *current_match = matches
+                .iter()
+                .position(|(match_line, _)| *match_line >= line);
+            Some(line)
+        }
+        DiffJump::NextMatch | DiffJump::PreviousMatch | DiffJump::Line(_) => None,
+    }
+}
+
+// First changed line of the hit's hunk that matches, the hunk or file header when none does
+fn hit_line(hit: &DiffHit, lines: &[&str]) -> Option<usize> {
+    let nth_after = |start: usize, end: usize, prefix: &str, n: usize| {
+        (start..end)
+            .filter(|&i| lines[i].starts_with(prefix))
+            .nth(n)
+    };
+    let next_after = |start: usize, prefix: &str| {
+        (start + 1..lines.len())
+            .find(|&i| lines[i].starts_with(prefix))
+            .unwrap_or(lines.len())
+    };
+
+    let file = nth_after(0, lines.len(), "diff --git", hit.file)?;
+    let Some(hunk) = hit.hunk else {
+        return Some(file);
+    };
+    let file_end = next_after(file, "diff --git");
+    let Some(hunk) = nth_after(file, file_end, "@@", hunk) else {
+        return Some(file);
+    };
+    let hunk_end = next_after(hunk, "@@").min(file_end);
+
+    // Changed lines start with + or -, or have [-removed-] or {+added+} words in word diffs
+    let changed =
+        |line: &str| line.starts_with(['+', '-']) || line.contains("[-") || line.contains("{+");
+    let hit_line =
+        (hunk + 1..hunk_end).find(|&i| changed(lines[i]) && hit.regex.is_match(lines[i]));
+    Some(hit_line.unwrap_or(hunk))
+}
+
+// Style of a diff line by how it starts, ex: + for added lines
+fn diff_line_style(line: &str, theme: &Theme) -> Style {
+    match line.chars().next() {
+        Some('+') => theme.diff_add,
+        Some('-') => theme.diff_del,
+        Some('@') => theme.hunk_header,
+        Some('d' | 'i') if line.starts_with("diff --git") || line.starts_with("index") => {
+            theme.diff_header
+        }
+        _ => Style::default(),
+    }
+}
+
+// Spans of a line with parts of it styled on top of its style, in order where they overlap
+fn styled_spans<'line>(
+    line: &'line str,
+    style: Style,
+    parts: &[(Range<usize>, Style)],
+) -> Vec<Span<'line>> {
+    let mut cuts = vec![0, line.len()];
+    cuts.extend(parts.iter().flat_map(|(range, _)| [range.start, range.end]));
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    cuts.windows(2)
+        .map(|cut| {
+            let style = parts
+                .iter()
+                .filter(|(range, _)| range.start <= cut[0] && cut[1] <= range.end)
+                .fold(style, |style, (_, part)| style.patch(*part));
+            Span::styled(&line[cut[0]..cut[1]], style)
+        })
+        .collect()
+}
+
+// Removed and added words of --word-diff=plain lines to color, ex: [-old-]{+new+}
+fn word_diff_marks(lines: &[(String, Style)], theme: &Theme) -> Vec<Mark> {
+    let mut marks = Vec::new();
+    for (i, (line, _)) in lines.iter().enumerate() {
+        let mut rest = 0;
+        while let Some((start, close, style)) =
+            [("[-", "-]", theme.diff_del), ("{+", "+}", theme.diff_add)]
+                .into_iter()
+                .filter_map(|(open, close, style)| {
+                    Some((rest + line[rest..].find(open)?, close, style))
+                })
+                .min_by_key(|(start, _, _)| *start)
+        {
+            let Some(end) = line[start + 2..].find(close).map(|end| start + 2 + end + 2) else {
+                break;
+            };
+            marks.push((i, start..end, style));
+            rest = end;
+        }
+    }
+    marks
+}
+
+// Changed files with bars scaled to the width like git diff --stat, and the bars to color:
+// src/lib.rs | 12 ++++++++----
+fn diff_stat_lines(
+    git_data: &GitData,
+    width: u16,
+    theme: &Theme,
+) -> (Vec<(String, Style)>, Vec<Mark>) {
+    let file_stats = git_data.file_stats();
+    if file_stats.is_empty() {
+        let line = "No changes against upstream".to_string();
+        return (vec![(line, Style::default())], Vec::new());
+    }
+
+    let width = usize::from(width);
+    let counts = file_stats
+        .iter()
+        .map(|file_stat| match file_stat.lines {
+            Some((insertions, deletions)) => (insertions + deletions).to_string(),
+            None => "Bin".to_string(),
+        })
+        .collect::<Vec<_>>();
+    let count_width = counts.iter().map(String::len).max().unwrap_or_default();
+    // Paths get up to half of the width, longer ones keep their end
+    let path_width = file_stats
+        .iter()
+        .map(|file_stat| file_stat.path.width())
+        .max()
+        .unwrap_or_default()
+        .min(width / 2)
+        .max(4);
+    let bar_width = width.saturating_sub(path_width + count_width + 4).max(1);
+    let most_lines = file_stats
+        .iter()
+        .filter_map(|file_stat| file_stat.lines)
+        .map(|(insertions, deletions)| insertions + deletions)
+        .max()
+        .unwrap_or_default();
+    let scale = |lines: usize| {
+        if most_lines <= bar_width {
+            lines
+        } else {
+            // Files with any changes keep at least one mark
+            (lines * bar_width / most_lines).max(usize::from(lines > 0))
+        }
+    };
+
+    let mut lines = Vec::new();
+    let mut marks = Vec::new();
+    for (file_stat, count) in file_stats.iter().zip(&counts) {
+        let path = if file_stat.path.width() <= path_width {
+            file_stat.path.clone()
+        } else {
+            let mut tail = String::new();
+            for char in file_stat.path.chars().rev() {
+                if tail.width() + char.to_string().width() + 3 > path_width {
+                    break;
+                }
+                tail.insert(0, char);
+            }
+            format!("...{tail}")
+        };
+        let padding = " ".repeat(path_width.saturating_sub(path.width()));
+        let mut line = format!("{path}{padding} | {count:>count_width$}");
+
+        if let Some((insertions, deletions)) = file_stat.lines {
+            let bar = scale(insertions + deletions);
+            let plus = if insertions + deletions == 0 {
+                0
+            } else {
+                (bar * insertions).div_ceil(insertions + deletions)
+            };
+            line.push(' ');
+            let start = line.len();
+            line.push_str(&"+".repeat(plus));
+            line.push_str(&"-".repeat(bar - plus));
+            marks.push((lines.len(), start..start + plus, theme.diff_add));
+            marks.push((lines.len(), start + plus..line.len(), theme.diff_del));
+        }
+        lines.push((line, Style::default()));
+    }
+
+    let plural = |count: usize, word: &str| {
+        if count == 1 {
+            format!("{count} {word}")
+        } else {
+            format!("{count} {word}s")
+        }
+    };
+    lines.push((
+        format!(
+            "{} changed, {}(+), {}(-)",
+            plural(file_stats.len(), "file"),
+            plural(git_data.insertions(), "insertion"),
+            plural(git_data.deletions(), "deletion")
+        ),
+        Style::new().add_modifier(Modifier::BOLD),
+    ));
+
+    (lines, marks)
+}
+
+// First line after the top line of the pane starting with prefix, ex: the next hunk
+fn next_line(lines: &[&str], top: usize, prefix: &str) -> Option<usize> {
+    (top + 1..lines.len()).find(|&i| lines[i].starts_with(prefix))
+}
+
+fn previous_line(lines: &[&str], top: usize, prefix: &str) -> Option<usize> {
+    (0..top.min(lines.len()))
+        .rev()
+        .find(|&i| lines[i].starts_with(prefix))
+}
+
+// Like ratatui::init with mouse capture, which is turned off again on panic
+pub fn init_terminal() -> DefaultTerminal {
+    MOUSE_PANIC_HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            execute!(io::stdout(), DisableMouseCapture).ok();
+            hook(info);
+        }));
+    });
+
+    let terminal = ratatui::init();
+    execute!(io::stdout(), EnableMouseCapture).ok();
+    terminal
+}
+
+pub fn restore_terminal() {
+    execute!(io::stdout(), DisableMouseCapture).ok();
+    ratatui::restore();
+}
+
+// Hands the terminal over to a command, unlike restore_terminal and init_terminal the panic hooks
+// installed by init_terminal stay as they are
+fn suspend_terminal() {
+    execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen).ok();
+    disable_raw_mode().ok();
+}
+
+fn resume_terminal(terminal: &mut DefaultTerminal) {
+    enable_raw_mode().ok();
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture).ok();
+    // The command drew over the screen, so everything is drawn again
+    terminal.clear().ok();
 }
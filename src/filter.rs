@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use clap::ValueEnum;
+use regex::{Regex, RegexBuilder};
+
+use crate::git_data::{GitData, Repo, RepoState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StateFilter {
+    /// Any uncommitted or unpushed changes
+    Dirty,
+    Clean,
+    Unpushed,
+    Unstaged,
+    Staged,
+    Conflicted,
+    Error,
+}
+
+impl StateFilter {
+    // Order of the number keys toggling each filter in the diff TUI
+    pub const ALL: [StateFilter; 7] = [
+        StateFilter::Dirty,
+        StateFilter::Clean,
+        StateFilter::Unpushed,
+        StateFilter::Unstaged,
+        StateFilter::Staged,
+        StateFilter::Conflicted,
+        StateFilter::Error,
+    ];
+
+    pub fn matches(self, state: RepoState) -> bool {
+        match self {
+            StateFilter::Dirty => matches!(
+                state,
+                RepoState::Conflicted
+                    | RepoState::Unpushed
+                    | RepoState::Staged
+                    | RepoState::Unstaged
+            ),
+            StateFilter::Clean => state == RepoState::Clean,
+            StateFilter::Unpushed => state == RepoState::Unpushed,
+            StateFilter::Unstaged => state == RepoState::Unstaged,
+            StateFilter::Staged => state == RepoState::Staged,
+            StateFilter::Conflicted => state == RepoState::Conflicted,
+            StateFilter::Error => matches!(state, RepoState::Error | RepoState::Timeout),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StateFilter::Dirty => "dirty",
+            StateFilter::Clean => "clean",
+            StateFilter::Unpushed => "unpushed",
+            StateFilter::Unstaged => "unstaged",
+            StateFilter::Staged => "staged",
+            StateFilter::Conflicted => "conflicted",
+            StateFilter::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    pub only: Vec<StateFilter>,
+    pub hide_clean: bool,
+    pub grep: Option<ContentSearch>,
+}
+
+// Searches the pending changes of repos, the added and removed lines of their diff or
+// with file_names the paths of changed files (including untracked ones from git status).
+// Diffs have to be loaded first (see git_data::load_diffs).
+#[derive(Debug, Clone)]
+pub struct ContentSearch {
+    pub regex: Regex,
+    pub file_names: bool,
+}
+
+// First place a content search matched in a repo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit<'data> {
+    pub file: &'data str,
+    pub line: Option<usize>, // Line of the diff, None for files only listed by git status
+    pub text: &'data str,
+}
+
+impl Filters {
+    // Repos are kept if they match any of the --only filters (or there are none)
+    pub fn matches(&self, git_data: &GitData) -> bool {
+        let state = git_data.state();
+
+        (self.only.is_empty() || self.only.iter().any(|filter| filter.matches(state)))
+            && !(self.hide_clean && state == RepoState::Clean)
+            && self
+                .grep
+                .as_ref()
+                .is_none_or(|grep| grep.first_hit(git_data).is_some())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.only.is_empty() && !self.hide_clean && self.grep.is_none()
+    }
+
+    pub fn toggle(&mut self, filter: StateFilter) {
+        if let Some(i) = self.only.iter().position(|&only| only == filter) {
+            self.only.remove(i);
+        } else {
+            self.only.push(filter);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.only.clear();
+        self.hide_clean = false;
+        self.grep = None;
+    }
+
+    pub fn label(&self) -> String {
+        let mut labels: Vec<&str> = self.only.iter().map(|filter| filter.label()).collect();
+        if self.hide_clean {
+            labels.push("hide clean");
+        }
+        let grep = self.grep.as_ref().map(ContentSearch::label);
+        labels.extend(grep.as_deref());
+
+        if labels.is_empty() {
+            "none".to_string()
+        } else {
+            labels.join(", ")
+        }
+    }
+}
+
+impl ContentSearch {
+    pub fn new(pattern: &str, file_names: bool) -> Result<Self, String> {
+        let regex = smart_case_regex(pattern)?;
+
+        Ok(Self { regex, file_names })
+    }
+
+    pub fn first_hit<'data>(&self, git_data: &'data GitData) -> Option<Hit<'data>> {
+        let mut file = "";
+
+        let diff = git_data.diff.as_ref().map_or("", |diff| diff.text.as_str());
+        for (i, line) in diff.lines().enumerate() {
+            if let Some(paths) = line.strip_prefix("diff --git ") {
+                // diff --git a/old b/new
+                file = paths
+                    .rsplit_once(" b/")
+                    .map_or(paths, |(_, new_path)| new_path);
+                if self.file_names && self.regex.is_match(file) {
+                    return Some(Hit {
+                        file,
+                        line: Some(i),
+                        text: file,
+                    });
+                }
+            } else if !self.file_names
+                && ((line.starts_with('+') && !line.starts_with("+++"))
+                    || (line.starts_with('-') && !line.starts_with("---")))
+                && self.regex.is_match(line)
+            {
+                return Some(Hit {
+                    file,
+                    line: Some(i),
+                    text: line,
+                });
+            }
+        }
+
+        if !self.file_names {
+            return None;
+        }
+        status_files(&git_data.status)
+            .find(|file| self.regex.is_match(file))
+            .map(|file| Hit {
+                file,
+                line: None,
+                text: file,
+            })
+    }
+
+    pub fn label(&self) -> String {
+        let target = if self.file_names { "files" } else { "changes" };
+        format!("{target} matching {}", self.regex.as_str())
+    }
+}
+
+// Like vim's smartcase, patterns are only case-sensitive if they have capitals
+pub fn smart_case_regex(pattern: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(!pattern.chars().any(char::is_uppercase))
+        .build()
+        .map_err(|e| {
+            // The last line of regex errors is the reason, the ones before point at the pattern
+            let reason = e.to_string().lines().last().unwrap_or_default().to_string();
+            reason.trim_start_matches("error: ").to_string()
+        })
+}
+
+// Labels git status puts before paths of tracked files, untracked ones are listed without one
+const STATUS_KINDS: [&str; 13] = [
+    "modified",
+    "new file",
+    "deleted",
+    "renamed",
+    "copied",
+    "typechange",
+    "both modified",
+    "both added",
+    "both deleted",
+    "added by us",
+    "added by them",
+    "deleted by us",
+    "deleted by them",
+];
+
+// Paths git status lists under its sections, ex: "\tdeleted:    src/lib.rs" or "\tnew.txt"
+fn status_files(status: &str) -> impl Iterator<Item = &str> {
+    status.lines().filter_map(|line| {
+        let entry = line.strip_prefix('\t')?;
+        let path = match entry.split_once(": ") {
+            Some((kind, path)) if STATUS_KINDS.contains(&kind) => path.trim_start(),
+            _ => entry,
+        };
+
+        // renamed:    old.rs -> new.rs
+        Some(
+            path.rsplit_once(" -> ")
+                .map_or(path, |(_, new_path)| new_path),
+        )
+    })
+}
+
+pub fn state_counts(repos: &[Repo]) -> BTreeMap<RepoState, usize> {
+    let mut counts = BTreeMap::new();
+    for repo in repos {
+        *counts.entry(repo.state()).or_default() += 1;
+    }
+
+    counts
+}
@@ -1,169 +1,1231 @@
 use std::{
-    path::PathBuf,
-    process::Command,
-    sync::mpsc::{self, Sender},
+    collections::HashSet,
+    fmt,
+    fs::Metadata,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::{
+        Mutex, PoisonError,
+        atomic::Ordering,
+        mpsc::{self, RecvTimeoutError, Sender},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use color_eyre::eyre::{Result, eyre};
+use clap::ValueEnum;
 use rayon::{
-    iter::{IntoParallelRefIterator, ParallelBridge, ParallelIterator},
+    iter::{
+        IntoParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator,
+    },
     slice::ParallelSliceMut,
 };
+use serde::Deserialize;
 
-use crate::cache::{create_cache, mtime_matches_cache};
+use crate::{
+    cache::{
+        Cache, CacheEntry, CachedStatus, DirCache, canonical_path, read_cache, repo_fingerprint,
+        write_cache,
+    },
+    progress::Progress,
+    scan::{Error, Scan, ScanResult},
+};
+
+// Used when refreshing a single repo from the TUI
+pub const DEFAULT_REPO_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Folders and repos listed by --timings
+pub const SLOWEST_COUNT: usize = 10;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct GitData {
+    pub path: PathBuf,
     pub status: String,
-    pub diff: String,
+    pub diff_stat: String,  // git diff --numstat against upstream
+    pub diff: Option<Diff>, // Only loaded when shown or searched, see load_diff
+    pub error: Option<GitError>,
+    pub last_commit: Option<u64>, // Only loaded to sort by it, see load_last_commits
+    pub modified: Option<u64>,
+    pub git_duration: Option<Duration>, // None when reused from the cache
+}
+
+// Full diff against upstream, which can be too large to keep around for every repo
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diff {
+    pub text: String,
+    pub truncated: bool, // Cut off at the size it was loaded with
+}
+
+// How diffs are shown and searched, git's own settings apply to whatever is left at its default.
+// Scans count changed lines without them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    pub ignore_whitespace: bool,
+    pub ignore_blank_lines: bool,
+    pub context: Option<u32>, // Lines around each change, 3 unless git is set up otherwise
+    pub renames: RenameDetection,
+    pub word_diff: bool,
+    pub algorithm: DiffAlgorithm,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenameDetection {
+    Off,
+    /// Renamed files, like git does by default
+    #[default]
+    On,
+    /// Copied files too
+    Copies,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffAlgorithm {
+    /// Whichever git is set up with (diff.algorithm), myers otherwise
+    #[default]
+    Default,
+    Myers,
+    Patience,
+    Histogram,
+}
+
+// One file of the diffstat, without line counts for binary files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStat {
+    pub path: String,
+    pub lines: Option<(usize, usize)>, // Insertions and deletions
+}
+
+/// A repo found by a scan.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Repo {
+    /// Path relative to the base path (ex: `work/api`).
+    pub name: String,
+    pub data: GitData,
+}
+
+impl Repo {
+    pub fn new(name: String, data: GitData) -> Self {
+        Self { name, data }
+    }
+
+    pub fn state(&self) -> RepoState {
+        self.data.state()
+    }
+}
+
+pub type Repos = Vec<Repo>;
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ScanStats {
+    pub duration: Duration,
+    pub cache_hit: bool,
+    pub rescanned_folders: usize,
+    pub status_cache_hits: usize,
+    pub repo_count: usize,
+    pub daemon_age: Option<Duration>, // Time since the daemon last refreshed, when served by it
+    pub discovery_duration: Duration,
+    pub slowest_folders: Vec<(PathBuf, Duration)>, // Time to read each folder, slowest first
+}
+
+impl fmt::Display for ScanStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(daemon_age) = self.daemon_age {
+            write!(f, "from daemon {}s ago, ", daemon_age.as_secs())?;
+        }
+        write!(f, "scanned in {:.2}s ", self.duration.as_secs_f64())?;
+
+        match (self.cache_hit, self.rescanned_folders) {
+            (false, _) => write!(f, "(cache miss")?,
+            (true, 0) => write!(f, "(cache hit")?,
+            (true, rescanned_folders) => write!(
+                f,
+                "(cache hit, {rescanned_folders} changed folders searched"
+            )?,
+        }
+
+        write!(
+            f,
+            ", {}/{} statuses cached)",
+            self.status_cache_hits, self.repo_count
+        )
+    }
+}
+
+// Limits for discovery, set per base path in config or with flags
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ScanOptions {
+    pub max_depth: Option<usize>, // Folders below the base path to search, 1 only searches its children
+    pub follow_symlinks: bool,
+    pub one_file_system: bool, // Don't search mount points of other file systems
+}
+
+impl ScanOptions {
+    // Stored in the cache, folders searched with other options have to be searched again
+    pub fn cache_key(self) -> u64 {
+        let max_depth = self.max_depth.map_or(0, |max_depth| max_depth as u64 + 1);
+        (max_depth << 2) | (u64::from(self.follow_symlinks) << 1) | u64::from(self.one_file_system)
+    }
+}
+
+// Variants are ordered by severity, most urgent first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum RepoState {
+    Error,
+    Timeout,
+    Conflicted,
+    Unpushed,
+    Staged,
+    Unstaged,
+    Unknown,
+    Clean,
+}
+
+impl RepoState {
+    pub fn label(self) -> &'static str {
+        match self {
+            RepoState::Error => "ERROR",
+            RepoState::Timeout => "TIMEOUT",
+            RepoState::Conflicted => "DIRTY (merge conflicts)",
+            RepoState::Unpushed => "DIRTY (changes committed, not pushed)",
+            RepoState::Staged => "DIRTY (changes added, not committed)",
+            RepoState::Unstaged => "DIRTY (changes not added)",
+            RepoState::Unknown => "UNKNOWN",
+            RepoState::Clean => "CLEAN",
+        }
+    }
+
+    pub fn short_label(self) -> &'static str {
+        match self {
+            RepoState::Error => "error",
+            RepoState::Timeout => "timeout",
+            RepoState::Conflicted => "conflicted",
+            RepoState::Unpushed => "unpushed",
+            RepoState::Staged => "staged",
+            RepoState::Unstaged => "unstaged",
+            RepoState::Unknown => "unknown",
+            RepoState::Clean => "clean",
+        }
+    }
+}
+
+impl GitData {
+    // Git data of a repo that git ran for without errors, with nothing loaded on demand yet
+    pub fn new(path: PathBuf, status: String, diff_stat: String) -> Self {
+        Self {
+            path,
+            status,
+            diff_stat,
+            diff: None,
+            error: None,
+            last_commit: None,
+            modified: None,
+            git_duration: None,
+        }
+    }
+
+    fn from_cache(path: &Path, cached_status: &CachedStatus) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            status: cached_status.status.clone(),
+            diff_stat: cached_status.diff_stat.clone(),
+            diff: None,
+            error: None,
+            last_commit: None,
+            modified: last_modified(path),
+            git_duration: None,
+        }
+    }
+
+    fn from_error(path: PathBuf, error: GitError) -> Self {
+        Self {
+            path,
+            status: String::new(),
+            diff_stat: String::new(),
+            diff: None,
+            error: Some(error),
+            last_commit: None,
+            modified: None,
+            git_duration: None,
+        }
+    }
+
+    pub fn state(&self) -> RepoState {
+        if let Some(error) = &self.error {
+            if error.timed_out {
+                RepoState::Timeout
+            } else {
+                RepoState::Error
+            }
+        } else if self.status.contains("Unmerged paths") {
+            RepoState::Conflicted
+        } else if self.status.contains("nothing to commit")
+            && !self.status.contains("branch is ahead")
+        {
+            // Being behind the upstream leaves nothing local to push
+            if self.diff_stat.is_empty() || self.status.contains("branch is behind") {
+                RepoState::Clean
+            } else {
+                // edge case: no upstream branch, but there are local commits not pushed
+                RepoState::Unpushed
+            }
+        } else if self.status.contains("no changes added to commit")
+            || self.status.contains("untracked")
+        {
+            RepoState::Unstaged
+        } else if self.status.contains("Changes to be committed") {
+            RepoState::Staged
+        } else if self.status.contains("branch is ahead") || self.status.contains("diverged") {
+            RepoState::Unpushed
+        } else {
+            RepoState::Unknown
+        }
+    }
+
+    pub fn changed_files(&self) -> usize {
+        self.diff_stat.lines().count()
+    }
+
+    // Local commits not on the upstream, as reported by git status
+    pub fn unpushed_commits(&self) -> usize {
+        self.status
+            .lines()
+            .find_map(|line| {
+                if let Some((_, ahead)) = line.split_once("is ahead of") {
+                    // Your branch is ahead of 'origin/main' by 3 commits.
+                    ahead.split_once(" by ")?.1.split_whitespace().next()
+                } else if let Some((_, diverged)) = line.split_once("and have ") {
+                    // and have 3 and 5 different commits each, respectively.
+                    diverged.split_whitespace().next()
+                } else {
+                    None
+                }
+            })
+            .and_then(|count| count.parse().ok())
+            .unwrap_or_default()
+    }
+
+    // Files of the diff as git diff --numstat lists them
+    pub fn file_stats(&self) -> Vec<FileStat> {
+        // 3\t1\tsrc/lib.rs, or -\t-\tlogo.png for binary files
+        self.diff_stat
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let (insertions, deletions) = (fields.next()?, fields.next()?);
+                Some(FileStat {
+                    path: fields.next()?.to_string(),
+                    lines: insertions.parse().ok().zip(deletions.parse().ok()),
+                })
+            })
+            .collect()
+    }
+
+    // Added lines in the diff, binary files count as none
+    pub fn insertions(&self) -> usize {
+        self.file_stats()
+            .iter()
+            .filter_map(|file_stat| file_stat.lines)
+            .map(|(insertions, _)| insertions)
+            .sum()
+    }
+
+    pub fn deletions(&self) -> usize {
+        self.file_stats()
+            .iter()
+            .filter_map(|file_stat| file_stat.lines)
+            .map(|(_, deletions)| deletions)
+            .sum()
+    }
+
+    pub fn changed_lines(&self) -> usize {
+        self.insertions() + self.deletions()
+    }
+}
+
+// Failure of a git command (or of reading a folder during discovery) for a single repo
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct GitError {
+    pub message: String,
+    pub code: Option<i32>,
+    pub timed_out: bool,
+}
+
+impl GitError {
+    pub fn new(message: String) -> Self {
+        Self {
+            message,
+            code: None,
+            timed_out: false,
+        }
+    }
+
+    fn from_output(output: &Output) -> Self {
+        Self {
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            code: output.status.code(),
+            timed_out: false,
+        }
+    }
 }
 
-enum GitCmd {
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{} (exit code {code})", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+enum GitCmd<'options> {
     Status,
-    Diff,
+    Diff(&'options DiffOptions),
+    DiffStat,
+    LastCommit,
+    RemoteUrl,
+    Branch,
+}
+
+// Git is killed once the deadline passes, so one hanging repo (ex: on a network mount) can't stall the scan.
+// Past max_stdout bytes (plus one, to tell it was cut off) the rest of the output isn't read.
+pub(crate) fn run_git(
+    args: &[&str],
+    repo_path: &Path,
+    deadline: Option<Instant>,
+    max_stdout: Option<usize>,
+    input: Option<&[u8]>, // Written to stdin, ex: patches for git apply
+) -> Result<Output, GitError> {
+    let not_executed =
+        |e: io::Error| GitError::new(format!("git {} could not be executed: {e}", args.join(" ")));
+
+    // Without optional locks git status doesn't rewrite the index, which would race with git
+    // commands run by the user and change the repo fingerprint
+    let mut command = Command::new("git");
+    command
+        .arg("--no-optional-locks")
+        .args(args)
+        .current_dir(repo_path);
+
+    if deadline.is_none() && max_stdout.is_none() && input.is_none() {
+        return command.output().map_err(not_executed);
+    }
+
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(not_executed)?;
+
+    // Written on its own thread like the pipes are read, stdin is closed once it's done
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.unwrap_or_default().to_vec();
+        thread::spawn(move || stdin.write_all(&input));
+    }
+
+    // Pipes are drained while waiting, git blocks once a pipe is full otherwise. A pipe closed
+    // early makes git stop writing instead.
+    let stdout = read_pipe(child.stdout.take(), max_stdout.map(|max| max + 1));
+    let stderr = read_pipe(child.stderr.take(), None);
+
+    let mut poll_interval = Duration::from_millis(1);
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if deadline.is_none_or(|deadline| Instant::now() < deadline) => {
+                thread::sleep(poll_interval);
+                poll_interval = (poll_interval * 2).min(Duration::from_millis(50));
+            }
+            Ok(None) => {
+                child.kill().ok();
+                child.wait().ok();
+                return Err(GitError {
+                    message: format!("git {} timed out", args.join(" ")),
+                    code: None,
+                    timed_out: true,
+                });
+            }
+            Err(e) => return Err(not_executed(e)),
+        }
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
 }
 
-fn execute_git_command(r#type: GitCmd, repo_path: &PathBuf) -> Result<String> {
+fn read_pipe(
+    pipe: Option<impl Read + Send + 'static>,
+    max: Option<usize>,
+) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(pipe) = pipe {
+            let max = max.map_or(u64::MAX, |max| max as u64);
+            pipe.take(max).read_to_end(&mut buffer).ok();
+        }
+        buffer
+    })
+}
+
+// Output of the command, longer than max_stdout when it was cut off there
+fn execute_git_command(
+    r#type: GitCmd<'_>,
+    repo_path: &Path,
+    deadline: Option<Instant>,
+    max_stdout: Option<usize>,
+) -> Result<String, GitError> {
+    let options;
     let subcommand = match r#type {
         GitCmd::Status => vec!["status"],
-        GitCmd::Diff => vec!["diff", "@{upstream}"], // Compare against remote of current branch
+        GitCmd::Diff(diff_options) => {
+            options = diff_options.args();
+            let options = options.iter().map(String::as_str);
+            // Compare against remote of current branch
+            ["diff"]
+                .into_iter()
+                .chain(options)
+                .chain(["@{upstream}"])
+                .collect()
+        }
+        GitCmd::DiffStat => vec!["diff", "--numstat", "@{upstream}"],
+        GitCmd::LastCommit => vec!["log", "-1", "--format=%ct"],
+        GitCmd::RemoteUrl => vec!["ls-remote", "--get-url"], // Remote of current branch or origin
+        GitCmd::Branch => vec!["branch", "--show-current"],
     };
+    // Git fails writing the rest of the output once it isn't read anymore
+    let succeeded = |output: &Output| {
+        output.status.success() || max_stdout.is_some_and(|max| output.stdout.len() > max)
+    };
+
+    let output = run_git(&subcommand, repo_path, deadline, max_stdout, None)?;
 
-    let output = Command::new("git")
-        .args(&subcommand)
-        .current_dir(repo_path)
-        .output()
-        .map_err(|e| eyre!(format!("git {subcommand:#?} could not be executed: {e:#?}")))?;
+    if succeeded(&output) {
+        return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+    }
 
-    if !output.status.success()
-        && let error_message = String::from_utf8_lossy(&output.stderr)
-        && error_message.contains("no upstream configured")
+    let error_message = String::from_utf8_lossy(&output.stderr);
+    if let GitCmd::Diff(_) | GitCmd::DiffStat = r#type
+        && (error_message.contains("no upstream configured")
+            || error_message.contains("HEAD does not point to a branch")
+            || error_message.contains("no such branch"))
     {
-        let output = Command::new("git")
-            .args(["diff", "origin/HEAD"]) // Fallback to origin/HEAD if no upstream exists
-            .current_dir(repo_path)
-            .output()
-            .map_err(|e| {
-                eyre!(format!(
-                    "git status origin/HEAD could not be executed: {e:#?}"
-                ))
-            })?;
+        // Fallback to origin/HEAD if no upstream exists
+        let subcommand = subcommand
+            .iter()
+            .map(|&arg| {
+                if arg == "@{upstream}" {
+                    "origin/HEAD"
+                } else {
+                    arg
+                }
+            })
+            .collect::<Vec<_>>();
+        let output = run_git(&subcommand, repo_path, deadline, max_stdout, None)?;
 
-        return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+        if succeeded(&output)
+            || String::from_utf8_lossy(&output.stderr).contains("unknown revision")
+        {
+            // Local only repos have no remote to compare against
+            return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+        }
+
+        return Err(GitError::from_output(&output));
+    }
+
+    Err(GitError::from_output(&output))
+}
+
+impl Diff {
+    pub fn new(text: String, truncated: bool) -> Self {
+        Self { text, truncated }
     }
+}
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+impl DiffOptions {
+    // Arguments of git diff, nothing for options left at their default
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.ignore_whitespace {
+            args.push("-w".to_string());
+        }
+        if self.ignore_blank_lines {
+            args.push("--ignore-blank-lines".to_string());
+        }
+        if let Some(context) = self.context {
+            args.push(format!("-U{context}"));
+        }
+        match self.renames {
+            RenameDetection::Off => args.push("--no-renames".to_string()),
+            RenameDetection::On => {}
+            RenameDetection::Copies => args.push("--find-copies".to_string()),
+        }
+        if self.word_diff {
+            // [-removed-]{+added+} inside the line instead of whole removed and added lines
+            args.push("--word-diff=plain".to_string());
+        }
+        if self.algorithm != DiffAlgorithm::Default {
+            args.push(format!("--diff-algorithm={}", self.algorithm.label()));
+        }
+        args
+    }
 }
 
-pub fn collect_git_data(
-    fuzzit_path: Option<PathBuf>,
-    fuzzit_base_path: Option<PathBuf>,
-    refresh_cache: bool,
-) -> Result<(String, Vec<(String, GitData)>)> {
+impl RenameDetection {
+    pub fn next(self) -> Self {
+        match self {
+            RenameDetection::Off => RenameDetection::On,
+            RenameDetection::On => RenameDetection::Copies,
+            RenameDetection::Copies => RenameDetection::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RenameDetection::Off => "off",
+            RenameDetection::On => "renames",
+            RenameDetection::Copies => "renames and copies",
+        }
+    }
+}
+
+impl DiffAlgorithm {
+    pub fn next(self) -> Self {
+        match self {
+            DiffAlgorithm::Default => DiffAlgorithm::Myers,
+            DiffAlgorithm::Myers => DiffAlgorithm::Patience,
+            DiffAlgorithm::Patience => DiffAlgorithm::Histogram,
+            DiffAlgorithm::Histogram => DiffAlgorithm::Default,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DiffAlgorithm::Default => "default",
+            DiffAlgorithm::Myers => "myers",
+            DiffAlgorithm::Patience => "patience",
+            DiffAlgorithm::Histogram => "histogram",
+        }
+    }
+}
+
+/// Replaces a leading `~` with the home directory.
+pub fn expand_home(path: &Path) -> Result<PathBuf, Error> {
+    if !path.starts_with("~") {
+        return Ok(path.to_path_buf());
+    }
+
+    let mut home = dirs::home_dir().ok_or(Error::HomeDirNotFound)?;
+    let mut components = path.components();
+    components.next(); // Skip the tilde component to use home dir instead
+
+    home.extend(components);
+    Ok(home)
+}
+
+pub(crate) fn collect_git_data(
+    scan: &Scan,
+    repo_sender: Option<&Sender<Repo>>,
+) -> Result<ScanResult, Error> {
+    let progress = Progress::default();
+
+    // The progress line is only drawn while this runs, and cleared once it's done
+    thread::scope(|scope| {
+        if scan.progress {
+            scope.spawn(|| progress.report());
+        }
+
+        let result = scan_base_path(scan, repo_sender, &progress);
+        progress.finish();
+        result
+    })
+}
+
+fn scan_base_path(
+    scan: &Scan,
+    repo_sender: Option<&Sender<Repo>>,
+    progress: &Progress,
+) -> Result<ScanResult, Error> {
+    let Scan {
+        options: scan_options,
+        refresh_cache,
+        repo_timeout,
+        ..
+    } = *scan;
+    let no_status_cache = !scan.status_cache;
+
+    let start = Instant::now();
+    let mut rescanned_folders = 0;
     let mut repo_paths = Vec::new();
+    let mut failed_paths = Vec::new();
     let (tx, rx) = mpsc::channel();
 
-    let base_path = if let Some(fuzzit_path) = fuzzit_path {
-        fuzzit_path
-    } else if let Some(fuzzit_base_path) = fuzzit_base_path {
-        fuzzit_base_path
-    } else {
-        Err(eyre!(
-            "Add FUZZIT_BASE_PATH to your environment (ex: ~/.zshrc) or use FUZZIT_PATH before command"
-        ))?
+    let parsed_base_path = expand_home(&scan.base_path)?;
+    let base_path_error = |source| Error::BasePath {
+        path: scan.base_path.clone(),
+        source,
     };
 
-    let parsed_base_path = if base_path.starts_with("~") {
-        if let Some(mut home) = dirs::home_dir() {
-            let mut components = base_path.components();
-            components.next(); // Skip the tilde component to use home dir instead
-
-            home.extend(components);
-            home
-        } else {
-            Err(eyre!(
-                "Home directory could not be determined, please use full path for FUZZIT_BASE_PATH or FUZZIT_PATH"
-            ))?
-        }
+    let cache = if refresh_cache || !scan.cache {
+        None
     } else {
-        base_path.clone()
+        read_cache(&parsed_base_path)
     };
+    let Cache {
+        dirs: dir_cache,
+        statuses: status_cache,
+        scan_options: cached_scan_options,
+    } = cache.unwrap_or_default();
 
-    if !refresh_cache && let Some(cached_repo_paths) = mtime_matches_cache(&parsed_base_path) {
-        repo_paths.extend(cached_repo_paths);
+    // Statuses stay valid when the scan options change, but found folders don't
+    let cache_hit = !dir_cache.is_empty() && cached_scan_options == scan_options.cache_key();
+    let dir_cache = if cache_hit {
+        dir_cache
     } else {
-        recursive_repo_search(&parsed_base_path, tx)?;
-        while let Ok(repo_path) = rx.recv() {
-            repo_paths.push(repo_path)
-        }
+        DirCache::default()
+    };
 
-        // Parallel sort repo paths a-z (unstable is faster)
-        repo_paths.par_sort_unstable();
+    let search = Search {
+        dir_cache: &dir_cache,
+        options: scan_options,
+        root_device: device(&std::fs::metadata(&parsed_base_path).map_err(base_path_error)?),
+        visited: Mutex::default(),
+        folder_durations: Mutex::default(),
+        progress,
+    };
+    recursive_repo_search(&parsed_base_path, 0, &search, tx).map_err(base_path_error)?;
+    let discovery_duration = start.elapsed();
 
-        create_cache(&parsed_base_path, &repo_paths);
+    let mut cache_entries = Vec::new();
+    while let Ok(found) = rx.recv() {
+        match found {
+            Found::Repo(repo_path, mtime) => {
+                cache_entries.push(CacheEntry {
+                    path: repo_path.clone(),
+                    mtime: Some(mtime),
+                    is_repo: true,
+                });
+                repo_paths.push(repo_path);
+            }
+            Found::Folder(folder_path, mtime, searched) => {
+                rescanned_folders += usize::from(searched);
+                cache_entries.push(CacheEntry {
+                    path: folder_path,
+                    mtime: Some(mtime),
+                    is_repo: false,
+                });
+            }
+            Found::Failed(failed_path, error) => {
+                cache_entries.push(CacheEntry {
+                    path: failed_path.clone(),
+                    mtime: None,
+                    is_repo: false,
+                });
+                failed_paths.push((failed_path, error));
+            }
+        }
     }
 
-    // Parallel iterate through collected repos
-    let git_data = repo_paths
+    // Parallel sort repo paths a-z (unstable is faster)
+    repo_paths.par_sort_unstable();
+    progress
+        .repos_total
+        .store(repo_paths.len(), Ordering::Relaxed);
+
+    // Parallel iterate through collected repos, only running git for repos that changed
+    let stream_repo = |repo: &Repo| {
+        if let Some(repo_sender) = repo_sender {
+            repo_sender.send(repo.clone()).ok();
+        }
+    };
+
+    let repos: Vec<(Repo, Option<CachedStatus>, bool)> = repo_paths
         .par_iter()
         .map(|repo_path| {
-            let stripped_repo_path = {
-                let removed_base_path = repo_path
-                    .display()
-                    .to_string()
-                    .replace(&parsed_base_path.display().to_string(), "");
-
-                if cfg!(windows) {
-                    removed_base_path.replacen("\\", "", 1)
-                } else {
-                    removed_base_path.replacen("/", "", 1)
+            let name = strip_base_path(repo_path, &parsed_base_path);
+            let repo_start = Instant::now();
+            // Taken before running git so changes made while it runs aren't missed next time
+            let fingerprint = if no_status_cache {
+                None
+            } else {
+                match fingerprint_within(repo_path, repo_timeout) {
+                    Ok(fingerprint) => fingerprint,
+                    Err(error) => {
+                        progress.repos_done.fetch_add(1, Ordering::Relaxed);
+                        let repo = Repo {
+                            name,
+                            data: GitData {
+                                git_duration: Some(repo_start.elapsed()),
+                                ..GitData::from_error(repo_path.clone(), error)
+                            },
+                        };
+                        stream_repo(&repo);
+                        return (repo, None, false);
+                    }
                 }
             };
 
-            // Concurrently get git data
-            let (status, diff) = rayon::join(
-                || execute_git_command(GitCmd::Status, repo_path).unwrap_or_default(),
-                || execute_git_command(GitCmd::Diff, repo_path).unwrap_or_default(),
-            );
+            if let Some(fingerprint) = fingerprint
+                && let Some(cached_status) = status_cache.get(&canonical_path(repo_path))
+                && cached_status.fingerprint == fingerprint
+            {
+                progress.repos_done.fetch_add(1, Ordering::Relaxed);
+                let repo = Repo {
+                    name,
+                    data: GitData::from_cache(repo_path, cached_status),
+                };
+                stream_repo(&repo);
+                return (repo, Some(cached_status.clone()), true);
+            }
+
+            // Git gets what is left of the repo timeout after the fingerprint
+            let git_timeout =
+                repo_timeout.map(|timeout| timeout.saturating_sub(repo_start.elapsed()));
+            let git_data = collect_repo_data(repo_path, git_timeout);
+            progress.repos_done.fetch_add(1, Ordering::Relaxed);
+            let cached_status =
+                fingerprint
+                    .filter(|_| git_data.error.is_none())
+                    .map(|fingerprint| CachedStatus {
+                        fingerprint,
+                        status: git_data.status.clone(),
+                        diff_stat: git_data.diff_stat.clone(),
+                    });
 
-            (stripped_repo_path, GitData { status, diff })
+            let repo = Repo {
+                name,
+                data: git_data,
+            };
+            stream_repo(&repo);
+            (repo, cached_status, false)
         })
         .collect();
 
-    Ok((base_path.display().to_string(), git_data))
+    let status_cache_hits = repos.iter().filter(|(_, _, cache_hit)| *cache_hit).count();
+    let cache_outdated = !cache_hit
+        || rescanned_folders > 0
+        || (!no_status_cache && status_cache_hits < repos.len());
+    if scan.cache && cache_outdated {
+        // Statuses cached by earlier runs are kept when the status cache isn't used
+        let statuses = repos
+            .iter()
+            .filter_map(|(repo, cached_status, _)| {
+                let cached_status = cached_status.as_ref().or_else(|| {
+                    no_status_cache.then(|| status_cache.get(&canonical_path(&repo.data.path)))?
+                })?;
+
+                Some((repo.data.path.as_path(), cached_status))
+            })
+            .collect::<Vec<_>>();
+
+        write_cache(
+            &parsed_base_path,
+            scan_options.cache_key(),
+            &cache_entries,
+            &statuses,
+        );
+    }
+
+    let mut repos: Repos = repos.into_iter().map(|(repo, _, _)| repo).collect();
+
+    // Folders that could not be searched are listed alongside repos so they aren't silently missing
+    if !failed_paths.is_empty() {
+        repos.extend(failed_paths.into_iter().map(|(failed_path, error)| {
+            let repo = Repo {
+                name: strip_base_path(&failed_path, &parsed_base_path),
+                data: GitData::from_error(failed_path, error),
+            };
+            stream_repo(&repo);
+            repo
+        }));
+        repos.par_sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    let mut slowest_folders = search
+        .folder_durations
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner);
+    slowest_folders.par_sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
+    slowest_folders.truncate(SLOWEST_COUNT);
+
+    let scan_stats = ScanStats {
+        duration: start.elapsed(),
+        cache_hit,
+        rescanned_folders,
+        status_cache_hits,
+        repo_count: repo_paths.len(),
+        daemon_age: None,
+        discovery_duration,
+        slowest_folders,
+    };
+
+    Ok(ScanResult {
+        base_path: scan.base_path.clone(),
+        repos,
+        stats: scan_stats,
+    })
+}
+
+// Fingerprint of the repo, given up on like git once the repo timeout is over (slow or hung
+// file systems). The thread taking it is left to finish on its own then.
+pub(crate) fn fingerprint_within(
+    repo_path: &Path,
+    timeout: Option<Duration>,
+) -> Result<Option<u64>, GitError> {
+    let Some(timeout) = timeout else {
+        return Ok(repo_fingerprint(repo_path));
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let path = repo_path.to_path_buf();
+    thread::spawn(move || sender.send(repo_fingerprint(&path)));
+
+    match receiver.recv_timeout(timeout) {
+        Ok(fingerprint) => Ok(fingerprint),
+        Err(RecvTimeoutError::Timeout) => Err(GitError {
+            message: "reading the repo for the status cache timed out".to_string(),
+            code: None,
+            timed_out: true,
+        }),
+        Err(RecvTimeoutError::Disconnected) => Ok(None), // Git is run without the cache then
+    }
+}
+
+pub fn collect_repo_data(repo_path: &Path, timeout: Option<Duration>) -> GitData {
+    let start = Instant::now();
+    let deadline = timeout.map(|timeout| start + timeout); // Shared by all commands of the repo
+
+    // Concurrently get git data
+    let (status, diff_stat) = rayon::join(
+        || execute_git_command(GitCmd::Status, repo_path, deadline, None),
+        || execute_git_command(GitCmd::DiffStat, repo_path, deadline, None),
+    );
+
+    match (status, diff_stat) {
+        (Ok(status), Ok(diff_stat)) => GitData {
+            path: repo_path.to_path_buf(),
+            status,
+            diff_stat,
+            diff: None,
+            error: None,
+            last_commit: None,
+            modified: last_modified(repo_path),
+            git_duration: Some(start.elapsed()),
+        },
+        (Err(error), _) | (_, Err(error)) => GitData {
+            git_duration: Some(start.elapsed()),
+            ..GitData::from_error(repo_path.to_path_buf(), error)
+        },
+    }
 }
 
-fn recursive_repo_search(current_path: &PathBuf, repo_path_sender: Sender<PathBuf>) -> Result<()> {
-    let mut git_path = current_path.clone();
-    git_path.push(".git");
+// Dates of the last commits, only needed to sort by them so scans don't run git for it
+pub fn load_last_commits(repos: &mut [Repo], timeout: Option<Duration>) {
+    repos.par_iter_mut().for_each(|repo| {
+        let git_data = &mut repo.data;
+        if git_data.last_commit.is_none() && git_data.error.is_none() {
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            // Repos without commits yet have no commit date
+            git_data.last_commit =
+                execute_git_command(GitCmd::LastCommit, &git_data.path, deadline, None)
+                    .ok()
+                    .and_then(|output| output.trim().parse().ok());
+        }
+    });
+}
 
-    if git_path.exists() {
-        git_path.pop();
-        repo_path_sender.send(git_path)?;
+// Diff of the repo against upstream, cut off at the last whole line within max_size bytes
+pub fn load_diff(
+    repo_path: &Path,
+    max_size: Option<usize>,
+    options: &DiffOptions,
+    timeout: Option<Duration>,
+) -> Result<Diff, GitError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut text = execute_git_command(GitCmd::Diff(options), repo_path, deadline, max_size)?;
+
+    let truncated = max_size.is_some_and(|max_size| text.len() > max_size);
+    if truncated {
+        let mut end = max_size.unwrap_or_default();
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(text[..end].rfind('\n').map_or(0, |newline| newline + 1));
+    }
+
+    Ok(Diff {
+        text: summarize_binary_files(text),
+        truncated,
+    })
+}
+
+// Loads the diffs of repos with changes that don't have one yet, for searching them. They aren't
+// cut off, so hits past the max size of the preview are found too.
+pub fn load_diffs(repos: &mut [Repo], options: &DiffOptions, timeout: Option<Duration>) {
+    // Searches match whole added and removed lines
+    let options = DiffOptions {
+        word_diff: false,
+        ..options.clone()
+    };
+    repos.par_iter_mut().for_each(|repo| {
+        let git_data = &mut repo.data;
+        if git_data.diff.is_none() && !git_data.diff_stat.is_empty() {
+            git_data.diff = load_diff(&git_data.path, None, &options, timeout).ok();
+        }
+    });
+}
+
+// Git only prints a summary for files it knows are binary, files diffed as text anyway (ex: by
+// a diff attribute) are summarized the same way instead of filling the diff with garbage
+fn summarize_binary_files(text: String) -> String {
+    let binary = |lines: &str| lines.contains(['\0', char::REPLACEMENT_CHARACTER]);
+    if !binary(&text) {
+        return text;
+    }
+
+    let mut summarized = String::with_capacity(text.len());
+    let file_starts = [0]
+        .into_iter()
+        .chain(
+            text.match_indices("\ndiff --git ")
+                .map(|(newline, _)| newline + 1),
+        )
+        .chain([text.len()])
+        .collect::<Vec<_>>();
+
+    for window in file_starts.windows(2) {
+        let file = &text[window[0]..window[1]];
+        let hunks = file.find("\n@@").map_or(file.len(), |newline| newline + 1);
+        if !binary(&file[hunks..]) {
+            summarized.push_str(file);
+            continue;
+        }
+
+        let mut lines = file[..hunks].lines();
+        let paths = lines
+            .next()
+            .and_then(|line| line.strip_prefix("diff --git "))
+            .unwrap_or_default();
+        summarized.push_str(&format!("diff --git {paths}\n"));
+        for line in lines.filter(|line| !line.starts_with("--- ") && !line.starts_with("+++ ")) {
+            summarized.push_str(&format!("{line}\n"));
+        }
+
+        // diff --git a/old b/new
+        let (old, new) = paths.rsplit_once(" b/").unwrap_or((paths, paths));
+        summarized.push_str(&format!("Binary files {old} and b/{new} differ\n"));
+    }
+
+    summarized
+}
+
+pub fn remote_url(repo_path: &Path) -> Result<String, GitError> {
+    execute_git_command(GitCmd::RemoteUrl, repo_path, None, None).map(|url| url.trim().to_string())
+}
+
+pub fn current_branch(repo_path: &Path) -> Result<String, GitError> {
+    execute_git_command(GitCmd::Branch, repo_path, None, None)
+        .map(|branch| branch.trim().to_string())
+}
+
+// Newest mtime of the repo folder and its index, which git touches whenever it sees changes
+fn last_modified(repo_path: &Path) -> Option<u64> {
+    [
+        repo_path.to_path_buf(),
+        repo_path.join(".git").join("index"),
+    ]
+    .iter()
+    .filter_map(|path| std::fs::metadata(path).ok()?.modified().ok())
+    .max()
+    .and_then(|mtime: SystemTime| mtime.duration_since(UNIX_EPOCH).ok())
+    .map(|mtime| mtime.as_secs())
+}
+
+fn strip_base_path(repo_path: &Path, base_path: &Path) -> String {
+    // Compared by components, so a trailing separator on the base path doesn't matter
+    repo_path
+        .strip_prefix(base_path)
+        .unwrap_or(repo_path)
+        .display()
+        .to_string()
+}
+
+// Sent while searching, with mtimes (in nanoseconds) for the cache
+enum Found {
+    Repo(PathBuf, u128),
+    Folder(PathBuf, u128, bool), // Whether it was searched again instead of taken from the cache
+    Failed(PathBuf, GitError),
+}
+
+// Shared by every folder of one scan
+struct Search<'scan> {
+    dir_cache: &'scan DirCache,
+    options: ScanOptions,
+    root_device: Option<u64>,
+    visited: Mutex<HashSet<DirId>>, // Only filled when following symlinks, to break cycles
+    folder_durations: Mutex<Vec<(PathBuf, Duration)>>,
+    progress: &'scan Progress,
+}
+
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+fn dir_id(_path: &Path, metadata: &Metadata) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(path: &Path, _metadata: &Metadata) -> Option<DirId> {
+    std::fs::canonicalize(path).ok()
+}
+
+// Mount points can't be told apart without device ids, so --one-file-system is Unix only
+#[cfg(unix)]
+fn device(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+fn recursive_repo_search(
+    current_path: &Path,
+    depth: usize,
+    search: &Search<'_>,
+    found_sender: Sender<Found>,
+) -> io::Result<()> {
+    let start = Instant::now();
+    search.progress.folders.fetch_add(1, Ordering::Relaxed);
+
+    let metadata = std::fs::metadata(current_path)?;
+    let mtime = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_err(io::Error::other)?
+        .as_nanos();
+
+    if search.options.one_file_system && device(&metadata) != search.root_device {
+        return Ok(());
+    }
+
+    // Folders reached twice through symlinks are only searched the first time
+    if search.options.follow_symlinks
+        && let Some(dir_id) = dir_id(current_path, &metadata)
+        && !search
+            .visited
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(dir_id)
+    {
+        return Ok(());
+    }
+
+    // Folders only change mtime when their own entries change, so unchanged ones reuse cached subfolders
+    if let Some(cached_dir) = search.dir_cache.get(current_path)
+        && cached_dir.mtime == Some(mtime)
+    {
+        if cached_dir.is_repo {
+            search.progress.repos_found.fetch_add(1, Ordering::Relaxed);
+            found_sender
+                .send(Found::Repo(current_path.to_path_buf(), mtime))
+                .ok();
+            return Ok(());
+        }
+
+        found_sender
+            .send(Found::Folder(current_path.to_path_buf(), mtime, false))
+            .ok();
+        cached_dir
+            .children
+            .par_iter()
+            .map(|subfolder_path| {
+                search_subfolder(subfolder_path, depth + 1, search, &found_sender)
+            })
+            .collect::<Vec<_>>();
+
+        return Ok(());
+    }
+
+    if current_path.join(".git").exists() {
+        search.progress.repos_found.fetch_add(1, Ordering::Relaxed);
+        found_sender
+            .send(Found::Repo(current_path.to_path_buf(), mtime))
+            .ok();
         return Ok(()); // Don't recurse into repos
     }
 
-    let subfolders = std::fs::read_dir(current_path)?;
+    if search
+        .options
+        .max_depth
+        .is_some_and(|max_depth| depth >= max_depth)
+    {
+        found_sender
+            .send(Found::Folder(current_path.to_path_buf(), mtime, true))
+            .ok();
+        return Ok(());
+    }
+
+    // Listed up front so slow folders (ex: on network mounts) show up in --timings
+    let subfolders = std::fs::read_dir(current_path)?.collect::<Vec<_>>();
+    found_sender
+        .send(Found::Folder(current_path.to_path_buf(), mtime, true))
+        .ok();
+
+    search
+        .folder_durations
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push((current_path.to_path_buf(), start.elapsed()));
+
     subfolders
-        .par_bridge() // Parallel iterate through subfolders
+        .into_par_iter() // Parallel iterate through subfolders
         .map(|subfolder_res| {
-            if let Ok(subfolder) = subfolder_res {
+            if let Ok(subfolder) = subfolder_res
+                && let Ok(file_type) = subfolder.file_type()
+            {
                 let subfolder_path = subfolder.path();
+                let is_dir = if file_type.is_symlink() {
+                    search.options.follow_symlinks && subfolder_path.is_dir()
+                } else {
+                    file_type.is_dir()
+                };
 
-                if subfolder_path.is_dir()
-                    && let Some(subfolder_name) = subfolder_path.file_name()
-                {
+                if is_dir && let Some(subfolder_name) = subfolder_path.file_name() {
                     let subfolder_name_str = subfolder_name.to_str().unwrap_or_default();
 
-                    // Skip hidden directories except .git and common build/dependency directories
+                    // Hidden and build folders are searched like any other (repos can live in
+                    // them), this only skips folders that are both hidden and named like a build
+                    // folder, which no folder is
                     if !(subfolder_name_str.starts_with('.') && subfolder_name_str != ".git")
                         || !(subfolder_name_str == "node_modules"
                             || subfolder_name_str == "target"
                             || subfolder_name_str == "dist"
                             || subfolder_name_str == "build")
                     {
-                        recursive_repo_search(&subfolder_path, repo_path_sender.clone())
-                            .unwrap_or_default();
+                        search_subfolder(&subfolder_path, depth + 1, search, &found_sender);
                     }
                 }
             }
@@ -172,3 +1234,17 @@ fn recursive_repo_search(current_path: &PathBuf, repo_path_sender: Sender<PathBu
 
     Ok(())
 }
+
+fn search_subfolder(
+    subfolder_path: &Path,
+    depth: usize,
+    search: &Search<'_>,
+    found_sender: &Sender<Found>,
+) {
+    if let Err(e) = recursive_repo_search(subfolder_path, depth, search, found_sender.clone()) {
+        let error = GitError::new(format!("Folder could not be searched: {e}"));
+        found_sender
+            .send(Found::Failed(subfolder_path.to_path_buf(), error))
+            .ok();
+    }
+}
@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+
+use ratatui::crossterm::event::KeyCode;
+use serde::Deserialize;
+
+use crate::filter::StateFilter;
+
+// Everything a key can be bound to in the diff TUI, named as in the [keys] config table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    SelectNext,
+    SelectPrevious,
+    FocusList,
+    FocusDiff,
+    SelectFirst,
+    SelectLast,
+    CycleSort,
+    CycleGroup,
+    ToggleGroup,
+    Filter(StateFilter),
+    ToggleHideClean,
+    ClearFilters,
+    SearchRepos,
+    OpenEditor,
+    OpenShell,
+    OpenGitClient,
+    OpenBrowser,
+    ShrinkSplit,
+    GrowSplit,
+    CycleOrientation,
+    ToggleZoom,
+    ToggleFooter,
+    SearchDiff,
+    NextMatch,
+    PreviousMatch,
+    NextHunk,
+    PreviousHunk,
+    NextFile,
+    PreviousFile,
+    LoadFullDiff,
+    ToggleDiffStat,
+    ToggleIgnoreWhitespace,
+    ToggleIgnoreBlankLines,
+    MoreContext,
+    LessContext,
+    CycleRenames,
+    ToggleWordDiff,
+    CycleDiffAlgorithm,
+    ReviewChanges,
+    StageHunk,
+    StageFile,
+    UnstageHunk,
+    UnstageFile,
+    DiscardHunk,
+    DiscardFile,
+    Commit,
+    Help,
+}
+
+// Key sequences bound to one action in the config, ex: select_first = ["gg", "<Home>"]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyBindings {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    Action(Action),
+    Pending, // The keys so far start a longer binding
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyCode>, Action)>,
+}
+
+impl Action {
+    // Order actions are listed in by the help overlay
+    pub const ALL: [Action; 54] = [
+        Action::SelectNext,
+        Action::SelectPrevious,
+        Action::FocusList,
+        Action::FocusDiff,
+        Action::SelectFirst,
+        Action::SelectLast,
+        Action::CycleSort,
+        Action::CycleGroup,
+        Action::ToggleGroup,
+        Action::Filter(StateFilter::Dirty),
+        Action::Filter(StateFilter::Clean),
+        Action::Filter(StateFilter::Unpushed),
+        Action::Filter(StateFilter::Unstaged),
+        Action::Filter(StateFilter::Staged),
+        Action::Filter(StateFilter::Conflicted),
+        Action::Filter(StateFilter::Error),
+        Action::ToggleHideClean,
+        Action::ClearFilters,
+        Action::SearchRepos,
+        Action::OpenEditor,
+        Action::OpenShell,
+        Action::OpenGitClient,
+        Action::OpenBrowser,
+        Action::ShrinkSplit,
+        Action::GrowSplit,
+        Action::CycleOrientation,
+        Action::ToggleZoom,
+        Action::ToggleFooter,
+        Action::SearchDiff,
+        Action::NextMatch,
+        Action::PreviousMatch,
+        Action::NextHunk,
+        Action::PreviousHunk,
+        Action::NextFile,
+        Action::PreviousFile,
+        Action::LoadFullDiff,
+        Action::ToggleDiffStat,
+        Action::ToggleIgnoreWhitespace,
+        Action::ToggleIgnoreBlankLines,
+        Action::MoreContext,
+        Action::LessContext,
+        Action::CycleRenames,
+        Action::ToggleWordDiff,
+        Action::CycleDiffAlgorithm,
+        Action::ReviewChanges,
+        Action::StageHunk,
+        Action::StageFile,
+        Action::UnstageHunk,
+        Action::UnstageFile,
+        Action::DiscardHunk,
+        Action::DiscardFile,
+        Action::Commit,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    // Actions hinted at in the footer, the help overlay lists the rest. Hints that don't fit are
+    // dropped from the end, but the last one (help) is always kept
+    pub const FOOTER: [Action; 20] = [
+        Action::SelectPrevious,
+        Action::SelectNext,
+        Action::FocusList,
+        Action::FocusDiff,
+        Action::SelectFirst,
+        Action::SelectLast,
+        Action::CycleSort,
+        Action::CycleGroup,
+        Action::ToggleGroup,
+        Action::ToggleHideClean,
+        Action::ClearFilters,
+        Action::OpenEditor,
+        Action::OpenShell,
+        Action::OpenGitClient,
+        Action::OpenBrowser,
+        Action::ToggleZoom,
+        Action::SearchDiff,
+        Action::ReviewChanges,
+        Action::Quit,
+        Action::Help,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::SelectNext => "select_next",
+            Action::SelectPrevious => "select_previous",
+            Action::FocusList => "focus_list",
+            Action::FocusDiff => "focus_diff",
+            Action::SelectFirst => "select_first",
+            Action::SelectLast => "select_last",
+            Action::CycleSort => "cycle_sort",
+            Action::CycleGroup => "cycle_group",
+            Action::ToggleGroup => "toggle_group",
+            Action::Filter(StateFilter::Dirty) => "filter_dirty",
+            Action::Filter(StateFilter::Clean) => "filter_clean",
+            Action::Filter(StateFilter::Unpushed) => "filter_unpushed",
+            Action::Filter(StateFilter::Unstaged) => "filter_unstaged",
+            Action::Filter(StateFilter::Staged) => "filter_staged",
+            Action::Filter(StateFilter::Conflicted) => "filter_conflicted",
+            Action::Filter(StateFilter::Error) => "filter_error",
+            Action::ToggleHideClean => "toggle_hide_clean",
+            Action::ClearFilters => "clear_filters",
+            Action::SearchRepos => "search_repos",
+            Action::OpenEditor => "open_editor",
+            Action::OpenShell => "open_shell",
+            Action::OpenGitClient => "open_git_client",
+            Action::OpenBrowser => "open_browser",
+            Action::ShrinkSplit => "shrink_split",
+            Action::GrowSplit => "grow_split",
+            Action::CycleOrientation => "cycle_orientation",
+            Action::ToggleZoom => "toggle_zoom",
+            Action::ToggleFooter => "toggle_footer",
+            Action::SearchDiff => "search_diff",
+            Action::NextMatch => "next_match",
+            Action::PreviousMatch => "previous_match",
+            Action::NextHunk => "next_hunk",
+            Action::PreviousHunk => "previous_hunk",
+            Action::NextFile => "next_file",
+            Action::PreviousFile => "previous_file",
+            Action::LoadFullDiff => "load_full_diff",
+            Action::ToggleDiffStat => "toggle_diff_stat",
+            Action::ToggleIgnoreWhitespace => "toggle_ignore_whitespace",
+            Action::ToggleIgnoreBlankLines => "toggle_ignore_blank_lines",
+            Action::MoreContext => "more_context",
+            Action::LessContext => "less_context",
+            Action::CycleRenames => "cycle_renames",
+            Action::ToggleWordDiff => "toggle_word_diff",
+            Action::CycleDiffAlgorithm => "cycle_diff_algorithm",
+            Action::ReviewChanges => "review_changes",
+            Action::StageHunk => "stage_hunk",
+            Action::StageFile => "stage_file",
+            Action::UnstageHunk => "unstage_hunk",
+            Action::UnstageFile => "unstage_file",
+            Action::DiscardHunk => "discard_hunk",
+            Action::DiscardFile => "discard_file",
+            Action::Commit => "commit",
+            Action::Help => "help",
+        }
+    }
+
+    pub fn description(self) -> String {
+        let description = match self {
+            Action::Quit => "Quit",
+            Action::SelectNext => "Move down, or scroll the diff down when it's focused",
+            Action::SelectPrevious => "Move up, or scroll the diff up when it's focused",
+            Action::FocusList => "Focus the repo list",
+            Action::FocusDiff => "Focus the diff preview",
+            Action::SelectFirst => "Go to the top",
+            Action::SelectLast => "Go to the bottom",
+            Action::CycleSort => "Cycle the sort order",
+            Action::CycleGroup => "Cycle the grouping",
+            Action::ToggleGroup => "Collapse or expand the selected group",
+            Action::Filter(filter) => {
+                return format!("Toggle showing only {} repos", filter.label());
+            }
+            Action::ToggleHideClean => "Hide clean repos",
+            Action::ClearFilters => "Clear filters",
+            Action::SearchRepos => "Only list repos with changes matching a regex",
+            Action::OpenEditor => "Open the repo in your editor",
+            Action::OpenShell => "Open a shell in the repo",
+            Action::OpenGitClient => "Open the repo in your git client",
+            Action::OpenBrowser => "Open the remote in your browser",
+            Action::ShrinkSplit => "Shrink the repo list",
+            Action::GrowSplit => "Grow the repo list",
+            Action::CycleOrientation => "Cycle panes between auto, side by side and stacked",
+            Action::ToggleZoom => "Zoom the diff to full screen",
+            Action::ToggleFooter => "Hide or show the footer",
+            Action::SearchDiff => "Search the diff with a regex (case-sensitive with capitals)",
+            Action::NextMatch => "Jump to the next search match",
+            Action::PreviousMatch => "Jump to the previous search match",
+            Action::NextHunk => "Jump to the next hunk, or select it while reviewing changes",
+            Action::PreviousHunk => {
+                "Jump to the previous hunk, or select it while reviewing changes"
+            }
+            Action::NextFile => "Jump to the next file, or select it while reviewing changes",
+            Action::PreviousFile => {
+                "Jump to the previous file, or select it while reviewing changes"
+            }
+            Action::LoadFullDiff => "Load all of a diff cut off at its max size",
+            Action::ToggleDiffStat => "Show the changed files with bars instead of the diff",
+            Action::ToggleIgnoreWhitespace => "Ignore whitespace in diffs",
+            Action::ToggleIgnoreBlankLines => "Ignore added or removed blank lines in diffs",
+            Action::MoreContext => "Show more lines around each change",
+            Action::LessContext => "Show fewer lines around each change",
+            Action::CycleRenames => "Cycle detecting renamed files, copies too, or neither",
+            Action::ToggleWordDiff => "Show changed words inside lines instead of whole lines",
+            Action::CycleDiffAlgorithm => {
+                "Cycle the diff algorithm (default, myers, patience, histogram)"
+            }
+            Action::ReviewChanges => "Review staged and unstaged changes of the repo",
+            Action::StageHunk => "Stage the selected hunk",
+            Action::StageFile => "Stage the file of the selected hunk",
+            Action::UnstageHunk => "Unstage the selected hunk",
+            Action::UnstageFile => "Unstage the file of the selected hunk",
+            Action::DiscardHunk => "Discard the selected hunk, after asking",
+            Action::DiscardFile => "Discard the file of the selected hunk, after asking",
+            Action::Commit => "Write a message and commit the staged changes",
+            Action::Help => "Show this help",
+        };
+
+        description.to_string()
+    }
+
+    // Short label in the footer
+    fn hint(self) -> &'static str {
+        match self {
+            Action::SelectPrevious => "move up",
+            Action::SelectNext => "move down",
+            Action::FocusList => "move left",
+            Action::FocusDiff => "move right",
+            Action::SelectFirst => "top",
+            Action::SelectLast => "bottom",
+            Action::CycleSort => "sort",
+            Action::CycleGroup => "group",
+            Action::ToggleGroup => "collapse group",
+            Action::ToggleHideClean => "hide clean",
+            Action::ClearFilters => "clear filters",
+            Action::OpenEditor => "editor",
+            Action::OpenShell => "shell",
+            Action::OpenGitClient => "git client",
+            Action::OpenBrowser => "browser",
+            Action::ToggleZoom => "zoom diff",
+            Action::SearchDiff => "search",
+            Action::ReviewChanges => "stage",
+            Action::Help => "all keys",
+            Action::Quit => "quit",
+            _ => self.name(),
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q", "<Esc>"],
+            Action::SelectNext => &["j", "<Down>"],
+            Action::SelectPrevious => &["k", "<Up>"],
+            Action::FocusList => &["h", "<Left>"],
+            Action::FocusDiff => &["l", "<Right>"],
+            Action::SelectFirst => &["g", "<PageUp>"],
+            Action::SelectLast => &["G", "<PageDown>"],
+            Action::CycleSort => &["s"],
+            Action::CycleGroup => &["v"],
+            Action::ToggleGroup => &["<Enter>", "<Space>"],
+            Action::Filter(StateFilter::Dirty) => &["1"],
+            Action::Filter(StateFilter::Clean) => &["2"],
+            Action::Filter(StateFilter::Unpushed) => &["3"],
+            Action::Filter(StateFilter::Unstaged) => &["4"],
+            Action::Filter(StateFilter::Staged) => &["5"],
+            Action::Filter(StateFilter::Conflicted) => &["6"],
+            Action::Filter(StateFilter::Error) => &["7"],
+            Action::ToggleHideClean => &["c"],
+            Action::ClearFilters => &["0"],
+            Action::SearchRepos => &["F"],
+            Action::OpenEditor => &["e"],
+            Action::OpenShell => &["!"],
+            Action::OpenGitClient => &["t"],
+            Action::OpenBrowser => &["o"],
+            Action::ShrinkSplit => &["["],
+            Action::GrowSplit => &["]"],
+            Action::CycleOrientation => &["L"],
+            Action::ToggleZoom => &["z"],
+            Action::ToggleFooter => &["f"],
+            Action::SearchDiff => &["/"],
+            Action::NextMatch => &["n"],
+            Action::PreviousMatch => &["N"],
+            Action::NextHunk => &["}"],
+            Action::PreviousHunk => &["{"],
+            Action::NextFile => &[")"],
+            Action::PreviousFile => &["("],
+            Action::LoadFullDiff => &["M"],
+            Action::ToggleDiffStat => &["="],
+            Action::ToggleIgnoreWhitespace => &["w"],
+            Action::ToggleIgnoreBlankLines => &["b"],
+            Action::MoreContext => &["+"],
+            Action::LessContext => &["-"],
+            Action::CycleRenames => &["R"],
+            Action::ToggleWordDiff => &["W"],
+            Action::CycleDiffAlgorithm => &["p"],
+            Action::ReviewChanges => &["S"],
+            Action::StageHunk => &["a"],
+            Action::StageFile => &["A"],
+            Action::UnstageHunk => &["u"],
+            Action::UnstageFile => &["U"],
+            Action::DiscardHunk => &["d"],
+            Action::DiscardFile => &["D"],
+            Action::Commit => &["C"],
+            Action::Help => &["?"],
+        }
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .iter()
+            .flat_map(|&action| {
+                action
+                    .default_keys()
+                    .iter()
+                    .filter_map(move |keys| Some((parse_keys(keys).ok()?, action)))
+            })
+            .collect();
+
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    // Default bindings with those of each action in the config replaced
+    pub fn new(overrides: &HashMap<String, KeyBindings>) -> Result<Self, String> {
+        let mut keymap = Self::default();
+
+        let mut overrides = overrides.iter().collect::<Vec<_>>();
+        overrides.sort_by_key(|(name, _)| name.as_str());
+        for (name, bindings) in overrides {
+            let action = Action::ALL
+                .into_iter()
+                .find(|action| action.name() == name)
+                .ok_or_else(|| {
+                    format!("unknown action {name}, see ? in the TUI for all actions")
+                })?;
+
+            let bindings = match bindings {
+                KeyBindings::One(keys) => std::slice::from_ref(keys),
+                KeyBindings::Many(bindings) => bindings.as_slice(),
+            };
+            keymap.bindings.retain(|(_, bound)| *bound != action);
+            for keys in bindings {
+                let keys = parse_keys(keys).map_err(|e| format!("{name}: {e}"))?;
+                if keys.is_empty() {
+                    return Err(format!("{name}: empty key binding"));
+                }
+                keymap.bindings.push((keys, action));
+            }
+        }
+
+        keymap.check_conflicts()?;
+        Ok(keymap)
+    }
+
+    // Bindings are matched as soon as they're complete, so one can't start with another
+    fn check_conflicts(&self) -> Result<(), String> {
+        for (i, (keys, action)) in self.bindings.iter().enumerate() {
+            for (other_keys, other_action) in &self.bindings[i + 1..] {
+                let (shorter, longer) = if keys.len() <= other_keys.len() {
+                    ((keys, action), (other_keys, other_action))
+                } else {
+                    ((other_keys, other_action), (keys, action))
+                };
+
+                if longer.0.starts_with(shorter.0) {
+                    return Err(if shorter.0.len() == longer.0.len() {
+                        format!(
+                            "{} is bound to both {} and {}",
+                            keys_label(shorter.0),
+                            shorter.1.name(),
+                            longer.1.name()
+                        )
+                    } else {
+                        format!(
+                            "{} of {} can never be pressed, {} is bound to {}",
+                            keys_label(longer.0),
+                            longer.1.name(),
+                            keys_label(shorter.0),
+                            shorter.1.name()
+                        )
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn lookup(&self, pressed: &[KeyCode]) -> Lookup {
+        let mut pending = false;
+
+        for (keys, action) in &self.bindings {
+            if keys == pressed {
+                return Lookup::Action(*action);
+            }
+            pending |= keys.starts_with(pressed);
+        }
+
+        if pending {
+            Lookup::Pending
+        } else {
+            Lookup::None
+        }
+    }
+
+    // Every binding of an action, ex: "j/↓"
+    pub fn label(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(keys, _)| keys_label(keys))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    // Footer hints like "(j/↓) move down", for bound actions only
+    pub fn hints(&self) -> Vec<String> {
+        Action::FOOTER
+            .iter()
+            .filter_map(|&action| {
+                let label = self.label(action);
+                (!label.is_empty()).then(|| format!("({label}) {}", action.hint()))
+            })
+            .collect()
+    }
+}
+
+// Parses key sequences like "gg" or "<Down>", special keys go in brackets
+pub fn parse_keys(keys: &str) -> Result<Vec<KeyCode>, String> {
+    let mut codes = Vec::new();
+    let mut chars = keys.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            codes.push(KeyCode::Char(c));
+            continue;
+        }
+
+        let mut name = String::new();
+        let mut closed = false;
+        for c in chars.by_ref() {
+            if c == '>' {
+                closed = true;
+                break;
+            }
+            name.push(c);
+        }
+        if !closed {
+            return Err(format!("<{name} is missing a closing >"));
+        }
+
+        let code = match name.to_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "lt" => KeyCode::Char('<'),
+            _ => {
+                return Err(format!(
+                    "unknown key <{name}>, use enter, esc, space, tab, backspace, up, down, left, right, home, end, pageup, pagedown or lt"
+                ));
+            }
+        };
+        codes.push(code);
+    }
+
+    Ok(codes)
+}
+
+// Display of a key sequence, ex: "gg" or "PgDn"
+pub fn keys_label(keys: &[KeyCode]) -> String {
+    keys.iter()
+        .map(|code| match code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => "Enter".to_string(),
+            KeyCode::Esc => "Esc".to_string(),
+            KeyCode::Tab => "Tab".to_string(),
+            KeyCode::Backspace => "Backspace".to_string(),
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::Home => "Home".to_string(),
+            KeyCode::End => "End".to_string(),
+            KeyCode::PageUp => "PgUp".to_string(),
+            KeyCode::PageDown => "PgDn".to_string(),
+            code => format!("{code:?}"),
+        })
+        .collect()
+}
@@ -0,0 +1,59 @@
+//! Discovery and status engine of [fuzzit](https://github.com/dawitalemu4/fuzzit): finds every git
+//! repo below a base path and classifies its state from `git status` and a diff against upstream.
+//! Scans only keep the changed line counts of that diff, [`git_data::load_diff`] loads the diff.
+//!
+//! ```no_run
+//! use fuzzit::{RepoState, Scan};
+//!
+//! // Repos are yielded as soon as git is done with them
+//! for repo in Scan::new("~/dev").stream() {
+//!     let repo = repo?;
+//!     if repo.state() != RepoState::Clean {
+//!         println!("{} .. {}", repo.name, repo.state().label());
+//!     }
+//! }
+//! # Ok::<(), fuzzit::Error>(())
+//! ```
+//!
+//! Scans share the cache of the fuzzit binary (`$XDG_CACHE_HOME/fuzzit`), so only folders and repos
+//! that changed since the last scan are read again.
+//!
+//! The items re-exported here are the stable API. Their structs and enums are `#[non_exhaustive]`,
+//! so fields and variants can be added without a breaking release. The other public modules are
+//! what the fuzzit binary is built from and may change in any release.
+
+pub mod git_data;
+pub mod scan;
+
+#[doc(hidden)]
+pub mod cache;
+#[doc(hidden)]
+pub mod config;
+#[cfg(unix)]
+#[doc(hidden)]
+pub mod daemon;
+#[doc(hidden)]
+pub mod diff;
+#[doc(hidden)]
+pub mod filter;
+#[doc(hidden)]
+pub mod keymap;
+#[doc(hidden)]
+pub mod open;
+#[doc(hidden)]
+pub mod progress;
+#[doc(hidden)]
+pub mod render;
+#[doc(hidden)]
+pub mod sort;
+#[doc(hidden)]
+pub mod stage;
+#[doc(hidden)]
+pub mod status;
+#[doc(hidden)]
+pub mod summary;
+#[doc(hidden)]
+pub mod theme;
+
+pub use git_data::{GitData, GitError, Repo, RepoState, ScanOptions, ScanStats, expand_home};
+pub use scan::{Error, RepoStream, Scan, ScanResult};
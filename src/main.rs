@@ -1,14 +1,28 @@
-use std::path::PathBuf;
+use std::{
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
-use clap::Parser;
-use color_eyre::eyre::Result;
+use clap::{Parser, Subcommand};
+use color_eyre::eyre::{Result, eyre};
+use ratatui::layout::Size;
+use regex::Regex;
 
-pub mod cache;
-pub mod diff;
-pub mod git_data;
-pub mod status;
-
-use git_data::collect_git_data;
+#[cfg(unix)]
+use fuzzit::daemon;
+use fuzzit::{
+    Scan, ScanResult, cache,
+    config::Config,
+    diff, expand_home,
+    filter::{ContentSearch, Filters, StateFilter, smart_case_regex},
+    git_data::{DiffAlgorithm, RenameDetection, Repos, load_diffs, load_last_commits},
+    render::{self, Keys, RenderFormat},
+    sort::{GroupBy, SortBy},
+    status,
+    summary::{self, Summary},
+    theme::ColorChoice,
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -17,16 +31,101 @@ use git_data::collect_git_data;
     about = "Fuzzy nested git repo finder with status and diff previews"
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Simple list of one-line git status summaries (diff TUI by default)
     #[arg(short, long, default_value = "false")]
     status: bool,
     /// Disable ascii art from displaying (false by default)
     #[arg(short, long, default_value = "false")]
     disable_ascii: bool,
-    /// Recreate the cache containing your repo paths (USE ONCE IF NEW REPO ISN'T SHOWING UP,
-    /// inconsistent detection of new repos in nested subfolders across different OS)
+    /// Only print a one-line summary of repo states (for shell prompts and status bars)
+    #[arg(long, default_value = "false")]
+    summary_only: bool,
+    /// Search every folder again and recreate the cache of your repo paths (only folders that
+    /// changed since the last run are searched again by default)
     #[arg(short, long, default_value = "false")]
     refresh_cache: bool,
+    /// Run git for every repo instead of reusing results for repos that haven't changed
+    #[arg(long, default_value = "false")]
+    no_status_cache: bool,
+    /// Folders below the base path to search for repos (unlimited by default)
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// Search symlinked folders too, each folder is only searched once (false by default)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    follow_symlinks: Option<bool>,
+    /// Don't search folders on other file systems, like network mounts (false by default)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    one_file_system: Option<bool>,
+    /// Seconds git may take per repo before the repo is marked TIMEOUT (0 waits forever)
+    #[arg(long, default_value_t = 30)]
+    repo_timeout: u64,
+    /// Print the slowest repos and folders of the scan to stderr
+    #[arg(long, default_value = "false")]
+    timings: bool,
+    /// Scan directly even if a fuzzit daemon is running
+    #[arg(long, default_value = "false")]
+    no_daemon: bool,
+    /// Order to list repos in, cycle with (s) in the diff TUI
+    #[arg(long, value_enum, default_value_t)]
+    sort: SortBy,
+    /// Group repos under headers, cycle with (v) in the diff TUI
+    #[arg(long, value_enum, default_value_t)]
+    group: GroupBy,
+    /// Only list repos in these states, can be repeated (toggle with 1-7 in the diff TUI)
+    #[arg(long, value_enum)]
+    only: Vec<StateFilter>,
+    /// Hide clean repos (toggle with c in the diff TUI)
+    #[arg(long, default_value = "false")]
+    hide_clean: bool,
+    /// Only list repos with added or removed lines matching this regex, case-sensitive only with
+    /// capitals (search with F in the diff TUI)
+    #[arg(long, value_name = "REGEX", value_parser = smart_case_regex)]
+    grep: Option<Regex>,
+    /// Match --grep against the paths of changed and untracked files instead
+    #[arg(long, default_value = "false", requires = "grep")]
+    grep_files: bool,
+    /// Ignore whitespace when comparing lines of diffs (toggle with w in the diff TUI)
+    #[arg(short = 'w', long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    ignore_whitespace: Option<bool>,
+    /// Ignore changes of diffs that only add or remove blank lines (toggle with b in the diff TUI)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    ignore_blank_lines: Option<bool>,
+    /// Lines of context around each change of diffs (3 by default, change with +/- in the diff TUI)
+    #[arg(short = 'U', long, value_name = "LINES")]
+    context: Option<u32>,
+    /// Detect renamed or copied files in diffs (cycle with R in the diff TUI)
+    #[arg(long, value_enum)]
+    renames: Option<RenameDetection>,
+    /// Show changed words inside lines of diffs, as [-removed-]{+added+} (toggle with W in the
+    /// diff TUI)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    word_diff: Option<bool>,
+    /// Algorithm git diffs with (cycle with p in the diff TUI)
+    #[arg(long, value_enum)]
+    diff_algorithm: Option<DiffAlgorithm>,
+    /// Print one frame of the diff TUI at this size to stdout instead of running it (ex: 120x40)
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = render::parse_size)]
+    render_once: Option<Size>,
+    /// Output of --render-once
+    #[arg(long, value_enum, default_value_t, requires = "render_once")]
+    render_format: RenderFormat,
+    /// Keys to press before --render-once draws, special keys in brackets (ex: jjl or <Down><Enter>)
+    #[arg(
+        long,
+        value_name = "KEYS",
+        default_value = "",
+        requires = "render_once"
+    )]
+    render_keys: Keys,
+    /// When to use colors, auto turns them off for NO_COLOR and output that isn't a terminal
+    #[arg(long, value_enum, default_value_t)]
+    color: ColorChoice,
+    /// Config file with commands to open repos with and scan limits per base path
+    /// (~/.config/fuzzit/config.toml by default)
+    #[arg(long, env = "FUZZIT_CONFIG")]
+    config: Option<PathBuf>,
     /// Path to start searching from, takes priority over FUZZIT_BASE_PATH
     #[arg(env)]
     fuzzit_path: Option<PathBuf>,
@@ -35,22 +134,305 @@ struct Args {
     fuzzit_base_path: Option<PathBuf>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage the cached repo paths and statuses
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Keep git data of base paths up to date in memory, so fuzzit can answer instantly
+    Daemon {
+        /// Seconds between polls of the base paths for added and removed repos (repos it already
+        /// knows are checked for changes before each answer)
+        #[arg(long, default_value_t = 60)]
+        interval: u64,
+        /// Stop the running daemon
+        #[arg(long, default_value = "false")]
+        stop: bool,
+        /// Base path to poll from the start (FUZZIT_PATH or FUZZIT_BASE_PATH by default)
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// List each cached base path with its repo count, age and size
+    List,
+    /// Remove the cache of a base path, or every cache if no path is given
+    Clear { path: Option<PathBuf> },
+    /// Remove caches of base paths that no longer exist
+    Prune,
+    /// Print the folder caches are stored in ($XDG_CACHE_HOME/fuzzit or ~/.cache/fuzzit)
+    Path,
+}
+
 fn main() -> Result<()> {
-    color_eyre::install()?;
     let args = Args::parse();
+    let mut eyre_hook = color_eyre::config::HookBuilder::default();
+    if !args.color.enabled(io::stderr().is_terminal()) {
+        eyre_hook = eyre_hook.theme(color_eyre::config::Theme::new());
+    }
+    eyre_hook.install()?;
 
-    let (base_path, git_data) =
-        collect_git_data(args.fuzzit_path, args.fuzzit_base_path, args.refresh_cache)?;
+    // Loaded after the subcommands that don't use it, so they still work with a broken config
+    match args.command {
+        Some(Command::Cache { action }) => return cache_command(action),
+        Some(Command::Daemon {
+            interval,
+            stop,
+            path,
+        }) => {
+            let base_path = path.or(args.fuzzit_path).or(args.fuzzit_base_path);
+            return daemon_command(base_path, interval, stop, args.config);
+        }
+        None => {}
+    }
+    let mut config = Config::load(args.config)?;
 
-    if args.status {
-        status::display(base_path, args.disable_ascii, git_data)?;
+    let base_path = resolve_base_path(args.fuzzit_path, args.fuzzit_base_path)?;
+    let parsed_base_path = expand_home(&base_path)?;
+
+    // Flags take priority over the scan limits configured for the base path
+    let mut scan_options = config.scan_options(&parsed_base_path);
+    if let Some(max_depth) = args.max_depth {
+        scan_options.max_depth = Some(max_depth);
+    }
+    if let Some(follow_symlinks) = args.follow_symlinks {
+        scan_options.follow_symlinks = follow_symlinks;
+    }
+    if let Some(one_file_system) = args.one_file_system {
+        scan_options.one_file_system = one_file_system;
+    }
+
+    let repo_timeout = (args.repo_timeout > 0).then(|| Duration::from_secs(args.repo_timeout));
+    // The daemon scans with the configured options, so it's skipped when flags ask for anything else
+    let use_daemon = !(args.no_daemon
+        || args.refresh_cache
+        || args.no_status_cache
+        || args.max_depth.is_some()
+        || args.follow_symlinks.is_some()
+        || args.one_file_system.is_some()
+        || args.timings);
+    let daemon_data = if use_daemon {
+        query_daemon(&parsed_base_path)
     } else {
-        let mut terminal = ratatui::init();
-        let res = diff::App::new(base_path, git_data).run(&mut terminal);
-        ratatui::restore();
+        None
+    };
+
+    let (base_path, mut git_data, scan_stats) = match daemon_data {
+        Some((repos, stats)) => (base_path, repos, stats),
+        None => {
+            let ScanResult {
+                base_path,
+                repos,
+                stats,
+                ..
+            } = Scan::new(base_path)
+                .options(scan_options)
+                .refresh_cache(args.refresh_cache)
+                .status_cache(!args.no_status_cache)
+                .repo_timeout(repo_timeout)
+                .progress(true)
+                .run()?;
+            (base_path, repos, stats)
+        }
+    };
+    let base_path = base_path.display().to_string();
+
+    if args.timings {
+        writeln!(
+            io::stderr(),
+            "{}\n",
+            summary::timings(&git_data, &scan_stats)
+        )?;
+    }
+
+    if let Some(ignore_whitespace) = args.ignore_whitespace {
+        config.diff.ignore_whitespace = ignore_whitespace;
+    }
+    if let Some(ignore_blank_lines) = args.ignore_blank_lines {
+        config.diff.ignore_blank_lines = ignore_blank_lines;
+    }
+    if let Some(context) = args.context {
+        config.diff.context = Some(context);
+    }
+    if let Some(renames) = args.renames {
+        config.diff.renames = renames;
+    }
+    if let Some(word_diff) = args.word_diff {
+        config.diff.word_diff = word_diff;
+    }
+    if let Some(diff_algorithm) = args.diff_algorithm {
+        config.diff.algorithm = diff_algorithm;
+    }
+
+    // Scans only count changed lines, the diffs themselves are needed to search them
+    if args.grep.is_some() {
+        load_diffs(&mut git_data, &config.diff.options(), repo_timeout);
+    }
+
+    // The TUI loads commit dates itself once it's sorted by them
+    if args.status && args.sort == SortBy::Commit {
+        load_last_commits(&mut git_data, repo_timeout);
+    }
+
+    let filters = Filters {
+        only: args.only,
+        hide_clean: args.hide_clean,
+        grep: args.grep.map(|regex| ContentSearch {
+            regex,
+            file_names: args.grep_files,
+        }),
+    };
+
+    // Frames are drawn for a terminal or an export format, so only NO_COLOR and --color matter
+    // for them, while the status list also goes plain when piped
+    let status_theme = config
+        .styles
+        .clone()
+        .with_color(args.color.enabled(io::stdout().is_terminal()));
+    config.styles = config.styles.with_color(args.color.enabled(true));
+
+    if let Some(size) = args.render_once {
+        let app = diff::App::new(
+            base_path, git_data, args.sort, args.group, filters, scan_stats, config,
+        )
+        .with_repo_timeout(repo_timeout);
+        print!(
+            "{}",
+            render::render_once(app, size, &args.render_keys, args.render_format)?
+        );
+    } else if args.summary_only {
+        println!("{}", Summary::new(&git_data, scan_stats));
+    } else if args.status {
+        let summary = Summary::new(&git_data, scan_stats);
+        if !args.disable_ascii {
+            status::print_ascii_art();
+        }
+        status::display(
+            base_path,
+            git_data,
+            args.sort,
+            args.group,
+            &filters,
+            &summary,
+            &status_theme,
+        )?;
+    } else {
+        let mut terminal = diff::init_terminal();
+        let res = diff::App::new(
+            base_path, git_data, args.sort, args.group, filters, scan_stats, config,
+        )
+        .with_repo_timeout(repo_timeout)
+        .run(&mut terminal);
+        diff::restore_terminal();
 
         res?
     }
 
     Ok(())
 }
+
+fn resolve_base_path(
+    fuzzit_path: Option<PathBuf>,
+    fuzzit_base_path: Option<PathBuf>,
+) -> Result<PathBuf> {
+    fuzzit_path.or(fuzzit_base_path).ok_or_else(|| {
+        eyre!(
+            "Add FUZZIT_BASE_PATH to your environment (ex: ~/.zshrc) or use FUZZIT_PATH before command"
+        )
+    })
+}
+
+#[cfg(unix)]
+fn query_daemon(base_path: &Path) -> Option<(Repos, fuzzit::ScanStats)> {
+    daemon::query_repos(base_path)
+}
+
+#[cfg(not(unix))]
+fn query_daemon(_base_path: &Path) -> Option<(Repos, fuzzit::ScanStats)> {
+    None
+}
+
+#[cfg(unix)]
+fn daemon_command(
+    base_path: Option<PathBuf>,
+    interval: u64,
+    stop: bool,
+    config_path: Option<PathBuf>,
+) -> Result<()> {
+    if stop {
+        daemon::stop()
+    } else {
+        let config = Config::load(config_path)?;
+        daemon::run(base_path, Duration::from_secs(interval.max(1)), config)
+    }
+}
+
+#[cfg(not(unix))]
+fn daemon_command(
+    _base_path: Option<PathBuf>,
+    _interval: u64,
+    _stop: bool,
+    _config_path: Option<PathBuf>,
+) -> Result<()> {
+    Err(eyre!("The daemon is only supported on Unix"))
+}
+
+fn cache_command(action: CacheAction) -> Result<()> {
+    match action {
+        CacheAction::List => {
+            let caches = cache::list_caches();
+            if caches.is_empty() {
+                println!("No caches yet");
+            }
+
+            for cache in caches {
+                let age = cache
+                    .modified
+                    .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                    .map(|age| match age.as_secs() {
+                        secs @ ..60 => format!("{secs}s"),
+                        secs @ ..3600 => format!("{}m", secs / 60),
+                        secs @ ..86400 => format!("{}h", secs / 3600),
+                        secs => format!("{}d", secs / 86400),
+                    })
+                    .unwrap_or_else(|| "?".to_string());
+
+                match cache.base_path {
+                    Some(base_path) => println!(
+                        "{} .. {} repos, {age} old, {:.1} KiB",
+                        base_path.display(),
+                        cache.repo_count,
+                        cache.size as f64 / 1024.0
+                    ),
+                    None => println!(
+                        "{} .. outdated or corrupt (remove with fuzzit cache prune)",
+                        cache.cache_path.display()
+                    ),
+                }
+            }
+        }
+        CacheAction::Clear { path } => {
+            let base_path = path.as_deref().map(expand_home).transpose()?;
+            let removed = cache::clear_caches(base_path.as_deref());
+            println!("Removed {removed} cache file(s)");
+        }
+        CacheAction::Prune => {
+            let pruned = cache::prune_caches();
+            for cache in &pruned {
+                let path = cache.base_path.as_ref().unwrap_or(&cache.cache_path);
+                println!("Removed {}", path.display());
+            }
+            println!("Pruned {} cache(s)", pruned.len());
+        }
+        CacheAction::Path => {
+            let cache_dir = cache::get_cache_dir()
+                .ok_or_else(|| eyre!("Cache folder could not be determined"))?;
+            println!("{}", cache_dir.display());
+        }
+    }
+
+    Ok(())
+}
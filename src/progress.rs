@@ -0,0 +1,79 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{
+        Condvar, Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+// Quick scans finish before anything is drawn, so shell prompts don't flicker
+const DELAY: Duration = Duration::from_millis(300);
+const INTERVAL: Duration = Duration::from_millis(100);
+
+// Counters of a running scan, drawn on stderr so stdout stays clean for --status and --summary-only
+#[derive(Debug, Default)]
+pub struct Progress {
+    pub folders: AtomicUsize,
+    pub repos_found: AtomicUsize,
+    pub repos_done: AtomicUsize,
+    pub repos_total: AtomicUsize, // Set once discovery is done
+    done: Mutex<bool>,
+    done_changed: Condvar,
+}
+
+impl Progress {
+    pub fn finish(&self) {
+        *self.done.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.done_changed.notify_all();
+    }
+
+    fn line(&self) -> String {
+        let repos_total = self.repos_total.load(Ordering::Relaxed);
+
+        if repos_total == 0 {
+            format!(
+                "Searching folders: {} visited, {} repos found",
+                self.folders.load(Ordering::Relaxed),
+                self.repos_found.load(Ordering::Relaxed)
+            )
+        } else {
+            format!(
+                "Reading repos: {}/{repos_total}",
+                self.repos_done.load(Ordering::Relaxed)
+            )
+        }
+    }
+
+    // Redraws one line until finish is called, nothing is drawn when stderr isn't a terminal
+    pub fn report(&self) {
+        let mut stderr = io::stderr();
+        if !stderr.is_terminal() {
+            return;
+        }
+
+        let start = Instant::now();
+        let mut drawn = String::new();
+        let mut done = self.done.lock().unwrap_or_else(PoisonError::into_inner);
+
+        while !*done {
+            done = self
+                .done_changed
+                .wait_timeout(done, INTERVAL)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+
+            let line = self.line();
+            if !*done && start.elapsed() >= DELAY && line != drawn {
+                write!(stderr, "\r\x1b[K{line}").ok();
+                stderr.flush().ok();
+                drawn = line;
+            }
+        }
+
+        if !drawn.is_empty() {
+            write!(stderr, "\r\x1b[K").ok();
+            stderr.flush().ok();
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::{fmt::Write, str::FromStr};
+
+use clap::ValueEnum;
+use color_eyre::eyre::Result;
+use ratatui::{
+    Terminal,
+    backend::TestBackend,
+    buffer::{Buffer, Cell},
+    crossterm::event::KeyCode,
+    layout::Size,
+    style::{Color, Modifier},
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    diff::{App, KeyAction},
+    keymap::parse_keys,
+    theme::sgr,
+};
+
+// Pixel sizes of one cell in SVG exports, close to a 14px monospace font
+const CELL_WIDTH: f32 = 8.4;
+const CELL_HEIGHT: f32 = 18.0;
+const FONT_SIZE: u16 = 14;
+
+// Colors used where the terminal would use its own defaults
+const DEFAULT_FG: &str = "#d4d4d4";
+const DEFAULT_BG: &str = "#1e1e1e";
+const FONT_FAMILY: &str = "'SFMono-Regular', Menlo, Consolas, 'DejaVu Sans Mono', monospace";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum RenderFormat {
+    /// Plain text, without colors
+    #[default]
+    Text,
+    /// Text with ANSI color escape codes
+    Ansi,
+    /// Standalone SVG image
+    Svg,
+    /// HTML <pre> block with inline styles
+    Html,
+}
+
+// Keys pressed before a headless frame is drawn, ex: "jjl" or "<Down><Enter>"
+#[derive(Debug, Clone, Default)]
+pub struct Keys(pub Vec<KeyCode>);
+
+impl FromStr for Keys {
+    type Err = String;
+
+    fn from_str(keys: &str) -> Result<Self, Self::Err> {
+        parse_keys(keys).map(Self)
+    }
+}
+
+// Parses sizes like 120x40
+pub fn parse_size(size: &str) -> Result<Size, String> {
+    let (width, height) = size
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("{size} is not a size like 120x40"))?;
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|&value| value > 0)
+            .ok_or_else(|| format!("{value} is not a positive number of cells"))
+    };
+
+    Ok(Size::new(parse(width)?, parse(height)?))
+}
+
+// Presses keys on the app like the TUI would, then draws one frame off-screen
+pub fn render_once(mut app: App, size: Size, keys: &Keys, format: RenderFormat) -> Result<String> {
+    for &key in &keys.0 {
+        // Opening repos needs a terminal, so those keys are ignored
+        if let Some(KeyAction::Quit) = app.handle_key(key) {
+            break;
+        }
+    }
+
+    let mut terminal = Terminal::new(TestBackend::new(size.width, size.height))?;
+    terminal.draw(|frame| app.render(frame))?;
+    let buffer = terminal.backend().buffer();
+
+    Ok(match format {
+        RenderFormat::Text => to_text(buffer),
+        RenderFormat::Ansi => to_ansi(buffer),
+        RenderFormat::Svg => to_svg(buffer),
+        RenderFormat::Html => to_html(buffer),
+    })
+}
+
+// Cells of one row grouped by style, with the column each group starts at
+fn runs(buffer: &Buffer, y: u16) -> Vec<(u16, &Cell, String)> {
+    let mut runs: Vec<(u16, &Cell, String)> = Vec::new();
+    let mut skip = 0;
+
+    for x in 0..buffer.area.width {
+        // Cells covered by a wide character before them are left out
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+
+        let cell = &buffer[(x, y)];
+        let symbol = if cell.modifier.contains(Modifier::HIDDEN) {
+            " "
+        } else {
+            cell.symbol()
+        };
+        skip = symbol.width().saturating_sub(1);
+
+        match runs.last_mut() {
+            Some((_, last, text))
+                if last.fg == cell.fg && last.bg == cell.bg && last.modifier == cell.modifier =>
+            {
+                text.push_str(symbol);
+            }
+            _ => runs.push((x, cell, symbol.to_string())),
+        }
+    }
+
+    runs
+}
+
+fn to_text(buffer: &Buffer) -> String {
+    (0..buffer.area.height)
+        .map(|y| {
+            let line: String = runs(buffer, y)
+                .into_iter()
+                .map(|(_, _, text)| text)
+                .collect();
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+fn to_ansi(buffer: &Buffer) -> String {
+    let mut ansi = String::new();
+
+    for y in 0..buffer.area.height {
+        for (_, cell, text) in runs(buffer, y) {
+            write!(ansi, "\x1b[{}m{text}", sgr(cell.style())).ok();
+        }
+        ansi.push_str("\x1b[0m\n");
+    }
+
+    ansi
+}
+
+// Hex color of a cell color, None for the default foreground or background
+fn hex_color(color: Color) -> Option<String> {
+    const ANSI: [(u8, u8, u8); 16] = [
+        (0x00, 0x00, 0x00),
+        (0xcd, 0x31, 0x31),
+        (0x0d, 0xbc, 0x79),
+        (0xe5, 0xe5, 0x10),
+        (0x24, 0x72, 0xc8),
+        (0xbc, 0x3f, 0xbc),
+        (0x11, 0xa8, 0xcd),
+        (0xe5, 0xe5, 0xe5),
+        (0x66, 0x66, 0x66),
+        (0xf1, 0x4c, 0x4c),
+        (0x23, 0xd1, 0x8b),
+        (0xf5, 0xf5, 0x43),
+        (0x3b, 0x8e, 0xea),
+        (0xd6, 0x70, 0xd6),
+        (0x29, 0xb8, 0xdb),
+        (0xff, 0xff, 0xff),
+    ];
+
+    let (r, g, b) = match color {
+        Color::Reset => return None,
+        Color::Black => ANSI[0],
+        Color::Red => ANSI[1],
+        Color::Green => ANSI[2],
+        Color::Yellow => ANSI[3],
+        Color::Blue => ANSI[4],
+        Color::Magenta => ANSI[5],
+        Color::Cyan => ANSI[6],
+        Color::Gray => ANSI[7],
+        Color::DarkGray => ANSI[8],
+        Color::LightRed => ANSI[9],
+        Color::LightGreen => ANSI[10],
+        Color::LightYellow => ANSI[11],
+        Color::LightBlue => ANSI[12],
+        Color::LightMagenta => ANSI[13],
+        Color::LightCyan => ANSI[14],
+        Color::White => ANSI[15],
+        Color::Indexed(i @ 0..16) => ANSI[usize::from(i)],
+        Color::Indexed(i @ 16..232) => {
+            // 6x6x6 color cube
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let i = i - 16;
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        Color::Indexed(i) => {
+            let gray = 8 + (i - 232) * 10;
+            (gray, gray, gray)
+        }
+        Color::Rgb(r, g, b) => (r, g, b),
+    };
+
+    Some(format!("#{r:02x}{g:02x}{b:02x}"))
+}
+
+// Foreground and background hex colors of a cell, swapped when reversed
+fn cell_colors(cell: &Cell) -> (String, String) {
+    let fg = hex_color(cell.fg).unwrap_or_else(|| DEFAULT_FG.to_string());
+    let bg = hex_color(cell.bg).unwrap_or_else(|| DEFAULT_BG.to_string());
+
+    if cell.modifier.contains(Modifier::REVERSED) {
+        (bg, fg)
+    } else {
+        (fg, bg)
+    }
+}
+
+// CSS of a run, shared by the SVG and HTML exports
+fn font_style(cell: &Cell) -> String {
+    let mut style = String::new();
+
+    if cell.modifier.contains(Modifier::BOLD) {
+        style.push_str("font-weight:bold;");
+    }
+    if cell.modifier.contains(Modifier::ITALIC) {
+        style.push_str("font-style:italic;");
+    }
+    if cell.modifier.contains(Modifier::DIM) {
+        style.push_str("opacity:0.6;");
+    }
+    match (
+        cell.modifier.contains(Modifier::UNDERLINED),
+        cell.modifier.contains(Modifier::CROSSED_OUT),
+    ) {
+        (true, true) => style.push_str("text-decoration:underline line-through;"),
+        (true, false) => style.push_str("text-decoration:underline;"),
+        (false, true) => style.push_str("text-decoration:line-through;"),
+        (false, false) => {}
+    }
+
+    style
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn to_svg(buffer: &Buffer) -> String {
+    let width = f32::from(buffer.area.width) * CELL_WIDTH;
+    let height = f32::from(buffer.area.height) * CELL_HEIGHT;
+
+    let mut backgrounds = String::new();
+    let mut texts = String::new();
+    for y in 0..buffer.area.height {
+        let top = f32::from(y) * CELL_HEIGHT;
+
+        for (x, cell, text) in runs(buffer, y) {
+            let left = f32::from(x) * CELL_WIDTH;
+            let (fg, bg) = cell_colors(cell);
+
+            if bg != DEFAULT_BG {
+                write!(
+                    backgrounds,
+                    r#"<rect x="{left:.1}" y="{top:.1}" width="{:.1}" height="{CELL_HEIGHT:.1}" fill="{bg}"/>"#,
+                    text.width() as f32 * CELL_WIDTH
+                )
+                .ok();
+            }
+
+            if !text.trim().is_empty() {
+                let style = font_style(cell);
+                let style = if style.is_empty() {
+                    String::new()
+                } else {
+                    format!(r#" style="{style}""#)
+                };
+
+                // Each run is placed on its own, so fonts a bit off the cell width don't drift
+                write!(
+                    texts,
+                    r#"<text x="{left:.1}" y="{:.1}" fill="{fg}"{style} xml:space="preserve">{}</text>"#,
+                    top + CELL_HEIGHT * 0.75,
+                    escape_xml(&text)
+                )
+                .ok();
+            }
+        }
+    }
+
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width:.0}" height="{height:.0}" viewBox="0 0 {width:.1} {height:.1}" font-family="{FONT_FAMILY}" font-size="{FONT_SIZE}">
+<rect width="100%" height="100%" fill="{DEFAULT_BG}"/>
+{backgrounds}
+{texts}
+</svg>
+"#
+    )
+}
+
+fn to_html(buffer: &Buffer) -> String {
+    let mut html = format!(
+        r#"<pre style="background:{DEFAULT_BG};color:{DEFAULT_FG};font-family:{FONT_FAMILY};font-size:{FONT_SIZE}px;line-height:{CELL_HEIGHT}px;padding:8px;display:inline-block">"#
+    );
+
+    for y in 0..buffer.area.height {
+        for (_, cell, text) in runs(buffer, y) {
+            let (fg, bg) = cell_colors(cell);
+            let mut style = font_style(cell);
+            if fg != DEFAULT_FG {
+                write!(style, "color:{fg};").ok();
+            }
+            if bg != DEFAULT_BG {
+                write!(style, "background:{bg};").ok();
+            }
+
+            if style.is_empty() {
+                html.push_str(&escape_xml(&text));
+            } else {
+                write!(
+                    html,
+                    r#"<span style="{style}">{}</span>"#,
+                    escape_xml(&text)
+                )
+                .ok();
+            }
+        }
+        html.push('\n');
+    }
+
+    html.push_str("</pre>\n");
+    html
+}
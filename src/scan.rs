@@ -0,0 +1,201 @@
+use std::{
+    error, fmt, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::git_data::{DEFAULT_REPO_TIMEOUT, Repo, ScanOptions, ScanStats, collect_git_data};
+
+/// Errors that stop a whole scan, failures of single repos or folders are reported per repo in
+/// [`GitData::error`](crate::GitData::error) instead.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The base path starts with `~` but the home directory could not be determined.
+    HomeDirNotFound,
+    /// The base path could not be read.
+    BasePath { path: PathBuf, source: io::Error },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::HomeDirNotFound => write!(
+                f,
+                "Home directory could not be determined, please use full path for FUZZIT_BASE_PATH or FUZZIT_PATH"
+            ),
+            Error::BasePath { path, .. } => write!(f, "{} could not be searched", path.display()),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::HomeDirNotFound => None,
+            Error::BasePath { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Builder for a scan of every git repo below a base path.
+///
+/// ```no_run
+/// let result = fuzzit::Scan::new("~/dev").max_depth(Some(3)).run()?;
+/// for repo in &result.repos {
+///     println!("{} .. {}", repo.name, repo.state().label());
+/// }
+/// # Ok::<(), fuzzit::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scan {
+    pub(crate) base_path: PathBuf,
+    pub(crate) options: ScanOptions,
+    pub(crate) cache: bool,
+    pub(crate) refresh_cache: bool,
+    pub(crate) status_cache: bool,
+    pub(crate) repo_timeout: Option<Duration>,
+    pub(crate) progress: bool,
+}
+
+/// Repos found by [`Scan::run`], sorted by name.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ScanResult {
+    /// Base path as given to [`Scan::new`] (`~` is not expanded).
+    pub base_path: PathBuf,
+    pub repos: Vec<Repo>,
+    pub stats: ScanStats,
+}
+
+impl Scan {
+    /// Scans `base_path` (a leading `~` is expanded) with no depth limit, without following
+    /// symlinks, using the cache and a 30 second git timeout per repo.
+    pub fn new(base_path: impl Into<PathBuf>) -> Self {
+        Self {
+            base_path: base_path.into(),
+            options: ScanOptions::default(),
+            cache: true,
+            refresh_cache: false,
+            status_cache: true,
+            repo_timeout: Some(DEFAULT_REPO_TIMEOUT),
+            progress: false,
+        }
+    }
+
+    pub fn base_path(&self) -> &Path {
+        &self.base_path
+    }
+
+    /// Replaces every discovery limit at once.
+    pub fn options(mut self, options: ScanOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Folders below the base path to search, `Some(1)` only searches its direct children.
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.options.max_depth = max_depth;
+        self
+    }
+
+    /// Search symlinked folders too, each folder is only searched once so cycles are safe.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.options.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Don't search folders on other file systems than the base path (Unix only).
+    pub fn one_file_system(mut self, one_file_system: bool) -> Self {
+        self.options.one_file_system = one_file_system;
+        self
+    }
+
+    /// Read and write the cache shared with the fuzzit binary (on by default).
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Search every folder again instead of only the ones that changed since the last scan.
+    pub fn refresh_cache(mut self, refresh_cache: bool) -> Self {
+        self.refresh_cache = refresh_cache;
+        self
+    }
+
+    /// Reuse git output of repos that haven't changed since the last scan (on by default).
+    pub fn status_cache(mut self, status_cache: bool) -> Self {
+        self.status_cache = status_cache;
+        self
+    }
+
+    /// Time git may take per repo before the repo is reported as
+    /// [`RepoState::Timeout`](crate::RepoState::Timeout), `None` waits forever.
+    pub fn repo_timeout(mut self, repo_timeout: Option<Duration>) -> Self {
+        self.repo_timeout = repo_timeout;
+        self
+    }
+
+    /// Draw a progress line on stderr while scanning, when stderr is a terminal.
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Scans and waits for every repo.
+    pub fn run(&self) -> Result<ScanResult, Error> {
+        collect_git_data(self, None)
+    }
+
+    /// Scans in the background, yielding each repo as soon as git is done with it.
+    pub fn stream(self) -> RepoStream {
+        let (repo_sender, repo_receiver) = mpsc::channel();
+        let scan = thread::spawn(move || {
+            collect_git_data(&self, Some(&repo_sender)).map(|result| result.stats)
+        });
+
+        RepoStream {
+            repo_receiver,
+            scan: Some(scan),
+            stats: None,
+        }
+    }
+}
+
+/// Iterator over the repos of a [`Scan::stream`], in the order they finish. Ends with an error
+/// if the scan failed.
+#[derive(Debug)]
+pub struct RepoStream {
+    repo_receiver: Receiver<Repo>,
+    scan: Option<JoinHandle<Result<ScanStats, Error>>>,
+    stats: Option<ScanStats>,
+}
+
+impl RepoStream {
+    /// Stats of the scan, once every repo has been yielded.
+    pub fn stats(&self) -> Option<&ScanStats> {
+        self.stats.as_ref()
+    }
+}
+
+impl Iterator for RepoStream {
+    type Item = Result<Repo, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Ok(repo) = self.repo_receiver.recv() {
+            return Some(Ok(repo));
+        }
+
+        // The sender is dropped once the scan is done
+        match self.scan.take()?.join() {
+            Ok(Ok(stats)) => {
+                self.stats = Some(stats);
+                None
+            }
+            Ok(Err(e)) => Some(Err(e)),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
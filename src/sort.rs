@@ -0,0 +1,134 @@
+use std::{cmp::Reverse, collections::BTreeMap, path::Path};
+
+use clap::ValueEnum;
+
+use crate::{filter::Filters, git_data::Repo};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    /// Repo path a-z
+    #[default]
+    Path,
+    /// State severity (conflicts, unpushed, staged, unstaged, clean)
+    State,
+    /// Last commit date, newest first
+    Commit,
+    /// Last modification time, newest first
+    Modified,
+    /// Number of changed lines, most first
+    Changes,
+}
+
+impl SortBy {
+    pub fn next(self) -> Self {
+        match self {
+            SortBy::Path => SortBy::State,
+            SortBy::State => SortBy::Commit,
+            SortBy::Commit => SortBy::Modified,
+            SortBy::Modified => SortBy::Changes,
+            SortBy::Changes => SortBy::Path,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortBy::Path => "path",
+            SortBy::State => "state",
+            SortBy::Commit => "last commit",
+            SortBy::Modified => "last modified",
+            SortBy::Changes => "changed lines",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// No grouping
+    #[default]
+    None,
+    /// Parent directory of each repo
+    Parent,
+    /// Repo state
+    State,
+}
+
+impl GroupBy {
+    pub fn next(self) -> Self {
+        match self {
+            GroupBy::None => GroupBy::Parent,
+            GroupBy::Parent => GroupBy::State,
+            GroupBy::State => GroupBy::None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GroupBy::None => "none",
+            GroupBy::Parent => "parent",
+            GroupBy::State => "state",
+        }
+    }
+}
+
+// Stable sort so ties keep the a-z order repos are collected in
+pub fn sort_repos(repos: &mut [Repo], sort_by: SortBy) {
+    match sort_by {
+        SortBy::Path => repos.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortBy::State => repos.sort_by_key(Repo::state),
+        SortBy::Commit => repos.sort_by_key(|repo| Reverse(repo.data.last_commit)),
+        SortBy::Modified => repos.sort_by_key(|repo| Reverse(repo.data.modified)),
+        SortBy::Changes => repos.sort_by_key(|repo| Reverse(repo.data.changed_lines())),
+    }
+}
+
+// Group labels with the indices of their repos, keeping the sorted order within each group
+pub fn group_repos(
+    repos: &[Repo],
+    group_by: GroupBy,
+    filters: &Filters,
+) -> Vec<(String, Vec<usize>)> {
+    let mut groups: BTreeMap<(usize, String), Vec<usize>> = BTreeMap::new();
+
+    for (
+        i,
+        Repo {
+            name: repo_path,
+            data: git_data,
+        },
+    ) in repos.iter().enumerate()
+    {
+        if !filters.matches(git_data) {
+            continue;
+        }
+
+        let key = match group_by {
+            GroupBy::None => (0, String::new()),
+            GroupBy::Parent => {
+                let parent = Path::new(repo_path)
+                    .parent()
+                    .map(|parent| parent.display().to_string())
+                    .unwrap_or_default();
+
+                (
+                    0,
+                    if parent.is_empty() {
+                        ".".to_string()
+                    } else {
+                        parent
+                    },
+                )
+            }
+            GroupBy::State => {
+                let state = git_data.state();
+                (state as usize, state.label().to_string())
+            }
+        };
+
+        groups.entry(key).or_default().push(i);
+    }
+
+    groups
+        .into_iter()
+        .map(|((_, label), indices)| (label, indices))
+        .collect()
+}
@@ -0,0 +1,430 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::git_data::run_git;
+
+// Changes of a repo relative to its index, listed by the diff TUI to stage, unstage and discard
+// them hunk by hunk. Unlike GitData::diff (against upstream) these can be applied with git apply.
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    pub repo_path: PathBuf, // Repo they were read from, which actions apply to
+    pub lines: Vec<String>,
+    patch_lines: Vec<Vec<u8>>, // Bytes of each line as git printed them, empty for titles
+    pub hunks: Vec<Hunk>,
+    pub titles: Vec<usize>, // Lines starting each section
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Unstaged,
+    Staged,
+    Untracked,
+}
+
+// Hunk of a diff, or a whole file for untracked and binary files, indexing into Changes::lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub section: Section,
+    pub file: String,
+    pub renamed_from: Option<String>, // Old path of renamed files
+    pub header: Range<usize>, // diff --git up to +++, the line of the path for untracked files
+    pub lines: Range<usize>,  // From the @@ line, empty for whole files
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeAction {
+    Stage,
+    Unstage,
+    Discard,
+}
+
+impl Section {
+    pub fn label(self) -> &'static str {
+        match self {
+            Section::Unstaged => "unstaged",
+            Section::Staged => "staged",
+            Section::Untracked => "untracked",
+        }
+    }
+}
+
+impl Hunk {
+    // Lines the hunk is drawn on, its header for whole files
+    pub fn range(&self) -> Range<usize> {
+        if self.lines.is_empty() {
+            self.header.clone()
+        } else {
+            self.lines.clone()
+        }
+    }
+}
+
+impl ChangeAction {
+    pub fn label(self) -> &'static str {
+        match self {
+            ChangeAction::Stage => "Staged",
+            ChangeAction::Unstage => "Unstaged",
+            ChangeAction::Discard => "Discarded",
+        }
+    }
+}
+
+impl Changes {
+    pub fn load(repo_path: &Path, timeout: Option<Duration>) -> Result<Self, String> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout); // Shared by all commands
+        // Prefixes are fixed, git apply expects a/ and b/ whatever diff.noprefix says, and the
+        // lines of the files as they are rather than converted for display by a textconv driver.
+        // Paths are only quoted for special characters then, not for being outside of ASCII.
+        let diff = [
+            "-c",
+            "core.quotePath=false",
+            "diff",
+            "--no-color",
+            "--no-ext-diff",
+            "--no-textconv",
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+        ];
+        let unstaged = git(repo_path, &diff, None, deadline)?;
+        let staged = git(
+            repo_path,
+            &[&diff[..], &["--cached"]].concat(),
+            None,
+            deadline,
+        )?;
+        let untracked = git(
+            repo_path,
+            &["ls-files", "-z", "--others", "--exclude-standard"],
+            None,
+            deadline,
+        )?;
+
+        let mut changes = Self {
+            repo_path: repo_path.to_path_buf(),
+            ..Self::default()
+        };
+        changes.push_diff(Section::Unstaged, "Unstaged changes", &unstaged);
+        changes.push_diff(Section::Staged, "Staged changes", &staged);
+
+        if !untracked.is_empty() {
+            changes.push_title("Untracked files");
+            for file in untracked
+                .split(|&byte| byte == 0)
+                .filter(|file| !file.is_empty())
+            {
+                let line = changes.lines.len();
+                let file = String::from_utf8_lossy(file).to_string();
+                changes.push_line(file.clone(), Vec::new());
+                changes.hunks.push(Hunk {
+                    section: Section::Untracked,
+                    file,
+                    renamed_from: None,
+                    header: line..line + 1,
+                    lines: line + 1..line + 1,
+                });
+            }
+        }
+
+        if changes.lines.is_empty() {
+            changes.push_line(
+                "Nothing to stage, discard or commit".to_string(),
+                Vec::new(),
+            );
+        }
+
+        Ok(changes)
+    }
+
+    fn push_line(&mut self, line: String, patch_line: Vec<u8>) {
+        self.lines.push(line);
+        self.patch_lines.push(patch_line);
+    }
+
+    // Sections are set apart by an empty line
+    fn push_title(&mut self, title: &str) {
+        if !self.lines.is_empty() {
+            self.push_line(String::new(), Vec::new());
+        }
+        self.titles.push(self.lines.len());
+        self.push_line(title.to_string(), Vec::new());
+    }
+
+    // Splits a diff into hunks under a title, files without any (binary, mode changes) are
+    // kept as a whole
+    fn push_diff(&mut self, section: Section, title: &str, diff: &[u8]) {
+        if diff.is_empty() {
+            return;
+        }
+        self.push_title(title);
+
+        let mut file = None;
+        for patch_line in diff.split_inclusive(|&byte| byte == b'\n') {
+            let i = self.lines.len();
+            // Shown without the line ending, which stays in the patch (ex: \r of CRLF files)
+            let line = String::from_utf8_lossy(patch_line)
+                .trim_end_matches(['\n', '\r'])
+                .to_string();
+
+            if let Some(paths) = line.strip_prefix("diff --git ") {
+                self.hunks.push(Hunk {
+                    section,
+                    file: new_path(paths),
+                    renamed_from: None,
+                    header: i..i + 1,
+                    lines: i + 1..i + 1,
+                });
+                file = Some(self.hunks.len() - 1);
+            } else if line.starts_with("@@")
+                && let Some(file_i) = file
+            {
+                let hunk = &mut self.hunks[file_i];
+                if hunk.lines.is_empty() {
+                    hunk.lines = i..i + 1;
+                } else {
+                    let (file, renamed_from, header) = (
+                        hunk.file.clone(),
+                        hunk.renamed_from.clone(),
+                        hunk.header.clone(),
+                    );
+                    self.hunks.push(Hunk {
+                        section,
+                        file,
+                        renamed_from,
+                        header,
+                        lines: i..i + 1,
+                    });
+                }
+            } else if let Some(hunk) = self.hunks.last_mut() {
+                if hunk.lines.is_empty() {
+                    hunk.header.end = i + 1;
+                    hunk.lines = i + 1..i + 1;
+                    if let Some(old_path) = line.strip_prefix("rename from ") {
+                        hunk.renamed_from = Some(unquote_path(old_path));
+                    }
+                } else {
+                    hunk.lines.end = i + 1;
+                }
+            }
+            self.push_line(line, patch_line.to_vec());
+        }
+    }
+
+    // Checks that the hunk can be acted on before asking to confirm a discard
+    pub fn check(&self, hunk_i: usize, action: ChangeAction) -> Result<&Hunk, String> {
+        let hunk = self
+            .hunks
+            .get(hunk_i)
+            .ok_or_else(|| "No changes left".to_string())?;
+
+        match (action, hunk.section) {
+            (ChangeAction::Stage, Section::Staged) => {
+                Err(format!("{} is already staged", hunk.file))
+            }
+            (ChangeAction::Unstage, Section::Unstaged | Section::Untracked) => {
+                Err(format!("{} has nothing staged", hunk.file))
+            }
+            (ChangeAction::Discard, Section::Staged) => Err(format!(
+                "Staged changes of {} can't be discarded, unstage them first",
+                hunk.file
+            )),
+            _ => Ok(hunk),
+        }
+    }
+
+    // Runs git for the hunk, or the whole file it belongs to, returning what was done
+    pub fn apply(
+        &self,
+        hunk_i: usize,
+        action: ChangeAction,
+        whole_file: bool,
+        timeout: Option<Duration>,
+    ) -> Result<String, String> {
+        let repo_path = self.repo_path.as_path();
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let hunk = self.check(hunk_i, action)?;
+        let file = hunk.file.as_str();
+        let whole_file = whole_file || hunk.lines.is_empty();
+
+        if whole_file {
+            let mut args = match (action, hunk.section) {
+                (ChangeAction::Stage, _) => vec!["add", "--", file],
+                // There's no HEAD to restore from before the first commit
+                (ChangeAction::Unstage, _) if !has_commits(repo_path, deadline) => {
+                    vec!["rm", "--cached", "--force", "-q", "--", file]
+                }
+                (ChangeAction::Unstage, _) => vec!["restore", "--staged", "--", file],
+                (ChangeAction::Discard, Section::Untracked) => {
+                    vec!["clean", "-f", "-q", "--", file]
+                }
+                (ChangeAction::Discard, _) => vec!["restore", "--", file],
+            };
+            // Renames are undone by restoring the old path as well
+            if action == ChangeAction::Unstage
+                && let Some(renamed_from) = &hunk.renamed_from
+            {
+                args.push(renamed_from);
+            }
+            git(repo_path, &args, None, deadline)?;
+
+            return Ok(format!("{} {file}", action.label()));
+        }
+
+        let args: &[&str] = match action {
+            ChangeAction::Stage => &["apply", "--cached", "-"],
+            ChangeAction::Unstage => &["apply", "--cached", "--reverse", "-"],
+            ChangeAction::Discard => &["apply", "--reverse", "-"],
+        };
+        git(repo_path, args, Some(&self.patch(hunk)), deadline)?;
+
+        Ok(format!("{} a hunk of {file}", action.label()))
+    }
+
+    // File header and lines of a single hunk, a patch git apply takes on its own
+    fn patch(&self, hunk: &Hunk) -> Vec<u8> {
+        self.patch_lines[hunk.header.clone()]
+            .iter()
+            .chain(&self.patch_lines[hunk.lines.clone()])
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    // Line to scroll to for a hunk, the file header for the first hunk of a file and the title
+    // for the first of a section
+    pub fn start(&self, hunk_i: usize) -> usize {
+        let hunk = &self.hunks[hunk_i];
+        let previous = hunk_i.checked_sub(1).map(|i| &self.hunks[i]);
+
+        match previous {
+            Some(previous) if previous.header == hunk.header => hunk.lines.start,
+            Some(previous) if previous.section == hunk.section => hunk.header.start,
+            _ => hunk.header.start.saturating_sub(1),
+        }
+    }
+
+    // First hunk of the next or previous file, for jumping between files
+    pub fn file_after(&self, hunk_i: usize, forward: bool) -> Option<usize> {
+        let header = &self.hunks.get(hunk_i)?.header;
+        if forward {
+            (hunk_i + 1..self.hunks.len()).find(|&i| self.hunks[i].header != *header)
+        } else {
+            let previous = (0..hunk_i).rfind(|&i| self.hunks[i].header != *header)?;
+            let previous_header = &self.hunks[previous].header;
+            (0..=previous).find(|&i| self.hunks[i].header == *previous_header)
+        }
+    }
+}
+
+// Commits what is staged with the message, returning the summary git prints, ex: [main 1a2b3c4] Fix
+pub fn commit(
+    repo_path: &Path,
+    message: &str,
+    timeout: Option<Duration>,
+) -> Result<String, String> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let output = git(
+        repo_path,
+        &["commit", "--file", "-"],
+        Some(message.as_bytes()),
+        deadline,
+    )?;
+
+    Ok(String::from_utf8_lossy(&output)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string())
+}
+
+// Whether HEAD points at a commit, it doesn't in new repos until their first commit
+fn has_commits(repo_path: &Path, deadline: Option<Instant>) -> bool {
+    git(
+        repo_path,
+        &["rev-parse", "--verify", "-q", "HEAD"],
+        None,
+        deadline,
+    )
+    .is_ok()
+}
+
+// Runs git in the repo with the input on stdin, failing with what git printed to stderr
+fn git(
+    repo_path: &Path,
+    args: &[&str],
+    input: Option<&[u8]>,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>, String> {
+    let output = run_git(args, repo_path, deadline, None, input).map_err(|e| e.message)?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // git commit explains why there's nothing to commit on stdout, the reason is last either way
+        let reason = if stderr.trim().is_empty() {
+            stdout
+        } else {
+            stderr
+        };
+        Err(reason.trim().lines().last().unwrap_or_default().to_string())
+    }
+}
+
+// New path of a diff --git line (a/old b/new), paths with special characters are quoted and
+// escaped like C strings ("b/new\tname")
+fn new_path(paths: &str) -> String {
+    if let Some(quoted) = paths.strip_suffix('"')
+        && let Some((_, new_path)) = quoted.rsplit_once(" \"b/")
+    {
+        return unquote(new_path);
+    }
+
+    paths
+        .rsplit_once(" b/")
+        .map_or(paths, |(_, new_path)| new_path)
+        .to_string()
+}
+
+// Path of a rename from/to line, quoted like in diff --git lines
+fn unquote_path(path: &str) -> String {
+    path.strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+        .map_or_else(|| path.to_string(), unquote)
+}
+
+fn unquote(quoted: &str) -> String {
+    let mut bytes = Vec::with_capacity(quoted.len());
+    let mut quoted = quoted.bytes();
+    while let Some(byte) = quoted.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+
+        match quoted.next() {
+            Some(b't') => bytes.push(b'\t'),
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b'r') => bytes.push(b'\r'),
+            Some(b'a') => bytes.push(0x07),
+            Some(b'b') => bytes.push(0x08),
+            Some(b'f') => bytes.push(0x0c),
+            Some(b'v') => bytes.push(0x0b),
+            // Octal escapes of bytes outside of ASCII, ex: \303\251 for é
+            Some(digit @ b'0'..=b'3') => {
+                let octal = [Some(digit), quoted.next(), quoted.next()];
+                let byte = octal.into_iter().flatten().fold(0_u8, |byte, digit| {
+                    byte.wrapping_mul(8).wrapping_add(digit.wrapping_sub(b'0'))
+                });
+                bytes.push(byte);
+            }
+            Some(other) => bytes.push(other), // \" and \\
+            None => {}
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).to_string()
+}
@@ -1,14 +1,16 @@
-use color_eyre::{eyre::Result, owo_colors::OwoColorize};
+use color_eyre::eyre::Result;
+use ratatui::style::{Modifier, Style};
 
-use crate::git_data::GitData;
+use crate::{
+    filter::Filters,
+    git_data::Repo,
+    sort::{GroupBy, SortBy, group_repos, sort_repos},
+    summary::Summary,
+    theme::Theme,
+};
 
-pub fn display(
-    base_path: String,
-    disable_ascii_art: bool,
-    git_data: Vec<(String, GitData)>,
-) -> Result<()> {
-    if !disable_ascii_art {
-        let ascii_art = r#"
+pub fn print_ascii_art() {
+    let ascii_art = r#"
  ________ ___  ___  ________  ________  ___  _________   
 |\  _____\\  \|\  \|\_____  \|\_____  \|\  \|\___   ___\ 
 \ \  \__/\ \  \\\  \\|___/  /|\|___/  /\ \  \|___ \  \_| 
@@ -18,9 +20,18 @@ pub fn display(
     \|__|    \|_______|\|_______|\|_______|\|__|    \|__|
 "#;
 
-        println!("{ascii_art}");
-    }
+    println!("{ascii_art}");
+}
 
+pub fn display(
+    base_path: String,
+    mut git_data: Vec<Repo>,
+    sort_by: SortBy,
+    group_by: GroupBy,
+    filters: &Filters,
+    summary: &Summary,
+    theme: &Theme,
+) -> Result<()> {
     if git_data.is_empty() {
         println!("Could not find any git repos from provided path");
         println!(
@@ -30,41 +41,90 @@ pub fn display(
         return Ok(());
     }
 
+    let mut failures = Vec::new();
+    sort_repos(&mut git_data, sort_by);
+
+    let groups = group_repos(&git_data, group_by, filters);
+
     println!("Iterating git repos from {base_path}\n");
-    for (repo_path, git_data) in git_data {
-        if git_data.status.contains("nothing to commit")
-            && !git_data.status.contains("branch is ahead")
-        {
-            if git_data.diff.is_empty() {
-                println!("{repo_path} .. {}", "CLEAN".green().italic());
+    if groups.is_empty() {
+        println!("No repos match the filters ({})", filters.label());
+    }
+
+    for (group_i, (label, indices)) in groups.into_iter().enumerate() {
+        if group_by != GroupBy::None {
+            if group_i > 0 {
+                println!();
+            }
+            let header = Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+            println!("{} ({})", theme.paint(header, &label), indices.len());
+        }
+
+        for i in indices {
+            let Repo {
+                name: repo_path,
+                data: git_data,
+            } = &git_data[i];
+            let state = git_data.state();
+            // Diffstat badge, ex: +120 −34
+            let badge = if git_data.diff_stat.is_empty() {
+                String::new()
             } else {
-                // edge case: no upstream branch, but there are local commits not pushed
+                format!(
+                    " {} {}",
+                    theme.paint(theme.diff_add, &format!("+{}", git_data.insertions())),
+                    theme.paint(theme.diff_del, &format!("−{}", git_data.deletions()))
+                )
+            };
+            println!(
+                "{repo_path} .. {}{badge}",
+                theme.paint(theme.state(state), state.label())
+            );
+            if let Some(grep) = &filters.grep
+                && let Some(hit) = grep.first_hit(git_data)
+            {
+                let text = if hit.text == hit.file {
+                    hit.file.to_string()
+                } else {
+                    format!("{}: {}", hit.file, hit.text)
+                };
                 println!(
-                    "{repo_path} .. {}",
-                    "DIRTY (changes committed, not pushed)".red().bold()
+                    "    {}",
+                    theme.paint(Style::new().add_modifier(Modifier::DIM), &text)
                 );
             }
-        } else if git_data.status.contains("no changes added to commit")
-            || git_data.status.contains("untracked")
-        {
-            println!(
-                "{repo_path} .. {}",
-                "DIRTY (changes not added)".fg_rgb::<255, 184, 108>() // orange
-            );
-        } else if git_data.status.contains("Changes to be committed") {
-            println!(
-                "{repo_path} .. {}",
-                "DIRTY (changes added, not committed)".red()
-            );
-        } else if git_data.status.contains("branch is ahead")
-            || git_data.status.contains("diverged")
-        {
-            println!(
-                "{repo_path} .. {}",
-                "DIRTY (changes committed, not pushed)".red().bold()
-            );
-        } else {
-            println!("{repo_path} .. {}", "UNKNOWN".yellow());
+
+            if let Some(error) = &git_data.error {
+                failures.push((repo_path, error));
+            }
+        }
+    }
+
+    let counts = summary
+        .state_counts
+        .iter()
+        .map(|(&state, count)| {
+            theme.paint(
+                theme.state(state),
+                &format!("{count} {}", state.short_label()),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    println!(
+        "\n{} repos: {counts} | {} | {}",
+        summary.total,
+        summary.totals(),
+        theme.paint(
+            Style::new().add_modifier(Modifier::DIM),
+            &summary.scan_stats.to_string()
+        )
+    );
+
+    if !failures.is_empty() {
+        println!("\n{} repo(s) could not be read:", failures.len());
+        for (repo_path, error) in failures {
+            println!("{repo_path}: {error}");
         }
     }
 
@@ -0,0 +1,95 @@
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use crate::{
+    filter::state_counts,
+    git_data::{Repo, RepoState, SLOWEST_COUNT, ScanStats},
+};
+
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub total: usize,
+    pub state_counts: BTreeMap<RepoState, usize>,
+    pub changed_files: usize,
+    pub changed_lines: usize,
+    pub unpushed_commits: usize,
+    pub scan_stats: ScanStats,
+}
+
+impl Summary {
+    pub fn new(repos: &[Repo], scan_stats: ScanStats) -> Self {
+        Self {
+            total: repos.len(),
+            state_counts: state_counts(repos),
+            changed_files: repos.iter().map(|repo| repo.data.changed_files()).sum(),
+            changed_lines: repos.iter().map(|repo| repo.data.changed_lines()).sum(),
+            unpushed_commits: repos.iter().map(|repo| repo.data.unpushed_commits()).sum(),
+            scan_stats,
+        }
+    }
+
+    pub fn totals(&self) -> String {
+        format!(
+            "{} files, {} lines changed | {} commits unpushed",
+            self.changed_files, self.changed_lines, self.unpushed_commits
+        )
+    }
+}
+
+// Slowest repos and folders of a scan, for --timings
+pub fn timings(repos: &[Repo], scan_stats: &ScanStats) -> String {
+    let mut slowest_repos = repos
+        .iter()
+        .filter_map(|repo| Some((&repo.name, repo.data.git_duration?)))
+        .collect::<Vec<_>>();
+    slowest_repos.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    let mut lines = vec![format!(
+        "Scanned in {:.2}s ({:.2}s searching folders, {:.2}s reading repos)",
+        scan_stats.duration.as_secs_f64(),
+        scan_stats.discovery_duration.as_secs_f64(),
+        scan_stats
+            .duration
+            .saturating_sub(scan_stats.discovery_duration)
+            .as_secs_f64()
+    )];
+    let timing =
+        |duration: Duration, name: &str| format!("  {:>7.3}s  {name}", duration.as_secs_f64());
+
+    lines.push("Slowest repos (statuses taken from the cache aren't timed):".to_string());
+    lines.extend(
+        slowest_repos
+            .iter()
+            .take(SLOWEST_COUNT)
+            .map(|(repo_path, duration)| timing(*duration, repo_path)),
+    );
+
+    lines.push("Slowest folders (folders taken from the cache aren't timed):".to_string());
+    lines.extend(
+        scan_stats
+            .slowest_folders
+            .iter()
+            .map(|(folder_path, duration)| timing(*duration, &folder_path.display().to_string())),
+    );
+
+    lines.join("\n")
+}
+
+// One line for shell prompts and status bars (ex: 12 repos: 1 unpushed, 11 clean | ...)
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = self
+            .state_counts
+            .iter()
+            .map(|(state, count)| format!("{count} {}", state.short_label()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        write!(
+            f,
+            "{} repos: {counts} | {} | {}",
+            self.total,
+            self.totals(),
+            self.scan_stats
+        )
+    }
+}
@@ -0,0 +1,362 @@
+use std::{env, str::FromStr};
+
+use clap::ValueEnum;
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+
+use crate::git_data::RepoState;
+
+// Styles of everything fuzzit colors, shared by the diff TUI and the status list
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub clean: Style,
+    pub unstaged: Style,
+    pub staged: Style,
+    pub unpushed: Style,
+    pub conflicted: Style,
+    pub unknown: Style,
+    pub timeout: Style,
+    pub error: Style,
+    pub diff_add: Style,
+    pub diff_del: Style,
+    pub hunk_header: Style,
+    pub diff_header: Style, // diff --git and index lines
+    pub search_match: Style,
+    pub search_current: Style, // The match n/N last jumped to
+    pub selected_hunk: Style,  // Hunk the stage keys act on while reviewing changes
+    pub color: bool,           // Off for NO_COLOR and --color never, only modifiers are kept then
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+    Colorblind, // Okabe-Ito palette, no red/green pairs
+}
+
+// [theme] in the config, a built-in theme with styles of some roles replaced,
+// ex: unstaged = "#d08770 bold" or error = "white on red"
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub base: ThemeName,
+    pub clean: Option<String>,
+    pub unstaged: Option<String>,
+    pub staged: Option<String>,
+    pub unpushed: Option<String>,
+    pub conflicted: Option<String>,
+    pub unknown: Option<String>,
+    pub timeout: Option<String>,
+    pub error: Option<String>,
+    pub diff_add: Option<String>,
+    pub diff_del: Option<String>,
+    pub hunk_header: Option<String>,
+    pub diff_header: Option<String>,
+    pub search_match: Option<String>,
+    pub search_current: Option<String>,
+    pub selected_hunk: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Colors unless NO_COLOR is set or output isn't a terminal
+    #[default]
+    Auto,
+    /// Colors even when piped, NO_COLOR is ignored
+    Always,
+    /// No colors, the TUI keeps bold and italic text
+    Never,
+}
+
+impl ColorChoice {
+    // Whether to color output going to a terminal (or not), see https://no-color.org
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                is_terminal && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::builtin(ThemeName::Dark)
+    }
+}
+
+impl Theme {
+    pub fn builtin(name: ThemeName) -> Self {
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+        let italic = Style::new().add_modifier(Modifier::ITALIC);
+        let warning = bold.add_modifier(Modifier::UNDERLINED);
+        let reversed = Style::new().add_modifier(Modifier::REVERSED);
+
+        match name {
+            ThemeName::Dark => Self {
+                clean: italic.fg(Color::Green),
+                unstaged: Style::new().fg(Color::Rgb(255, 184, 108)), // orange
+                staged: Style::new().fg(Color::Red),
+                unpushed: bold.fg(Color::LightRed),
+                conflicted: bold.fg(Color::Magenta),
+                unknown: Style::new().fg(Color::Yellow),
+                timeout: warning.fg(Color::Yellow),
+                error: warning.fg(Color::Red),
+                diff_add: Style::new().fg(Color::Green),
+                diff_del: Style::new().fg(Color::Red),
+                hunk_header: italic,
+                diff_header: bold,
+                search_match: reversed,
+                search_current: reversed.add_modifier(Modifier::BOLD).fg(Color::Yellow),
+                selected_hunk: Style::new().bg(Color::Indexed(236)),
+                color: true,
+            },
+            // Darker shades that stay readable on a white background
+            ThemeName::Light => Self {
+                clean: italic.fg(Color::Rgb(0, 120, 0)),
+                unstaged: Style::new().fg(Color::Rgb(175, 95, 0)),
+                staged: Style::new().fg(Color::Rgb(200, 0, 0)),
+                unpushed: bold.fg(Color::Rgb(175, 0, 0)),
+                conflicted: bold.fg(Color::Rgb(135, 0, 135)),
+                unknown: Style::new().fg(Color::Rgb(135, 95, 0)),
+                timeout: warning.fg(Color::Rgb(135, 95, 0)),
+                error: warning.fg(Color::Rgb(175, 0, 0)),
+                diff_add: Style::new().fg(Color::Rgb(0, 120, 0)),
+                diff_del: Style::new().fg(Color::Rgb(175, 0, 0)),
+                hunk_header: italic.fg(Color::Rgb(0, 95, 135)),
+                diff_header: bold,
+                search_match: reversed,
+                search_current: reversed
+                    .add_modifier(Modifier::BOLD)
+                    .fg(Color::Rgb(0, 95, 135)),
+                selected_hunk: Style::new().bg(Color::Indexed(254)),
+                color: true,
+            },
+            ThemeName::HighContrast => Self {
+                clean: bold.fg(Color::LightGreen),
+                unstaged: bold.fg(Color::LightYellow),
+                staged: bold.fg(Color::LightRed),
+                unpushed: bold.fg(Color::White).bg(Color::Red),
+                conflicted: bold.fg(Color::Black).bg(Color::LightMagenta),
+                unknown: Style::new().fg(Color::LightYellow),
+                timeout: bold.fg(Color::Black).bg(Color::Yellow),
+                error: warning.fg(Color::White).bg(Color::Red),
+                diff_add: bold.fg(Color::LightGreen),
+                diff_del: bold.fg(Color::LightRed),
+                hunk_header: bold.fg(Color::LightCyan),
+                diff_header: warning.fg(Color::White),
+                search_match: reversed.add_modifier(Modifier::BOLD),
+                search_current: warning.fg(Color::Black).bg(Color::LightCyan),
+                selected_hunk: Style::new().bg(Color::DarkGray),
+                color: true,
+            },
+            ThemeName::Colorblind => Self {
+                clean: italic.fg(Color::Rgb(86, 180, 233)), // sky blue
+                unstaged: Style::new().fg(Color::Rgb(230, 159, 0)), // orange
+                staged: Style::new().fg(Color::Rgb(213, 94, 0)), // vermillion
+                unpushed: bold.fg(Color::Rgb(213, 94, 0)),  // vermillion
+                conflicted: bold.fg(Color::Rgb(204, 121, 167)), // reddish purple
+                unknown: Style::new().fg(Color::Rgb(240, 228, 66)), // yellow
+                timeout: warning.fg(Color::Rgb(240, 228, 66)), // yellow
+                error: warning.fg(Color::Rgb(213, 94, 0)),  // vermillion
+                diff_add: Style::new().fg(Color::Rgb(86, 180, 233)), // sky blue
+                diff_del: Style::new().fg(Color::Rgb(230, 159, 0)), // orange
+                hunk_header: italic,
+                diff_header: bold,
+                search_match: reversed,
+                search_current: reversed
+                    .add_modifier(Modifier::BOLD)
+                    .fg(Color::Rgb(240, 228, 66)), // yellow
+                selected_hunk: Style::new().bg(Color::Indexed(236)),
+                color: true,
+            },
+        }
+    }
+
+    // Built-in base theme with the roles set in the config replaced
+    pub fn new(config: &ThemeConfig) -> Result<Self, String> {
+        let mut theme = Self::builtin(config.base);
+
+        for (name, style, role) in [
+            ("clean", &config.clean, &mut theme.clean),
+            ("unstaged", &config.unstaged, &mut theme.unstaged),
+            ("staged", &config.staged, &mut theme.staged),
+            ("unpushed", &config.unpushed, &mut theme.unpushed),
+            ("conflicted", &config.conflicted, &mut theme.conflicted),
+            ("unknown", &config.unknown, &mut theme.unknown),
+            ("timeout", &config.timeout, &mut theme.timeout),
+            ("error", &config.error, &mut theme.error),
+            ("diff_add", &config.diff_add, &mut theme.diff_add),
+            ("diff_del", &config.diff_del, &mut theme.diff_del),
+            ("hunk_header", &config.hunk_header, &mut theme.hunk_header),
+            ("diff_header", &config.diff_header, &mut theme.diff_header),
+            (
+                "search_match",
+                &config.search_match,
+                &mut theme.search_match,
+            ),
+            (
+                "search_current",
+                &config.search_current,
+                &mut theme.search_current,
+            ),
+            (
+                "selected_hunk",
+                &config.selected_hunk,
+                &mut theme.selected_hunk,
+            ),
+        ] {
+            if let Some(style) = style {
+                *role = parse_style(style).map_err(|e| format!("{name}: {e}"))?;
+            }
+        }
+
+        Ok(theme)
+    }
+
+    // Drops colors but keeps bold, italic... so states can still be told apart
+    pub fn with_color(mut self, color: bool) -> Self {
+        if !color {
+            for style in [
+                &mut self.clean,
+                &mut self.unstaged,
+                &mut self.staged,
+                &mut self.unpushed,
+                &mut self.conflicted,
+                &mut self.unknown,
+                &mut self.timeout,
+                &mut self.error,
+                &mut self.diff_add,
+                &mut self.diff_del,
+                &mut self.hunk_header,
+                &mut self.diff_header,
+                &mut self.search_match,
+                &mut self.search_current,
+                &mut self.selected_hunk,
+            ] {
+                style.fg = None;
+                style.bg = None;
+            }
+        }
+        self.color = color;
+
+        self
+    }
+
+    pub fn state(&self, state: RepoState) -> Style {
+        match state {
+            RepoState::Error => self.error,
+            RepoState::Timeout => self.timeout,
+            RepoState::Conflicted => self.conflicted,
+            RepoState::Unpushed => self.unpushed,
+            RepoState::Staged => self.staged,
+            RepoState::Unstaged => self.unstaged,
+            RepoState::Unknown => self.unknown,
+            RepoState::Clean => self.clean,
+        }
+    }
+
+    // Text wrapped in escape codes for printing, plain text without colors
+    pub fn paint(&self, style: Style, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{text}\x1b[0m", sgr(style))
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+// Parses styles like "green", "#ffb86c bold italic" or "white on red"
+pub fn parse_style(style: &str) -> Result<Style, String> {
+    let mut parsed = Style::new();
+    let mut words = style.split_whitespace();
+
+    while let Some(word) = words.next() {
+        let modifier = match word.to_lowercase().as_str() {
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underline" | "underlined" => Modifier::UNDERLINED,
+            "reversed" => Modifier::REVERSED,
+            "strikethrough" => Modifier::CROSSED_OUT,
+            "on" => {
+                let color = words
+                    .next()
+                    .ok_or_else(|| format!("missing background color after on in {style}"))?;
+                parsed = parsed.bg(parse_color(color)?);
+                continue;
+            }
+            _ => {
+                parsed = parsed.fg(parse_color(word)?);
+                continue;
+            }
+        };
+        parsed = parsed.add_modifier(modifier);
+    }
+
+    Ok(parsed)
+}
+
+fn parse_color(color: &str) -> Result<Color, String> {
+    Color::from_str(color).map_err(|_| {
+        format!(
+            "unknown color or modifier {color}, use names like red or light-blue, #rrggbb, 0-255, bold, dim, italic, underline, reversed or strikethrough"
+        )
+    })
+}
+
+// SGR parameters of a style, ex: "0;1;32" for bold green
+pub fn sgr(style: Style) -> String {
+    let mut codes = vec!["0".to_string()];
+
+    for (modifier, code) in [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::SLOW_BLINK, "5"),
+        (Modifier::RAPID_BLINK, "6"),
+        (Modifier::REVERSED, "7"),
+        (Modifier::CROSSED_OUT, "9"),
+    ] {
+        if style.add_modifier.contains(modifier) {
+            codes.push(code.to_string());
+        }
+    }
+    codes.extend(style.fg.and_then(|color| ansi_color(color, false)));
+    codes.extend(style.bg.and_then(|color| ansi_color(color, true)));
+
+    codes.join(";")
+}
+
+fn ansi_color(color: Color, background: bool) -> Option<String> {
+    let offset = if background { 10 } else { 0 };
+    let named = |code: u8| Some((code + offset).to_string());
+
+    match color {
+        Color::Reset => None,
+        Color::Black => named(30),
+        Color::Red => named(31),
+        Color::Green => named(32),
+        Color::Yellow => named(33),
+        Color::Blue => named(34),
+        Color::Magenta => named(35),
+        Color::Cyan => named(36),
+        Color::Gray => named(37),
+        Color::DarkGray => named(90),
+        Color::LightRed => named(91),
+        Color::LightGreen => named(92),
+        Color::LightYellow => named(93),
+        Color::LightBlue => named(94),
+        Color::LightMagenta => named(95),
+        Color::LightCyan => named(96),
+        Color::White => named(97),
+        Color::Indexed(i) => Some(format!("{};5;{i}", 38 + offset)),
+        Color::Rgb(r, g, b) => Some(format!("{};2;{r};{g};{b}", 38 + offset)),
+    }
+}
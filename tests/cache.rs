@@ -0,0 +1,167 @@
+#![allow(clippy::unwrap_used)]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{Fixture, commit, fuzzit_command, git, stdout, write};
+use fuzzit::cache::repo_fingerprint;
+use tempfile::TempDir;
+
+// Runs the fuzzit binary against its own cache and config
+fn fuzzit(cache_home: &Path, args: &[&str]) -> String {
+    stdout(fuzzit_command(cache_home).args(args))
+}
+
+fn summary(cache_home: &Path, base_path: &Path) -> String {
+    fuzzit(
+        cache_home,
+        &["--summary-only", "--no-daemon", base_path.to_str().unwrap()],
+    )
+}
+
+#[test]
+fn second_scan_hits_the_cache() {
+    let fixture = Fixture::new();
+    let cache_home = TempDir::new().unwrap();
+    fixture.init("a");
+    fixture.init("group/b");
+
+    let first = summary(cache_home.path(), &fixture.path());
+    assert!(first.contains("2 repos: 2 clean"), "{first}");
+    assert!(first.contains("cache miss, 0/2 statuses cached"), "{first}");
+
+    let second = summary(cache_home.path(), &fixture.path());
+    assert!(second.contains("2 repos: 2 clean"), "{second}");
+    assert!(
+        second.contains("cache hit, 2/2 statuses cached"),
+        "{second}"
+    );
+}
+
+#[test]
+fn changed_repos_are_read_again() {
+    let fixture = Fixture::new();
+    let cache_home = TempDir::new().unwrap();
+    let repo = fixture.init("a");
+    fixture.init("b");
+    summary(cache_home.path(), &fixture.path());
+
+    write(&repo.join("README.md"), "changed\n");
+    let after_edit = summary(cache_home.path(), &fixture.path());
+    assert!(after_edit.contains("1 clean"), "{after_edit}");
+    assert!(after_edit.contains("1 unstaged"), "{after_edit}");
+    assert!(after_edit.contains("1/2 statuses cached"), "{after_edit}");
+
+    commit(&repo, "README.md", "changed\n");
+    let after_commit = summary(cache_home.path(), &fixture.path());
+    assert!(after_commit.contains("2 clean"), "{after_commit}");
+}
+
+#[test]
+fn fingerprints_follow_tracked_files_but_not_ignored_ones() {
+    let fixture = Fixture::new();
+    for version in ["2", "3", "4"] {
+        let repo = fixture.init(&format!("v{version}"));
+        write(&repo.join(".gitignore"), "node_modules/\n");
+        commit(&repo, "src/deep/lib.rs", "fn main() {}\n");
+        git(&repo, &["update-index", "--index-version", version]);
+        let fingerprint = || repo_fingerprint(&repo).unwrap();
+
+        write(&repo.join("node_modules/pkg/index.js"), "ignored\n");
+        let clean = fingerprint();
+        write(&repo.join("node_modules/pkg/lib/util.js"), "ignored\n");
+        assert_eq!(fingerprint(), clean, "index v{version}");
+
+        write(&repo.join("src/deep/lib.rs"), "fn main() { changed(); }\n");
+        let edited = fingerprint();
+        assert_ne!(edited, clean, "index v{version}");
+
+        write(&repo.join("src/deep/new.rs"), "untracked\n");
+        assert_ne!(fingerprint(), edited, "index v{version}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn statuses_are_shared_between_spellings_of_the_base_path() {
+    let fixture = Fixture::new();
+    let cache_home = TempDir::new().unwrap();
+    fixture.init("projects/a");
+    let linked = fixture.join("linked");
+    std::os::unix::fs::symlink(fixture.join("projects"), &linked).unwrap();
+
+    summary(cache_home.path(), &linked);
+    let output = summary(cache_home.path(), &fixture.join("projects"));
+    assert!(output.contains("1/1 statuses cached"), "{output}");
+}
+
+#[test]
+fn new_repos_are_found_on_a_cache_hit() {
+    let fixture = Fixture::new();
+    let cache_home = TempDir::new().unwrap();
+    fixture.init("a");
+    summary(cache_home.path(), &fixture.path());
+
+    fixture.init("group/b");
+    let output = summary(cache_home.path(), &fixture.path());
+    assert!(output.contains("2 repos"), "{output}");
+}
+
+#[test]
+fn corrupt_cache_is_ignored() {
+    let fixture = Fixture::new();
+    let cache_home = TempDir::new().unwrap();
+    fixture.init("a");
+    summary(cache_home.path(), &fixture.path());
+
+    let cache_dir = cache_home.path().join("cache/fuzzit");
+    for entry in fs::read_dir(&cache_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "cache")
+        {
+            fs::write(path, "not a cache").unwrap();
+        }
+    }
+
+    let output = summary(cache_home.path(), &fixture.path());
+    assert!(output.contains("1 repos: 1 clean"), "{output}");
+    assert!(output.contains("cache miss"), "{output}");
+    // The rewritten cache is read back on the next scan
+    let output = summary(cache_home.path(), &fixture.path());
+    assert!(output.contains("cache hit"), "{output}");
+}
+
+#[test]
+fn cache_commands() {
+    let fixture = Fixture::new();
+    let cache_home = TempDir::new().unwrap();
+    fixture.init("a");
+    summary(cache_home.path(), &fixture.path());
+
+    let list = fuzzit(cache_home.path(), &["cache", "list"]);
+    assert!(
+        list.contains(&format!("{} .. 1 repos", fixture.path().display())),
+        "{list}"
+    );
+
+    // Cache commands don't read the config, so a broken one can't get in the way of clearing
+    let broken_config = cache_home.path().join("broken.toml");
+    write(&broken_config, "[layout\n");
+    fuzzit(
+        cache_home.path(),
+        &[
+            "--config",
+            broken_config.to_str().unwrap(),
+            "cache",
+            "clear",
+        ],
+    );
+    let list = fuzzit(cache_home.path(), &["cache", "list"]);
+    assert!(
+        !list.contains(&fixture.path().display().to_string()),
+        "{list}"
+    );
+}
@@ -0,0 +1,250 @@
+#![allow(clippy::panic, clippy::unwrap_used)]
+
+mod common;
+
+use std::{fs, time::Instant};
+
+use common::{Fixture, commit, fuzzit_command, git, git_output, stdout, write};
+use fuzzit::{RepoState, git_data::load_last_commits};
+use tempfile::TempDir;
+
+#[test]
+fn clean_clone() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    fixture.clone(&remote, "clean");
+
+    assert_eq!(fixture.state("clean"), RepoState::Clean);
+}
+
+#[test]
+fn unstaged_changes() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    let repo = fixture.clone(&remote, "unstaged");
+    write(&repo.join("README.md"), "changed\n");
+
+    assert_eq!(fixture.state("unstaged"), RepoState::Unstaged);
+}
+
+#[test]
+fn staged_changes() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    let repo = fixture.clone(&remote, "staged");
+    write(&repo.join("README.md"), "changed\n");
+    git(&repo, &["add", "README.md"]);
+
+    assert_eq!(fixture.state("staged"), RepoState::Staged);
+}
+
+#[test]
+fn untracked_files() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    let repo = fixture.clone(&remote, "untracked");
+    write(&repo.join("new.txt"), "new\n");
+
+    assert_eq!(fixture.state("untracked"), RepoState::Unstaged);
+}
+
+#[test]
+fn ahead_of_upstream() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    let repo = fixture.clone(&remote, "ahead");
+    commit(&repo, "local.txt", "local\n");
+
+    let repos = fixture.scan(&fixture.path());
+    assert_eq!(repos[0].state(), RepoState::Unpushed);
+    assert_eq!(repos[0].data.unpushed_commits(), 1);
+}
+
+#[test]
+fn behind_upstream() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    let repo = fixture.clone(&remote, "behind");
+    fixture.push_from_elsewhere(&remote, "remote.txt");
+    git(&repo, &["fetch", "-q"]);
+
+    let repos = fixture.scan(&fixture.path());
+    assert_eq!(repos[0].state(), RepoState::Clean);
+    assert_eq!(repos[0].data.unpushed_commits(), 0);
+}
+
+#[test]
+fn diverged_from_upstream() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    let repo = fixture.clone(&remote, "diverged");
+    fixture.push_from_elsewhere(&remote, "remote.txt");
+    commit(&repo, "local.txt", "local\n");
+    commit(&repo, "local2.txt", "local\n");
+    git(&repo, &["fetch", "-q"]);
+
+    let repos = fixture.scan(&fixture.path());
+    assert_eq!(repos[0].state(), RepoState::Unpushed);
+    assert_eq!(repos[0].data.unpushed_commits(), 2);
+}
+
+#[test]
+fn merge_conflict() {
+    let fixture = Fixture::new();
+    let repo = fixture.init("conflicted");
+    git(&repo, &["checkout", "-q", "-b", "other"]);
+    commit(&repo, "README.md", "other\n");
+    git(&repo, &["checkout", "-q", "main"]);
+    commit(&repo, "README.md", "main\n");
+    let merge = git_output(&repo, &["merge", "-q", "other"]);
+    assert!(!merge.status.success());
+
+    assert_eq!(fixture.state("conflicted"), RepoState::Conflicted);
+}
+
+#[test]
+fn detached_head() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    let repo = fixture.clone(&remote, "detached");
+    git(&repo, &["checkout", "-q", "--detach"]);
+
+    assert_eq!(fixture.state("detached"), RepoState::Clean);
+}
+
+#[test]
+fn branch_without_upstream() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    let repo = fixture.clone(&remote, "feature");
+    git(&repo, &["checkout", "-q", "-b", "feature"]);
+    commit(&repo, "feature.txt", "feature\n");
+
+    assert_eq!(fixture.state("feature"), RepoState::Unpushed);
+}
+
+#[test]
+fn local_repo_without_remote() {
+    let fixture = Fixture::new();
+    fixture.init("local");
+
+    assert_eq!(fixture.state("local"), RepoState::Clean);
+}
+
+#[test]
+fn repo_without_commits() {
+    let fixture = Fixture::new();
+    let repo = fixture.join("empty");
+    fs::create_dir_all(&repo).unwrap();
+    git(&repo, &["init", "-q", "-b", "main"]);
+
+    assert_eq!(fixture.state("empty"), RepoState::Clean);
+}
+
+#[test]
+fn broken_repo_is_an_error() {
+    let fixture = Fixture::new();
+    write(&fixture.join("broken/.git"), "gitdir: /nowhere\n");
+
+    let repos = fixture.scan(&fixture.path());
+    assert_eq!(repos[0].state(), RepoState::Error);
+    assert!(repos[0].data.error.as_ref().unwrap().code.is_some());
+}
+
+#[test]
+fn commit_dates_are_only_loaded_to_sort_by_them() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    fixture.clone(&remote, "clone");
+    let empty = fixture.join("empty");
+    fs::create_dir_all(&empty).unwrap();
+    git(&empty, &["init", "-q"]);
+
+    let mut repos = fixture.scan(&fixture.path());
+    assert!(repos.iter().all(|repo| repo.data.last_commit.is_none()));
+
+    load_last_commits(&mut repos, None);
+    let last_commit = |name: &str| {
+        let repo = repos.iter().find(|repo| repo.name == name).unwrap();
+        repo.data.last_commit
+    };
+    assert!(last_commit("clone").is_some());
+    assert_eq!(last_commit("empty"), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn repos_git_hangs_in_are_marked_timeout() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    fixture.clone(&remote, "clean");
+    let hung = fixture.clone(&remote, "hung");
+
+    // git status waits for the fsmonitor hook, like it would for a hung network mount
+    let hook = hung.join(".git/stall");
+    write(&hook, "#!/bin/sh\nexec sleep 30\n");
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    git(&hung, &["config", "core.fsmonitor", hook.to_str().unwrap()]);
+
+    let home = TempDir::new().unwrap();
+    let start = Instant::now();
+    let output = stdout(
+        fuzzit_command(home.path())
+            .args([
+                "--no-daemon",
+                "--status",
+                "--disable-ascii",
+                "--repo-timeout",
+                "1",
+            ])
+            .arg(fixture.path()),
+    );
+    assert!(start.elapsed().as_secs() < 20, "the scan waited for git");
+    assert!(output.contains("hung .. TIMEOUT"), "{output}");
+    assert!(output.contains("clean .. CLEAN"), "{output}");
+}
+
+#[test]
+fn timings_list_the_slowest_repos_and_folders() {
+    let fixture = Fixture::new();
+    let remote = fixture.remote("origin");
+    fixture.clone(&remote, "clean");
+    fixture.init("local");
+
+    let home = TempDir::new().unwrap();
+    let output = fuzzit_command(home.path())
+        .args(["--no-daemon", "--status", "--timings"])
+        .arg(fixture.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines = stderr.lines().collect::<Vec<_>>();
+    let position = |heading: &str| {
+        lines
+            .iter()
+            .position(|line| line.starts_with(heading))
+            .unwrap_or_else(|| panic!("{heading} is missing in {stderr}"))
+    };
+    let repos = position("Slowest repos");
+    let folders = position("Slowest folders");
+    assert!(position("Scanned in ") < repos, "{stderr}");
+
+    // Each repo ran git, so each one is timed
+    let mut timed_repos = lines[repos + 1..folders]
+        .iter()
+        .map(|line| line.split_once("s  ").unwrap().1)
+        .collect::<Vec<_>>();
+    timed_repos.sort_unstable();
+    assert_eq!(timed_repos, ["clean", "local"], "{stderr}");
+    assert!(
+        lines[folders + 1..]
+            .iter()
+            .take_while(|line| !line.is_empty())
+            .any(|line| line.ends_with(&fixture.path().display().to_string())),
+        "{stderr}"
+    );
+}
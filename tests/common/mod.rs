@@ -0,0 +1,255 @@
+// Temporary trees of real git repos, shared by the integration tests
+#![allow(dead_code, clippy::panic, clippy::unwrap_used)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use fuzzit::{
+    Repo, RepoState, Scan, ScanStats,
+    config::Config,
+    diff::App,
+    filter::Filters,
+    render::Keys,
+    sort::{GroupBy, SortBy},
+};
+use ratatui::{Terminal, backend::TestBackend};
+use tempfile::TempDir;
+
+#[derive(Debug)]
+pub struct Fixture {
+    dir: TempDir,
+}
+
+// Runs git isolated from the user's config
+pub fn git_output(repo_path: &Path, args: &[&str]) -> Output {
+    Command::new("git")
+        .args(args)
+        .current_dir(repo_path)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_AUTHOR_NAME", "fuzzit")
+        .env("GIT_AUTHOR_EMAIL", "fuzzit@example.com")
+        .env("GIT_COMMITTER_NAME", "fuzzit")
+        .env("GIT_COMMITTER_EMAIL", "fuzzit@example.com")
+        .env("LANG", "C")
+        .output()
+        .unwrap()
+}
+
+// Like git_output, but panics with git's output when it fails
+pub fn git(repo_path: &Path, args: &[&str]) -> String {
+    let output = git_output(repo_path, args);
+
+    if !output.status.success() {
+        panic!(
+            "git {} failed in {}: {}",
+            args.join(" "),
+            repo_path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+pub fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+// The fuzzit binary with its cache and config kept in home, and git isolated from the user's config
+pub fn fuzzit_command(home: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_fuzzit"));
+    command
+        .env("XDG_CACHE_HOME", home.join("cache"))
+        .env("FUZZIT_CONFIG", home.join("config.toml"))
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("LANG", "C")
+        .env_remove("NO_COLOR");
+    command
+}
+
+// Stdout of the command, panics with its stderr when it fails
+pub fn stdout(command: &mut Command) -> String {
+    let output = command.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{command:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+// TUI over the repos sorted by path, without groups, filters or config
+pub fn app(repos: Vec<Repo>) -> App {
+    app_with(repos, GroupBy::None, Filters::default(), Config::default())
+}
+
+pub fn app_with(repos: Vec<Repo>, group_by: GroupBy, filters: Filters, config: Config) -> App {
+    App::new(
+        "~/dev".to_string(),
+        repos,
+        SortBy::Path,
+        group_by,
+        filters,
+        ScanStats::default(),
+        config,
+    )
+}
+
+// Presses keys written like --render-keys, ex: "jj<Enter>"
+pub fn press(app: &mut App, keys: &str) {
+    let keys: Keys = keys.parse().unwrap();
+    for key in keys.0 {
+        app.handle_key(key);
+    }
+}
+
+// Draws one frame and returns its text, one line per terminal row
+pub fn draw(app: &mut App, width: u16, height: u16) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal.draw(|frame| app.render(frame)).unwrap();
+
+    let buffer = terminal.backend().buffer();
+    let mut text = String::new();
+    for y in 0..buffer.area.height {
+        let line: String = (0..buffer.area.width)
+            .map(|x| buffer[(x, y)].symbol())
+            .collect();
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+pub fn commit(repo_path: &Path, file: &str, content: &str) {
+    write(&repo_path.join(file), content);
+    git(repo_path, &["add", file]);
+    git(
+        repo_path,
+        &["commit", "-q", "-m", &format!("Update {file}")],
+    );
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Self {
+            dir: TempDir::new().unwrap(),
+        }
+    }
+
+    // Base path that gets scanned, remotes live next to it so they aren't found as repos
+    pub fn path(&self) -> PathBuf {
+        self.dir.path().join("repos")
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path().join(name)
+    }
+
+    fn remotes(&self) -> PathBuf {
+        self.dir.path().join("remotes")
+    }
+
+    // Repos with commits but without a remote
+    pub fn init(&self, name: &str) -> PathBuf {
+        let repo_path = self.join(name);
+        fs::create_dir_all(&repo_path).unwrap();
+        git(&repo_path, &["init", "-q", "-b", "main"]);
+        commit(&repo_path, "README.md", "fixture\n");
+
+        repo_path
+    }
+
+    pub fn remote(&self, name: &str) -> PathBuf {
+        let remote_path = self.remotes().join(format!("{name}.git"));
+        fs::create_dir_all(&remote_path).unwrap();
+        git(&remote_path, &["init", "-q", "--bare", "-b", "main"]);
+
+        let seed_path = self.remotes().join(format!("{name}-seed"));
+        fs::create_dir_all(&seed_path).unwrap();
+        git(&seed_path, &["init", "-q", "-b", "main"]);
+        commit(&seed_path, "README.md", "fixture\n");
+        git(
+            &seed_path,
+            &["push", "-q", remote_path.to_str().unwrap(), "main"],
+        );
+
+        remote_path
+    }
+
+    // Clones have main tracking origin/main, so they start out clean
+    pub fn clone(&self, remote_path: &Path, name: &str) -> PathBuf {
+        let repo_path = self.join(name);
+        fs::create_dir_all(self.path()).unwrap();
+        git(
+            &self.path(),
+            &[
+                "clone",
+                "-q",
+                remote_path.to_str().unwrap(),
+                repo_path.to_str().unwrap(),
+            ],
+        );
+
+        repo_path
+    }
+
+    // Clone of a new remote with the files committed and pushed, so changes made to them after
+    // are what the diff against upstream shows
+    pub fn pushed(&self, name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let remote_path = self.remote(name);
+        let repo_path = self.clone(&remote_path, name);
+        for (file, content) in files {
+            commit(&repo_path, file, content);
+        }
+        git(&repo_path, &["push", "-q"]);
+
+        repo_path
+    }
+
+    // Pushes a commit to the remote from another clone, so repo_path is behind after fetching
+    pub fn push_from_elsewhere(&self, remote_path: &Path, file: &str) {
+        let other_path = self.remotes().join(format!(
+            "{}-other",
+            remote_path.file_stem().unwrap().to_str().unwrap()
+        ));
+        if !other_path.exists() {
+            git(
+                &self.remotes(),
+                &[
+                    "clone",
+                    "-q",
+                    remote_path.to_str().unwrap(),
+                    other_path.to_str().unwrap(),
+                ],
+            );
+        }
+
+        git(&other_path, &["pull", "-q"]);
+        commit(&other_path, file, "from elsewhere\n");
+        git(&other_path, &["push", "-q"]);
+    }
+
+    pub fn scan(&self, base_path: &Path) -> Vec<Repo> {
+        Scan::new(base_path).cache(false).run().unwrap().repos
+    }
+
+    // TUI over the repos of the fixture
+    pub fn app(&self) -> App {
+        app(self.scan(&self.path()))
+    }
+
+    pub fn state(&self, name: &str) -> RepoState {
+        let repos = self.scan(&self.path());
+        let repo = repos.iter().find(|repo| repo.name == name);
+
+        repo.unwrap_or_else(|| panic!("{name} was not found in {repos:#?}"))
+            .state()
+    }
+}
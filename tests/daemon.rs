@@ -0,0 +1,181 @@
+#![cfg(unix)]
+#![allow(clippy::panic, clippy::unwrap_used)]
+
+mod common;
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    process::{Child, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{Fixture, fuzzit_command, stdout, write};
+use serde_json::Value;
+use tempfile::TempDir;
+
+// A daemon with its own socket, cache and config, killed if a test fails before stopping it
+struct Daemon {
+    home: TempDir,
+    child: Child,
+}
+
+impl Daemon {
+    // Polls once an hour, so only the checks before each answer can pick up changes
+    fn start(base_path: &Path) -> Self {
+        let home = TempDir::new().unwrap();
+        fs::create_dir_all(home.path().join("run")).unwrap();
+        let child = fuzzit_command(home.path())
+            .env("XDG_RUNTIME_DIR", home.path().join("run"))
+            .args(["daemon", "--interval", "3600"])
+            .arg(base_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let daemon = Self { home, child };
+
+        let start = Instant::now();
+        while UnixStream::connect(daemon.socket_path()).is_err() {
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "daemon didn't start"
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+        daemon
+    }
+
+    // Where the daemon puts its socket without XDG_RUNTIME_DIR support (macOS)
+    fn socket_path(&self) -> PathBuf {
+        if cfg!(target_os = "macos") {
+            self.home.path().join("cache/fuzzit/daemon.sock")
+        } else {
+            self.home.path().join("run/fuzzit.sock")
+        }
+    }
+
+    fn request(&self, request: &str) -> Value {
+        let mut stream = UnixStream::connect(self.socket_path()).unwrap();
+        stream.write_all(format!("{request}\n").as_bytes()).unwrap();
+
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    fn repos(&self, base_path: &Path) -> Value {
+        let request = serde_json::json!({
+            "version": 3,
+            "command": "repos",
+            "base_path": base_path,
+        });
+        self.request(&request.to_string())
+    }
+
+    // fuzzit --summary-only, asking the daemon like it does by default
+    fn summary(&self, base_path: &Path) -> String {
+        stdout(
+            fuzzit_command(self.home.path())
+                .env("XDG_RUNTIME_DIR", self.home.path().join("run"))
+                .arg("--summary-only")
+                .arg(base_path),
+        )
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+fn state<'response>(response: &'response Value, name: &str) -> &'response str {
+    response["repos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|repo| repo["name"] == name)
+        .unwrap_or_else(|| panic!("{name} was not found in {response}"))["state"]
+        .as_str()
+        .unwrap()
+}
+
+#[test]
+fn daemon_answers_pings_and_stops() {
+    let fixture = Fixture::new();
+    fixture.init("repo");
+    let mut daemon = Daemon::start(&fixture.path());
+
+    let response = daemon.request(r#"{"version": 3, "command": "ping"}"#);
+    assert_eq!(response["ok"], true, "{response}");
+    assert_eq!(response["pid"], daemon.child.id(), "{response}");
+
+    let response = daemon.request(r#"{"version": 3, "command": "stop"}"#);
+    assert_eq!(response["ok"], true, "{response}");
+
+    let start = Instant::now();
+    while daemon.child.try_wait().unwrap().is_none() {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "daemon didn't stop"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert!(!daemon.socket_path().exists());
+}
+
+#[test]
+fn requests_in_other_protocol_versions_are_refused() {
+    let fixture = Fixture::new();
+    fixture.init("repo");
+    let daemon = Daemon::start(&fixture.path());
+
+    let response = daemon.request(r#"{"version": 2, "command": "ping"}"#);
+    assert_eq!(response["ok"], false, "{response}");
+    assert_eq!(response["version"], 3, "{response}");
+    assert_eq!(
+        response["error"],
+        "Unsupported protocol version 2, this daemon speaks version 3"
+    );
+
+    let response = daemon.request(r#"{"version": 3, "command": "scan"}"#);
+    assert_eq!(response["error"], "Unknown command scan", "{response}");
+}
+
+#[test]
+fn repos_are_served_with_their_states() {
+    let fixture = Fixture::new();
+    fixture.init("local");
+    let app = fixture.pushed("app", &[("src/lib.rs", "fn a() {}\n")]);
+    write(&app.join("src/lib.rs"), "fn a() {}\nfn b() {}\n");
+    let daemon = Daemon::start(&fixture.path());
+
+    let response = daemon.repos(&fixture.path());
+    assert_eq!(response["ok"], true, "{response}");
+    assert_eq!(response["base_path"], fixture.path().to_str().unwrap());
+    assert_eq!(response["scan_stats"]["repo_count"], 2, "{response}");
+    assert_eq!(state(&response, "app"), "unstaged");
+    assert_eq!(state(&response, "local"), "clean");
+    assert_eq!(response["repos"][0]["diff_stat"], "1\t0\tsrc/lib.rs\n");
+}
+
+#[test]
+fn repos_changed_after_startup_are_answered_with_their_new_state() {
+    let fixture = Fixture::new();
+    let app = fixture.pushed("app", &[("src/lib.rs", "fn a() {}\n")]);
+    let daemon = Daemon::start(&fixture.path());
+    assert_eq!(state(&daemon.repos(&fixture.path()), "app"), "clean");
+
+    // Edited long before the next poll
+    write(&app.join("src/lib.rs"), "fn a() {}\nfn b() {}\n");
+    assert_eq!(state(&daemon.repos(&fixture.path()), "app"), "unstaged");
+
+    let summary = daemon.summary(&fixture.path());
+    assert!(summary.contains("from daemon"), "{summary}");
+    assert!(summary.contains("1 unstaged"), "{summary}");
+}
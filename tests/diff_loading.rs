@@ -0,0 +1,109 @@
+#![allow(clippy::unwrap_used)]
+
+mod common;
+
+use std::fs;
+
+use common::{Fixture, app_with, draw, press};
+use fuzzit::{
+    config::Config,
+    filter::Filters,
+    git_data::{DiffOptions, load_diff},
+    sort::GroupBy,
+};
+
+// Clone with 40 changed lines in nums.txt and a changed binary file
+fn fixture() -> Fixture {
+    let fixture = Fixture::new();
+    let nums = (1..=40).map(|i| format!("{i}\n")).collect::<String>();
+    let repo_path = fixture.pushed("app", &[("nums.txt", &nums), ("logo.png", "\0png\0")]);
+    fs::write(repo_path.join("nums.txt"), nums.replace('\n', "x\n")).unwrap();
+    fs::write(repo_path.join("logo.png"), "\0png2\0").unwrap();
+
+    fixture
+}
+
+#[test]
+fn scans_only_count_changed_lines() {
+    let fixture = fixture();
+    let repos = fixture.scan(&fixture.path());
+    let git_data = &repos[0].data;
+
+    assert_eq!(git_data.diff, None);
+    assert_eq!(git_data.changed_files(), 2);
+    // Binary files have no lines
+    assert_eq!(git_data.changed_lines(), 80);
+}
+
+#[test]
+fn diffs_are_cut_off_at_the_max_size() {
+    let fixture = fixture();
+    let repo_path = fixture.join("app");
+
+    let full = load_diff(&repo_path, None, &DiffOptions::default(), None).unwrap();
+    assert!(!full.truncated);
+    assert!(
+        full.text
+            .contains("Binary files a/logo.png and b/logo.png differ"),
+        "{}",
+        full.text
+    );
+
+    let diff = load_diff(&repo_path, Some(300), &DiffOptions::default(), None).unwrap();
+    assert!(diff.truncated);
+    assert!(diff.text.len() <= 300 && diff.text.ends_with('\n'));
+    assert!(full.text.starts_with(&diff.text));
+
+    // Big enough for all of it
+    let diff = load_diff(
+        &repo_path,
+        Some(full.text.len()),
+        &DiffOptions::default(),
+        None,
+    )
+    .unwrap();
+    assert_eq!(diff, full);
+}
+
+#[test]
+fn files_diffed_as_text_are_summarized_when_binary() {
+    let fixture = fixture();
+    let repo_path = fixture.join("app");
+    fs::write(repo_path.join(".gitattributes"), "*.png diff\n").unwrap();
+
+    let diff = load_diff(&repo_path, None, &DiffOptions::default(), None).unwrap();
+    assert!(!diff.text.contains('\0'), "{:?}", diff.text);
+    assert!(
+        diff.text
+            .contains("Binary files a/logo.png and b/logo.png differ\n"),
+        "{}",
+        diff.text
+    );
+    assert!(diff.text.contains("+40x"), "{}", diff.text);
+}
+
+#[test]
+fn cut_off_diffs_can_be_loaded_whole() {
+    let fixture = fixture();
+    let mut config = Config::default();
+    config.diff.max_size = 300;
+    let mut app = app_with(
+        fixture.scan(&fixture.path()),
+        GroupBy::None,
+        Filters::default(),
+        config,
+    );
+
+    press(&mut app, "z");
+    let frame = draw(&mut app, 80, 200);
+    assert!(
+        frame.contains("Diff cut off at 300 bytes, press M to load all of it"),
+        "{frame}"
+    );
+    assert!(!frame.contains("+40x"), "{frame}");
+
+    press(&mut app, "M");
+    let frame = draw(&mut app, 80, 200);
+    assert!(!frame.contains("Diff cut off"), "{frame}");
+    assert!(frame.contains("+40x"), "{frame}");
+}
@@ -0,0 +1,161 @@
+#![allow(clippy::unwrap_used)]
+
+mod common;
+
+use std::fs;
+
+use common::{Fixture, draw, fuzzit_command, press, stdout};
+use fuzzit::{
+    config::Config,
+    git_data::{DiffAlgorithm, DiffOptions, RenameDetection, load_diff},
+};
+use tempfile::TempDir;
+
+// Clone with a reindented file, blank lines added to another and one word changed
+fn fixture() -> Fixture {
+    let fixture = Fixture::new();
+    let repo_path = fixture.pushed(
+        "app",
+        &[
+            ("main.rs", "fn main() {\n  run();\n}\n"),
+            ("notes.md", "draft\nreview\n"),
+            ("words.txt", "the quick fox\n"),
+        ],
+    );
+    fs::write(repo_path.join("main.rs"), "fn main() {\n    run();\n}\n").unwrap();
+    fs::write(repo_path.join("notes.md"), "draft\n\n\nreview\n").unwrap();
+    fs::write(repo_path.join("words.txt"), "the slow fox\n").unwrap();
+
+    fixture
+}
+
+#[test]
+fn options_are_passed_to_git_diff() {
+    assert!(DiffOptions::default().args().is_empty());
+
+    let options = DiffOptions {
+        ignore_whitespace: true,
+        ignore_blank_lines: true,
+        context: Some(1),
+        renames: RenameDetection::Copies,
+        word_diff: true,
+        algorithm: DiffAlgorithm::Histogram,
+    };
+    assert_eq!(
+        options.args(),
+        [
+            "-w",
+            "--ignore-blank-lines",
+            "-U1",
+            "--find-copies",
+            "--word-diff=plain",
+            "--diff-algorithm=histogram"
+        ]
+    );
+}
+
+#[test]
+fn whitespace_and_blank_lines_can_be_ignored() {
+    let fixture = fixture();
+    let repo_path = fixture.join("app");
+
+    let diff = load_diff(&repo_path, None, &DiffOptions::default(), None).unwrap();
+    assert!(diff.text.contains("+    run();"), "{}", diff.text);
+    assert!(diff.text.contains("diff --git a/notes.md"), "{}", diff.text);
+
+    let options = DiffOptions {
+        ignore_whitespace: true,
+        ignore_blank_lines: true,
+        ..DiffOptions::default()
+    };
+    let diff = load_diff(&repo_path, None, &options, None).unwrap();
+    assert!(!diff.text.contains("run();"), "{}", diff.text);
+    assert!(!diff.text.contains("+review"), "{}", diff.text);
+    assert!(diff.text.contains("+the slow fox"), "{}", diff.text);
+}
+
+#[test]
+fn options_are_read_from_the_config() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(
+        &path,
+        r#"
+[diff]
+ignore_whitespace = true
+context = 5
+renames = "off"
+algorithm = "patience"
+"#,
+    )
+    .unwrap();
+
+    let options = Config::load(Some(path.clone())).unwrap().diff.options();
+    assert_eq!(
+        options.args(),
+        ["-w", "-U5", "--no-renames", "--diff-algorithm=patience"]
+    );
+
+    fs::write(&path, "[diff]\nalgorithm = \"fast\"\n").unwrap();
+    let error = Config::load(Some(path)).unwrap_err().to_string();
+    assert!(error.contains("unknown variant `fast`"), "{error}");
+}
+
+#[test]
+fn toggles_load_the_diff_again() {
+    let fixture = fixture();
+    let mut app = fixture.app();
+
+    let frame = draw(&mut app, 120, 40);
+    assert!(frame.contains(" Diff Preview "), "{frame}");
+    assert!(frame.contains("+    run();"), "{frame}");
+
+    press(&mut app, "w");
+    let frame = draw(&mut app, 120, 40);
+    assert!(frame.contains(" Diff Preview (-w) "), "{frame}");
+    assert!(!frame.contains("run();"), "{frame}");
+
+    press(&mut app, "W--");
+    let frame = draw(&mut app, 120, 40);
+    assert!(
+        frame.contains(" Diff Preview (-w -U1 --word-diff=plain) "),
+        "{frame}"
+    );
+    assert!(frame.contains("the [-quick-]{+slow+} fox"), "{frame}");
+
+    // Back to what git shows by default
+    press(&mut app, "wW");
+    let frame = draw(&mut app, 120, 40);
+    assert!(frame.contains(" Diff Preview (-U1) "), "{frame}");
+    assert!(frame.contains("-the quick fox"), "{frame}");
+}
+
+#[test]
+fn flags_take_priority_over_the_config() {
+    let fixture = fixture();
+    let home = TempDir::new().unwrap();
+    fs::write(
+        home.path().join("config.toml"),
+        "[diff]\nignore_whitespace = true\n",
+    )
+    .unwrap();
+
+    let grep = |flags: &[&str]| {
+        stdout(
+            fuzzit_command(home.path())
+                .args([
+                    "--no-daemon",
+                    "--status",
+                    "--disable-ascii",
+                    "--grep",
+                    "run",
+                ])
+                .args(flags)
+                .arg(fixture.path()),
+        )
+    };
+
+    // Reindenting isn't a change when whitespace is ignored
+    assert!(grep(&[]).contains("No repos match"));
+    assert!(grep(&["--ignore-whitespace=false"]).contains("app .. DIRTY"));
+}
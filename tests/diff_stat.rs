@@ -0,0 +1,104 @@
+#![allow(clippy::unwrap_used)]
+
+mod common;
+
+use std::fs;
+
+use common::{Fixture, draw, press};
+use fuzzit::git_data::FileStat;
+
+// Clone with lines changed in two text files and a changed binary file
+fn fixture() -> Fixture {
+    let fixture = Fixture::new();
+    let nums = (1..=40).map(|i| format!("{i}\n")).collect::<String>();
+    let repo_path = fixture.pushed(
+        "app",
+        &[
+            ("nums.txt", &nums),
+            ("notes.md", "draft\nreview\n"),
+            ("logo.png", "\0png\0"),
+        ],
+    );
+    fs::write(
+        repo_path.join("nums.txt"),
+        nums.replace('\n', "x\n") + "41\n",
+    )
+    .unwrap();
+    fs::write(repo_path.join("notes.md"), "draft\n").unwrap();
+    fs::write(repo_path.join("logo.png"), "\0png2\0").unwrap();
+
+    fixture
+}
+
+#[test]
+fn numstat_is_counted_per_file() {
+    let fixture = fixture();
+    let repos = fixture.scan(&fixture.path());
+    let git_data = &repos[0].data;
+
+    assert_eq!(
+        git_data.file_stats(),
+        vec![
+            FileStat {
+                path: "logo.png".to_string(),
+                lines: None,
+            },
+            FileStat {
+                path: "notes.md".to_string(),
+                lines: Some((0, 1)),
+            },
+            FileStat {
+                path: "nums.txt".to_string(),
+                lines: Some((41, 40)),
+            },
+        ]
+    );
+    assert_eq!(git_data.insertions(), 41);
+    assert_eq!(git_data.deletions(), 41);
+    assert_eq!(git_data.changed_lines(), 82);
+}
+
+#[test]
+fn badges_and_diffstat_are_shown() {
+    let fixture = fixture();
+    let mut app = fixture.app();
+
+    let frame = draw(&mut app, 120, 30);
+    assert!(
+        frame.contains("app .. DIRTY (changes not added) +41 −41"),
+        "{frame}"
+    );
+    assert!(frame.contains(" Diff Preview "), "{frame}");
+
+    // Zoomed in, the bars have room for every line
+    press(&mut app, "=z");
+    let frame = draw(&mut app, 120, 30);
+    assert!(frame.contains(" Diffstat "), "{frame}");
+    assert!(frame.contains("logo.png | Bin "), "{frame}");
+    assert!(frame.contains("notes.md |   1 -"), "{frame}");
+    assert!(
+        frame.contains(&format!(
+            "nums.txt |  81 {}{}",
+            "+".repeat(41),
+            "-".repeat(40)
+        )),
+        "{frame}"
+    );
+    assert!(
+        frame.contains("3 files changed, 41 insertions(+), 41 deletions(-)"),
+        "{frame}"
+    );
+
+    // Bars are scaled down to fit narrower panes
+    press(&mut app, "z");
+    let frame = draw(&mut app, 120, 30);
+    let bar = frame
+        .lines()
+        .find_map(|line| line.split_once("nums.txt |  81 "))
+        .map(|(_, bar)| bar.trim_end_matches([' ', '│']))
+        .unwrap();
+    assert!(
+        bar.len() < 81 && bar.starts_with('+') && bar.ends_with('-'),
+        "{frame}"
+    );
+}
@@ -0,0 +1,274 @@
+#![allow(clippy::unwrap_used)]
+
+mod common;
+
+use std::{collections::HashSet, fs, path::Path};
+
+use common::{Fixture, fuzzit_command, git, stdout, write};
+use fuzzit::{RepoState, Scan, expand_home};
+use tempfile::TempDir;
+
+fn names(scan: Scan) -> Vec<String> {
+    scan.cache(false)
+        .run()
+        .unwrap()
+        .repos
+        .into_iter()
+        .map(|repo| repo.name)
+        .collect()
+}
+
+#[test]
+fn names_are_relative_to_the_base_path() {
+    let fixture = Fixture::new();
+    fixture.init("top");
+    fixture.init("group/nested");
+    fixture.init("group/deeper/nested");
+
+    assert_eq!(
+        names(Scan::new(fixture.path())),
+        ["group/deeper/nested", "group/nested", "top"]
+    );
+}
+
+#[test]
+fn trailing_slash_on_base_path() {
+    let fixture = Fixture::new();
+    fixture.init("group/nested");
+    let base_path = format!("{}/", fixture.path().display());
+
+    assert_eq!(names(Scan::new(base_path)), ["group/nested"]);
+}
+
+#[test]
+fn repos_inside_repos_are_not_listed() {
+    let fixture = Fixture::new();
+    let outer = fixture.init("outer");
+    let inner = outer.join("vendor/inner");
+    fs::create_dir_all(&inner).unwrap();
+    git(&inner, &["init", "-q", "-b", "main"]);
+
+    let repos = fixture.scan(&fixture.path());
+    assert_eq!(repos.len(), 1);
+    assert_eq!(repos[0].name, "outer");
+    // The nested repo shows up as untracked content of the outer one
+    assert_eq!(repos[0].state(), RepoState::Unstaged);
+}
+
+#[test]
+fn hidden_and_build_folders_are_searched() {
+    let fixture = Fixture::new();
+    fixture.init("shown");
+    fixture.init(".hidden/repo");
+    fixture.init("node_modules/package");
+    fixture.init("target/repo");
+
+    assert_eq!(
+        names(Scan::new(fixture.path())),
+        [
+            ".hidden/repo",
+            "node_modules/package",
+            "shown",
+            "target/repo"
+        ]
+    );
+}
+
+#[test]
+fn worktrees_are_repos() {
+    let fixture = Fixture::new();
+    let main = fixture.init("main");
+    let worktree = fixture.join("worktree");
+    git(
+        &main,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            worktree.to_str().unwrap(),
+            "-b",
+            "wt",
+        ],
+    );
+    assert!(worktree.join(".git").is_file());
+
+    let repos = fixture.scan(&fixture.path());
+    let names: Vec<_> = repos.iter().map(|repo| repo.name.as_str()).collect();
+    assert_eq!(names, ["main", "worktree"]);
+    assert!(repos.iter().all(|repo| repo.state() == RepoState::Clean));
+}
+
+#[test]
+fn submodules_belong_to_their_parent() {
+    let fixture = Fixture::new();
+    let library = fixture.remote("library");
+    let parent = fixture.init("parent");
+    git(
+        &parent,
+        &[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            "-q",
+            library.to_str().unwrap(),
+            "lib",
+        ],
+    );
+    git(&parent, &["commit", "-q", "-m", "Add submodule"]);
+
+    let repos = fixture.scan(&fixture.path());
+    assert_eq!(repos.len(), 1);
+    assert_eq!(repos[0].name, "parent");
+    assert_eq!(repos[0].state(), RepoState::Clean);
+}
+
+#[test]
+fn max_depth_limits_the_search() {
+    let fixture = Fixture::new();
+    fixture.init("one");
+    fixture.init("a/two");
+    fixture.init("a/b/three");
+
+    assert_eq!(names(Scan::new(fixture.path()).max_depth(Some(1))), ["one"]);
+    assert_eq!(
+        names(Scan::new(fixture.path()).max_depth(Some(2))),
+        ["a/two", "one"]
+    );
+    assert_eq!(
+        names(Scan::new(fixture.path()).max_depth(None)),
+        ["a/b/three", "a/two", "one"]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_are_only_followed_when_asked() {
+    let fixture = Fixture::new();
+    let outside = fixture.path().with_file_name("outside");
+    fs::create_dir_all(&outside).unwrap();
+    git(&outside, &["init", "-q", "-b", "main"]);
+    fs::create_dir_all(fixture.path()).unwrap();
+    std::os::unix::fs::symlink(&outside, fixture.join("linked")).unwrap();
+    // A cycle back to the base path must not hang the scan
+    std::os::unix::fs::symlink(fixture.path(), fixture.join("loop")).unwrap();
+
+    assert!(names(Scan::new(fixture.path())).is_empty());
+    assert_eq!(
+        names(Scan::new(fixture.path()).follow_symlinks(true)),
+        ["linked"]
+    );
+}
+
+// Needs a folder on another file system than the temp folder, like /dev/shm on most Linux systems
+#[cfg(unix)]
+#[test]
+fn other_file_systems_are_only_searched_when_allowed() {
+    use std::os::unix::fs::MetadataExt;
+
+    let fixture = Fixture::new();
+    fixture.init("local");
+    let Ok(other_file_system) = TempDir::new_in("/dev/shm") else {
+        return;
+    };
+    let device = |path: &Path| fs::metadata(path).unwrap().dev();
+    if device(other_file_system.path()) == device(&fixture.path()) {
+        return;
+    }
+    let mounted = other_file_system.path().join("mounted");
+    fs::create_dir_all(&mounted).unwrap();
+    git(&mounted, &["init", "-q", "-b", "main"]);
+    std::os::unix::fs::symlink(&mounted, fixture.join("mounted")).unwrap();
+
+    let scan = || Scan::new(fixture.path()).follow_symlinks(true);
+    assert_eq!(names(scan()), ["local", "mounted"]);
+    assert_eq!(names(scan().one_file_system(true)), ["local"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn scan_limits_are_read_per_base_path_from_the_config() {
+    let fixture = Fixture::new();
+    fixture.init("one");
+    fixture.init("a/two");
+    let outside = fixture.path().with_file_name("outside");
+    fs::create_dir_all(&outside).unwrap();
+    git(&outside, &["init", "-q", "-b", "main"]);
+    std::os::unix::fs::symlink(&outside, fixture.join("linked")).unwrap();
+
+    let home = TempDir::new().unwrap();
+    let config = format!(
+        "[scan.\"{}\"]\nmax_depth = 1\nfollow_symlinks = true\n\n[scan.\"~/elsewhere\"]\nmax_depth = 5\n",
+        fixture.path().display()
+    );
+    fs::write(home.path().join("config.toml"), config).unwrap();
+    let status = |flags: &[&str]| {
+        stdout(
+            fuzzit_command(home.path())
+                .args(["--no-daemon", "--status", "--disable-ascii"])
+                .args(flags)
+                .arg(fixture.path()),
+        )
+    };
+
+    let output = status(&[]);
+    assert!(output.contains("linked .."), "{output}");
+    assert!(output.contains("one .."), "{output}");
+    assert!(!output.contains("a/two .."), "{output}");
+
+    // Flags take priority over the config
+    let output = status(&["--max-depth", "2", "--follow-symlinks=false"]);
+    assert!(!output.contains("linked .."), "{output}");
+    assert!(output.contains("a/two .."), "{output}");
+
+    // Other base paths don't get the limits
+    let output = stdout(
+        fuzzit_command(home.path())
+            .args(["--no-daemon", "--status", "--disable-ascii"])
+            .arg(fixture.path().parent().unwrap()),
+    );
+    assert!(output.contains("repos/a/two .."), "{output}");
+}
+
+#[test]
+fn stream_yields_the_same_repos_as_run() {
+    let fixture = Fixture::new();
+    fixture.init("a");
+    fixture.init("b/c");
+    write(&fixture.join("broken/.git"), "gitdir: /nowhere\n");
+
+    let streamed: HashSet<_> = Scan::new(fixture.path())
+        .cache(false)
+        .stream()
+        .map(|repo| repo.unwrap().name)
+        .collect();
+    let ran: HashSet<_> = names(Scan::new(fixture.path())).into_iter().collect();
+
+    assert_eq!(streamed, ran);
+    assert_eq!(streamed.len(), 3);
+}
+
+#[test]
+fn missing_base_path_is_an_error() {
+    let fixture = Fixture::new();
+    let missing = fixture.join("missing");
+
+    let error = Scan::new(&missing).cache(false).run().unwrap_err();
+    assert!(matches!(error, fuzzit::Error::BasePath { path, .. } if path == missing));
+}
+
+#[test]
+fn tilde_expands_to_the_home_directory() {
+    let home = dirs::home_dir().unwrap();
+
+    assert_eq!(expand_home(Path::new("~")).unwrap(), home);
+    assert_eq!(
+        expand_home(Path::new("~/dev/x")).unwrap(),
+        home.join("dev/x")
+    );
+    assert_eq!(
+        expand_home(Path::new("/tmp/~/x")).unwrap(),
+        Path::new("/tmp/~/x")
+    );
+    assert_eq!(expand_home(Path::new("~user")).unwrap(), Path::new("~user"));
+}
@@ -0,0 +1,97 @@
+#![allow(clippy::unwrap_used)]
+
+use std::fs;
+
+use fuzzit::{
+    config::Config,
+    keymap::{Action, Lookup, parse_keys},
+};
+use ratatui::crossterm::event::KeyCode;
+use tempfile::TempDir;
+
+// Loads a config file with the given content
+fn load(content: &str) -> Result<Config, String> {
+    let dir = TempDir::new().map_err(|e| e.to_string())?;
+    let path = dir.path().join("config.toml");
+    fs::write(&path, content).map_err(|e| e.to_string())?;
+
+    Config::load(Some(path)).map_err(|e| e.to_string())
+}
+
+#[test]
+fn default_keys() {
+    let keymap = Config::default().keymap;
+
+    assert_eq!(
+        keymap.lookup(&[KeyCode::Char('j')]),
+        Lookup::Action(Action::SelectNext)
+    );
+    assert_eq!(keymap.lookup(&[KeyCode::Esc]), Lookup::Action(Action::Quit));
+    assert_eq!(keymap.lookup(&[KeyCode::Char('x')]), Lookup::None);
+    assert_eq!(keymap.label(Action::SelectPrevious), "k/↑");
+    assert_eq!(keymap.label(Action::ToggleGroup), "Enter/Space");
+}
+
+#[test]
+fn config_overrides_replace_default_keys() {
+    let config = load(
+        r#"
+[keys]
+select_first = "gg"
+select_last = ["<End>", "G"]
+toggle_zoom = []
+"#,
+    )
+    .unwrap();
+    let keymap = config.keymap;
+
+    assert_eq!(keymap.lookup(&[KeyCode::Char('g')]), Lookup::Pending);
+    assert_eq!(
+        keymap.lookup(&[KeyCode::Char('g'), KeyCode::Char('g')]),
+        Lookup::Action(Action::SelectFirst)
+    );
+    assert_eq!(keymap.lookup(&[KeyCode::PageUp]), Lookup::None);
+    assert_eq!(keymap.label(Action::SelectLast), "End/G");
+    assert_eq!(keymap.lookup(&[KeyCode::Char('z')]), Lookup::None);
+    assert_eq!(keymap.label(Action::ToggleZoom), "");
+    // Untouched actions keep their defaults
+    assert_eq!(keymap.label(Action::SelectNext), "j/↓");
+}
+
+#[test]
+fn invalid_keys_are_config_errors() {
+    let error = load("[keys]\njump = \"J\"\n").unwrap_err();
+    assert!(error.contains("unknown action jump"), "{error}");
+
+    let error = load("[keys]\nquit = \"<Nope>\"\n").unwrap_err();
+    assert!(error.contains("quit: unknown key <Nope>"), "{error}");
+
+    let error = load("[keys]\nquit = \"\"\n").unwrap_err();
+    assert!(error.contains("quit: empty key binding"), "{error}");
+
+    let error = load("[keys]\nquit = \"j\"\n").unwrap_err();
+    assert!(error.contains("j is bound to both"), "{error}");
+
+    // g is still bound to select_first, so gg would never be reached
+    let error = load("[keys]\ncycle_sort = \"gs\"\n").unwrap_err();
+    assert!(
+        error.contains("gs of cycle_sort can never be pressed, g is bound to select_first"),
+        "{error}"
+    );
+
+    let error = load("[keys]\nquit = 1\n").unwrap_err();
+    assert!(error.contains("could not be parsed"), "{error}");
+}
+
+#[test]
+fn key_sequences_are_parsed() {
+    assert_eq!(
+        parse_keys("g<Space><lt>").unwrap(),
+        vec![KeyCode::Char('g'), KeyCode::Char(' '), KeyCode::Char('<')]
+    );
+    assert!(
+        parse_keys("<Enter")
+            .unwrap_err()
+            .contains("missing a closing >")
+    );
+}
@@ -0,0 +1,634 @@
+#![allow(clippy::unwrap_used)]
+
+mod common;
+
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+use common::{app_with, draw, press};
+use fuzzit::{
+    GitData, GitError, Repo,
+    config::{Config, LayoutConfig, Orientation},
+    diff::{App, KeyAction},
+    filter::Filters,
+    git_data::Diff,
+    keymap::{KeyBindings, Keymap},
+    open::OpenTarget,
+    render::{Keys, RenderFormat, parse_size, render_once},
+    sort::GroupBy,
+};
+use ratatui::{
+    Terminal,
+    backend::TestBackend,
+    crossterm::event::{KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    style::{Color, Modifier},
+};
+
+const DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs
+index 3b18e51..a4c5d2f 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    println!(\"hello\");
++    println!(\"hello world\");
+ }
+";
+
+// What git diff --numstat prints for the diff
+fn numstat(diff: &str) -> String {
+    diff.split("diff --git a/")
+        .skip(1)
+        .map(|file| {
+            let path = file.split(' ').next().unwrap();
+            let count = |prefix: char, header: &str| {
+                file.lines()
+                    .filter(|line| line.starts_with(prefix) && !line.starts_with(header))
+                    .count()
+            };
+            format!("{}\t{}\t{path}\n", count('+', "+++"), count('-', "---"))
+        })
+        .collect()
+}
+
+fn repo(name: &str, status: &str, diff: &str) -> Repo {
+    let mut data = GitData::new(
+        PathBuf::from("/dev").join(name),
+        status.to_string(),
+        numstat(diff),
+    );
+    data.diff = Some(Diff::new(diff.to_string(), false));
+    data.last_commit = Some(1_700_000_000);
+    data.modified = Some(1_700_000_000);
+
+    Repo::new(name.to_string(), data)
+}
+
+fn repos() -> Vec<Repo> {
+    let mut broken = repo("tools/broken", "", "");
+    broken.data.error = Some(GitError::new("fatal: not a git repository".to_string()));
+
+    vec![
+        repo("api", "nothing to commit, working tree clean", ""),
+        repo(
+            "web",
+            "Changes not staged for commit:\n\tmodified:   src/lib.rs\n\nno changes added to commit",
+            DIFF,
+        ),
+        repo(
+            "tools/cli",
+            "Your branch is ahead of 'origin/main' by 2 commits.\n\nnothing to commit, working tree clean",
+            DIFF,
+        ),
+        repo(
+            "tools/staged",
+            "Changes to be committed:\n\tnew file:   a.txt",
+            "",
+        ),
+        broken,
+    ]
+}
+
+fn app(group_by: GroupBy) -> App {
+    app_with(repos(), group_by, Filters::default(), Config::default())
+}
+
+// Compares against tests/snapshots/<name>.txt, UPDATE_SNAPSHOTS=1 rewrites it instead
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.txt"));
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        expected == actual,
+        "snapshot {name} changed, rerun with UPDATE_SNAPSHOTS=1 to accept it\n\nexpected:\n{expected}\nactual:\n{actual}"
+    );
+}
+
+#[test]
+fn initial_frame() {
+    let mut app = app(GroupBy::None);
+
+    assert_snapshot("initial_frame", &draw(&mut app, 120, 24));
+}
+
+#[test]
+fn diff_of_selected_repo() {
+    let mut app = app(GroupBy::None);
+    app.handle_key(KeyCode::Char('G'));
+    app.handle_key(KeyCode::Right);
+
+    assert_snapshot("diff_of_selected_repo", &draw(&mut app, 120, 24));
+}
+
+#[test]
+fn error_of_selected_repo() {
+    let mut app = app(GroupBy::None);
+    app.handle_key(KeyCode::Down);
+
+    let frame = draw(&mut app, 120, 24);
+    assert!(frame.contains("Could not read repo:"), "{frame}");
+    assert!(frame.contains("fatal: not a git repository"), "{frame}");
+}
+
+#[test]
+fn grouped_by_parent() {
+    let mut app = app(GroupBy::Parent);
+
+    assert_snapshot("grouped_by_parent", &draw(&mut app, 120, 24));
+}
+
+#[test]
+fn collapsed_group_and_filter() {
+    let mut app = app(GroupBy::Parent);
+    app.handle_key(KeyCode::Enter);
+    app.handle_key(KeyCode::Char('c'));
+
+    assert_snapshot("collapsed_group_and_filter", &draw(&mut app, 120, 24));
+}
+
+#[test]
+fn narrow_terminal() {
+    let mut app = app(GroupBy::None);
+
+    assert_snapshot("narrow_terminal", &draw(&mut app, 60, 20));
+}
+
+#[test]
+fn quit_and_open_keys_are_returned() {
+    let mut app = app(GroupBy::None);
+    assert_eq!(app.handle_key(KeyCode::Char('j')), None);
+    assert_eq!(app.handle_key(KeyCode::Char('q')), Some(KeyAction::Quit));
+    assert_eq!(
+        app.handle_key(KeyCode::Char('e')),
+        Some(KeyAction::Open(OpenTarget::Editor))
+    );
+}
+
+#[test]
+fn render_once_presses_keys_first() {
+    let keys: Keys = "G<Right>".parse().unwrap();
+    let frame = render_once(
+        app(GroupBy::None),
+        parse_size("120x24").unwrap(),
+        &keys,
+        RenderFormat::Text,
+    )
+    .unwrap();
+
+    assert!(frame.contains("> web .. DIRTY"), "{frame}");
+    assert!(frame.contains("+    println!(\"hello world\");"), "{frame}");
+    assert_eq!(frame.lines().count(), 24);
+}
+
+#[test]
+fn render_once_formats() {
+    let size = parse_size("80x20").unwrap();
+    let render = |format| render_once(app(GroupBy::None), size, &Keys::default(), format).unwrap();
+
+    let ansi = render(RenderFormat::Ansi);
+    assert!(ansi.contains("\x1b[0;38;2;255;184;108mDIRTY"), "{ansi}");
+
+    let svg = render(RenderFormat::Svg);
+    assert!(
+        svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"672\" height=\"360\"")
+    );
+    assert!(svg.trim_end().ends_with("</svg>"));
+
+    let html = render(RenderFormat::Html);
+    assert!(html.starts_with("<pre "));
+    assert!(html.contains("color:#0dbc79;\">1 clean</span>"), "{html}");
+}
+
+#[test]
+fn render_keys_and_sizes_are_parsed() {
+    let keys: Keys = "j<Down><enter><lt>q".parse().unwrap();
+    assert_eq!(
+        keys.0,
+        [
+            KeyCode::Char('j'),
+            KeyCode::Down,
+            KeyCode::Enter,
+            KeyCode::Char('<'),
+            KeyCode::Char('q')
+        ]
+    );
+    assert!("<Nope>".parse::<Keys>().is_err());
+    assert!("<Down".parse::<Keys>().is_err());
+
+    assert_eq!(parse_size("120x40").unwrap().width, 120);
+    assert_eq!(parse_size("120X40").unwrap().height, 40);
+    assert!(parse_size("120").is_err());
+    assert!(parse_size("0x40").is_err());
+}
+
+fn mouse(app: &mut App, kind: MouseEventKind, column: u16, row: u16) {
+    app.handle_mouse(MouseEvent {
+        kind,
+        column,
+        row,
+        modifiers: KeyModifiers::NONE,
+    });
+}
+
+#[test]
+fn mouse_click_selects_rows_and_focuses_panes() {
+    let mut app = app(GroupBy::None);
+    draw(&mut app, 120, 24);
+
+    // The path list starts below the 4 line header
+    mouse(&mut app, MouseEventKind::Down(MouseButton::Left), 5, 6);
+    let frame = draw(&mut app, 120, 24);
+    assert!(frame.contains("tools/cli .."), "{frame}");
+    assert!(frame.contains("web .."), "{frame}");
+
+    // Focusing the diff makes j scroll it instead of moving the selection
+    mouse(&mut app, MouseEventKind::Down(MouseButton::Left), 90, 10);
+    app.handle_key(KeyCode::Char('j'));
+    let frame = draw(&mut app, 120, 24);
+    assert!(frame.contains("tools/cli .."), "{frame}");
+    assert!(frame.contains("web .."), "{frame}");
+
+    // Clicks below the last row change nothing
+    mouse(&mut app, MouseEventKind::Down(MouseButton::Left), 5, 15);
+    let frame = draw(&mut app, 120, 24);
+    assert!(frame.contains("tools/cli .."), "{frame}");
+    assert!(frame.contains("web .."), "{frame}");
+}
+
+#[test]
+fn mouse_wheel_scrolls_the_diff() {
+    let mut app = app(GroupBy::None);
+    app.handle_key(KeyCode::Char('G'));
+    draw(&mut app, 120, 24);
+
+    mouse(&mut app, MouseEventKind::ScrollDown, 90, 10);
+    let frame = draw(&mut app, 120, 24);
+    let first_diff_line = frame.lines().nth(5).unwrap();
+    assert!(
+        first_diff_line.ends_with("│ +++ b/src/lib.rs                                         │"),
+        "{frame}"
+    );
+
+    // Scrolling stops at the end of the diff
+    for _ in 0..10 {
+        mouse(&mut app, MouseEventKind::ScrollDown, 90, 10);
+    }
+    mouse(&mut app, MouseEventKind::ScrollUp, 90, 10);
+    let frame = draw(&mut app, 120, 24);
+    assert!(frame.contains("│  }"), "{frame}");
+}
+
+#[test]
+fn mouse_drag_moves_the_split() {
+    let mut app = app(GroupBy::None);
+    draw(&mut app, 120, 24);
+
+    mouse(&mut app, MouseEventKind::Down(MouseButton::Left), 60, 10);
+    mouse(&mut app, MouseEventKind::Drag(MouseButton::Left), 30, 10);
+    mouse(&mut app, MouseEventKind::Up(MouseButton::Left), 30, 10);
+    let frame = draw(&mut app, 120, 24);
+    let top_of_panes: Vec<char> = frame.lines().nth(4).unwrap().chars().collect();
+    assert_eq!(top_of_panes[30], '╭', "{frame}");
+
+    // Dragging past the edge keeps both panes visible
+    mouse(&mut app, MouseEventKind::Down(MouseButton::Left), 30, 10);
+    mouse(&mut app, MouseEventKind::Drag(MouseButton::Left), 0, 10);
+    let frame = draw(&mut app, 120, 24);
+    let top_of_panes: Vec<char> = frame.lines().nth(4).unwrap().chars().collect();
+    assert_eq!(top_of_panes[12], '╭', "{frame}");
+}
+
+// Column of the top left corner of the diff pane, on the first line below the header
+fn diff_pane_column(frame: &str) -> Option<usize> {
+    frame
+        .lines()
+        .nth(4)?
+        .chars()
+        .skip(1)
+        .position(|c| c == '╭')
+        .map(|i| i + 1)
+}
+
+#[test]
+fn split_can_be_resized_with_keys() {
+    let mut app = app(GroupBy::None);
+    assert_eq!(diff_pane_column(&draw(&mut app, 120, 24)), Some(60));
+
+    app.handle_key(KeyCode::Char(']'));
+    app.handle_key(KeyCode::Char(']'));
+    assert_eq!(diff_pane_column(&draw(&mut app, 120, 24)), Some(72));
+
+    for _ in 0..20 {
+        app.handle_key(KeyCode::Char('['));
+    }
+    assert_eq!(diff_pane_column(&draw(&mut app, 120, 24)), Some(12));
+}
+
+#[test]
+fn orientation_and_footer_toggles() {
+    let mut app = app(GroupBy::None);
+
+    // Side by side, then forced stacked, then back to auto
+    app.handle_key(KeyCode::Char('L'));
+    assert_eq!(diff_pane_column(&draw(&mut app, 60, 30)), Some(30));
+    app.handle_key(KeyCode::Char('L'));
+    let frame = draw(&mut app, 120, 30);
+    assert_eq!(diff_pane_column(&frame), None, "{frame}");
+    app.handle_key(KeyCode::Char('L'));
+    assert_eq!(diff_pane_column(&draw(&mut app, 120, 30)), Some(60));
+
+    app.handle_key(KeyCode::Char('f'));
+    let frame = draw(&mut app, 120, 30);
+    assert!(!frame.contains("(?) all keys"), "{frame}");
+    assert!(frame.lines().last().unwrap().starts_with("  "), "{frame}");
+}
+
+#[test]
+fn zoomed_diff_fills_the_screen() {
+    let mut app = app(GroupBy::None);
+    app.handle_key(KeyCode::Char('G'));
+    app.handle_key(KeyCode::Char('z'));
+
+    assert_snapshot("zoomed_diff", &draw(&mut app, 80, 16));
+
+    // Esc leaves the zoom before it quits
+    assert_eq!(app.handle_key(KeyCode::Esc), None);
+    assert!(draw(&mut app, 80, 16).contains("Viewing git repos"));
+    assert_eq!(app.handle_key(KeyCode::Esc), Some(KeyAction::Quit));
+}
+
+#[test]
+fn layout_comes_from_config() {
+    let config = Config {
+        layout: LayoutConfig {
+            orientation: Orientation::Auto,
+            split: 30,
+            stack_below: 140,
+            footer: false,
+        },
+        ..Config::default()
+    };
+    let mut app = app_with(repos(), GroupBy::None, Filters::default(), config);
+
+    let frame = draw(&mut app, 150, 24);
+    assert_eq!(diff_pane_column(&frame), Some(45), "{frame}");
+    assert!(!frame.contains("(?) all keys"), "{frame}");
+    // Narrower than stack_below
+    let frame = draw(&mut app, 120, 24);
+    assert_eq!(diff_pane_column(&frame), None, "{frame}");
+}
+
+#[test]
+fn help_overlay_lists_every_binding() {
+    let mut app = app(GroupBy::None);
+    app.handle_key(KeyCode::Char('?'));
+
+    let frame = draw(&mut app, 120, 40);
+    assert_snapshot("help_overlay", &frame);
+    assert!(
+        frame.contains("g/PgUp  Go to the top (select_first)"),
+        "{frame}"
+    );
+
+    // Any key but scrolling closes it without doing anything else
+    app.handle_key(KeyCode::Char('j'));
+    app.handle_key(KeyCode::Char('q'));
+    let frame = draw(&mut app, 120, 40);
+    assert!(!frame.contains("(select_first)"), "{frame}");
+    assert!(frame.contains("> api .. CLEAN"), "{frame}");
+}
+
+#[test]
+fn keys_come_from_config() {
+    let overrides = HashMap::from([
+        (
+            "select_first".to_string(),
+            KeyBindings::Many(vec!["gg".to_string(), "<Home>".to_string()]),
+        ),
+        ("quit".to_string(), KeyBindings::One("Q".to_string())),
+        ("help".to_string(), KeyBindings::Many(Vec::new())),
+    ]);
+    let config = Config {
+        keymap: Keymap::new(&overrides).unwrap(),
+        ..Config::default()
+    };
+    let mut app = app_with(repos(), GroupBy::None, Filters::default(), config);
+
+    app.handle_key(KeyCode::Char('G'));
+    assert_eq!(app.handle_key(KeyCode::Char('g')), None);
+    let frame = draw(&mut app, 120, 24);
+    assert!(frame.contains("> web .. DIRTY"), "{frame}");
+    assert!(frame.contains("╭ g… ─"), "{frame}");
+    assert!(frame.contains("(gg/Home) top"), "{frame}");
+    assert!(!frame.contains("all keys"), "{frame}");
+
+    app.handle_key(KeyCode::Char('g'));
+    assert!(draw(&mut app, 120, 24).contains("> api .. CLEAN"));
+
+    // A key that doesn't continue a chord is handled on its own
+    app.handle_key(KeyCode::Char('g'));
+    app.handle_key(KeyCode::Char('j'));
+    assert!(draw(&mut app, 120, 24).contains("> tools/broken"));
+    // Esc cancels a chord, and isn't bound to quit anymore
+    app.handle_key(KeyCode::Char('g'));
+    assert_eq!(app.handle_key(KeyCode::Esc), None);
+    assert_eq!(app.handle_key(KeyCode::Char('g')), None);
+    assert_eq!(app.handle_key(KeyCode::Esc), None);
+    assert_eq!(app.handle_key(KeyCode::Char('q')), None);
+    assert_eq!(app.handle_key(KeyCode::Char('Q')), Some(KeyAction::Quit));
+}
+
+// Two files with two hunks each, the first hunk starts with a line that wraps
+fn long_diff() -> String {
+    let mut diff = String::new();
+    for file in ["a.rs", "b.rs"] {
+        diff += &format!(
+            "diff --git a/{file} b/{file}\nindex 1..2 100644\n--- a/{file}\n+++ b/{file}\n"
+        );
+        for hunk in 0..2 {
+            diff += &format!("@@ -{0},20 +{0},20 @@\n", hunk * 100 + 1);
+            if file == "a.rs" && hunk == 0 {
+                diff += &format!("+{}\n", "wrapped ".repeat(40));
+            }
+            for line in 0..20 {
+                diff += &format!(" {file} hunk {hunk} line {line}\n");
+            }
+        }
+    }
+    diff
+}
+
+// Zoomed into the diff of a single repo, so the first diff line is on row 1
+fn zoomed_long_diff() -> App {
+    let mut app = common::app(vec![repo(
+        "big",
+        "Changes not staged for commit:",
+        &long_diff(),
+    )]);
+    app.handle_key(KeyCode::Char('z'));
+    app
+}
+
+fn top_line(frame: &str) -> &str {
+    frame.lines().nth(1).unwrap()
+}
+
+#[test]
+fn diff_search_jumps_between_matches() {
+    let mut app = zoomed_long_diff();
+
+    press(&mut app, "/line 7$");
+    let frame = draw(&mut app, 80, 20);
+    assert!(frame.contains("/line 7$█ "), "{frame}");
+    assert!(frame.contains("a.rs hunk 0 line 0"), "{frame}");
+
+    press(&mut app, "<Enter>");
+    let frame = draw(&mut app, 80, 20);
+    assert!(top_line(&frame).contains("a.rs hunk 0 line 7"), "{frame}");
+    assert!(frame.contains(" /line 7$ 1/4 "), "{frame}");
+
+    press(&mut app, "n");
+    let frame = draw(&mut app, 80, 20);
+    assert!(top_line(&frame).contains("a.rs hunk 1 line 7"), "{frame}");
+    assert!(frame.contains(" /line 7$ 2/4 "), "{frame}");
+
+    // Wraps around from the first match to the last
+    press(&mut app, "NN");
+    let frame = draw(&mut app, 80, 20);
+    assert!(top_line(&frame).contains("b.rs hunk 1 line 7"), "{frame}");
+    assert!(frame.contains(" /line 7$ 4/4 "), "{frame}");
+
+    // Lowercase patterns ignore case, patterns with capitals don't
+    press(&mut app, "/A\\.RS<Enter>");
+    assert!(draw(&mut app, 80, 20).contains(" /A\\.RS no matches "));
+    press(&mut app, "/a\\.rs<Enter>");
+    assert!(draw(&mut app, 80, 20).contains(" /a\\.rs 1/"));
+
+    // Esc clears the search, then unzooms, then quits
+    assert_eq!(app.handle_key(KeyCode::Esc), None);
+    assert!(!draw(&mut app, 80, 20).contains(" /a\\.rs"));
+    assert_eq!(app.handle_key(KeyCode::Esc), None);
+    assert_eq!(app.handle_key(KeyCode::Esc), Some(KeyAction::Quit));
+}
+
+#[test]
+fn diff_search_reports_invalid_patterns() {
+    let mut app = zoomed_long_diff();
+
+    press(&mut app, "/(line");
+    let frame = draw(&mut app, 80, 20);
+    assert!(frame.contains("/(line█ (unclosed group) "), "{frame}");
+    press(&mut app, "<Enter>");
+    let frame = draw(&mut app, 80, 20);
+    assert!(
+        frame.contains(" Invalid search: unclosed group "),
+        "{frame}"
+    );
+    // Typing keys aren't actions, so q didn't quit and Backspace edits the pattern
+    press(&mut app, "/qx<Backspace><Esc>");
+    assert!(!draw(&mut app, 80, 20).contains("search"));
+}
+
+#[test]
+fn diff_search_highlights_matches() {
+    let mut app = zoomed_long_diff();
+    press(&mut app, "/hunk 1 line 3$<Enter>");
+
+    let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+    terminal.draw(|frame| app.render(frame)).unwrap();
+    let buffer = terminal.backend().buffer();
+    // " a.rs hunk 1 line 3" is on the top line, after the left border and padding
+    let highlighted = (0..80)
+        .filter(|&x| buffer[(x, 1)].modifier.contains(Modifier::REVERSED))
+        .collect::<Vec<_>>();
+    assert_eq!(highlighted, (8..21).collect::<Vec<_>>());
+    assert_eq!(buffer[(8, 1)].fg, Color::Yellow);
+}
+
+#[test]
+fn repos_can_be_searched_by_their_changes() {
+    let mut repos = repos();
+    let diff = long_diff() + "diff --git a/c.rs b/c.rs\n@@ -1 +1 @@\n+needle\n";
+    repos.push(repo("big", "Changes not staged for commit:", &diff));
+    let mut app = common::app(repos);
+
+    // The list narrows as you type and the preview scrolls to the first hit
+    press(&mut app, "Fneedle");
+    let frame = draw(&mut app, 100, 24);
+    assert!(
+        frame.contains(" Search changes: needle█ (Tab: file names) "),
+        "{frame}"
+    );
+    assert!(frame.contains("Showing 1/6"), "{frame}");
+    assert!(
+        frame.lines().nth(5).unwrap().contains("│ +needle"),
+        "{frame}"
+    );
+
+    // Enter keeps the filter and highlights the hits in the diff
+    press(&mut app, "<Enter>");
+    let frame = draw(&mut app, 100, 24);
+    assert!(
+        frame.contains("filtered by changes matching needle"),
+        "{frame}"
+    );
+    assert!(frame.contains(" /needle 1/1 "), "{frame}");
+
+    // Tab searches the names of changed files instead
+    assert_eq!(app.handle_key(KeyCode::Esc), None);
+    press(&mut app, "F<Tab>lib\\.rs");
+    let frame = draw(&mut app, 100, 24);
+    assert!(frame.contains(" Search file names: lib\\.rs█ "), "{frame}");
+    assert!(frame.contains("Showing 2/6"), "{frame}");
+    assert!(frame.contains("tools/cli .."), "{frame}");
+    assert!(frame.contains("web .."), "{frame}");
+
+    // Invalid patterns show every repo until they're fixed
+    press(&mut app, "(");
+    let frame = draw(&mut app, 100, 24);
+    assert!(frame.contains("(unclosed group)"), "{frame}");
+    assert!(frame.contains("Showing 6/6"), "{frame}");
+
+    // Esc clears the search, then quits
+    assert_eq!(app.handle_key(KeyCode::Esc), None);
+    assert!(draw(&mut app, 100, 24).contains("filtered by none"));
+    assert_eq!(app.handle_key(KeyCode::Esc), Some(KeyAction::Quit));
+}
+
+#[test]
+fn hunks_and_files_can_be_jumped_to() {
+    let mut app = zoomed_long_diff();
+
+    press(&mut app, "}");
+    let frame = draw(&mut app, 80, 20);
+    assert!(top_line(&frame).contains("@@ -1,20 +1,20 @@"), "{frame}");
+    // The next hunk is below a line that wraps over several rows
+    press(&mut app, "}");
+    let frame = draw(&mut app, 80, 20);
+    assert!(
+        top_line(&frame).contains("@@ -101,20 +101,20 @@"),
+        "{frame}"
+    );
+
+    press(&mut app, ")");
+    let frame = draw(&mut app, 80, 20);
+    assert!(
+        top_line(&frame).contains("diff --git a/b.rs b/b.rs"),
+        "{frame}"
+    );
+    press(&mut app, "(");
+    let frame = draw(&mut app, 80, 20);
+    assert!(
+        top_line(&frame).contains("diff --git a/a.rs b/a.rs"),
+        "{frame}"
+    );
+    press(&mut app, "{");
+    assert!(top_line(&draw(&mut app, 80, 20)).contains("diff --git a/a.rs"));
+}
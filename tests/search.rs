@@ -0,0 +1,214 @@
+#![allow(clippy::unwrap_used)]
+
+mod common;
+
+use std::{fs, path::PathBuf, process::Command};
+
+use common::{Fixture, app_with, commit, draw, fuzzit_command, press, stdout, write};
+use fuzzit::{
+    GitData,
+    config::Config,
+    filter::{ContentSearch, Filters},
+    git_data::Diff,
+    sort::GroupBy,
+};
+use tempfile::TempDir;
+
+// Runs fuzzit --status on the fixture
+fn run(home: &TempDir, fixture: &Fixture, args: &[&str]) -> Command {
+    let mut command = fuzzit_command(home.path());
+    command
+        .args(["--no-daemon", "--status", "--disable-ascii"])
+        .args(args)
+        .arg(fixture.path());
+    command
+}
+
+fn fuzzit(fixture: &Fixture, args: &[&str]) -> String {
+    let home = TempDir::new().unwrap();
+    stdout(&mut run(&home, fixture, args))
+}
+
+fn fuzzit_error(fixture: &Fixture, args: &[&str]) -> String {
+    let home = TempDir::new().unwrap();
+    let output = run(&home, fixture, args).output().unwrap();
+    assert!(!output.status.success(), "fuzzit {args:?} succeeded");
+
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+fn fixture() -> Fixture {
+    let fixture = Fixture::new();
+
+    let remote = fixture.remote("todo");
+    let todo = fixture.clone(&remote, "todo");
+    commit(&todo, "src/main.rs", "fn main() {}\n");
+    fs::write(todo.join("src/main.rs"), "fn main() {}\n// TODO: args\n").unwrap();
+
+    let config = fixture.pushed("config", &[("config.yaml", "replicas: 1\n")]);
+    fs::write(config.join("config.yaml"), "replicas: 2\n").unwrap();
+    write(&config.join("deploy/app.yaml"), "image: app\n");
+
+    let remote = fixture.remote("clean");
+    fixture.clone(&remote, "clean");
+
+    fixture
+}
+
+fn git_data(status: &str, diff: &str) -> GitData {
+    let mut git_data = GitData::new(
+        PathBuf::from("/dev/repo"),
+        status.to_string(),
+        String::new(),
+    );
+    git_data.diff = Some(Diff::new(diff.to_string(), false));
+    git_data
+}
+
+#[test]
+fn grep_lists_repos_with_matching_changes() {
+    let fixture = fixture();
+
+    let output = fuzzit(&fixture, &["--grep", "todo"]);
+    assert!(output.contains("todo .. DIRTY"), "{output}");
+    assert!(
+        output.contains("    src/main.rs: +// TODO: args"),
+        "{output}"
+    );
+    assert!(!output.contains("config .."), "{output}");
+    assert!(!output.contains("clean .."), "{output}");
+
+    // Capitals make the search case-sensitive
+    let output = fuzzit(&fixture, &["--grep", "Todo"]);
+    assert!(
+        output.contains("No repos match the filters (changes matching Todo)"),
+        "{output}"
+    );
+
+    let error = fuzzit_error(&fixture, &["--grep", "todo("]);
+    assert!(error.contains("unclosed group"), "{error}");
+}
+
+#[test]
+fn grep_files_matches_changed_and_untracked_paths() {
+    let fixture = fixture();
+
+    let output = fuzzit(&fixture, &["--grep", r"config\.yaml", "--grep-files"]);
+    assert!(output.contains("config .. DIRTY"), "{output}");
+    assert!(output.contains("    config.yaml\n"), "{output}");
+    assert!(!output.contains("todo .."), "{output}");
+
+    // git status lists untracked folders, not the files in them
+    let output = fuzzit(&fixture, &["--grep", "^deploy", "--grep-files"]);
+    assert!(output.contains("    deploy/\n"), "{output}");
+
+    // Only file names are searched, not their changes
+    let output = fuzzit(&fixture, &["--grep", "todo", "--grep-files"]);
+    assert!(output.contains("No repos match the filters"), "{output}");
+
+    let error = fuzzit_error(&fixture, &["--grep-files"]);
+    assert!(error.contains("--grep <REGEX>"), "{error}");
+}
+
+#[test]
+fn first_hit_points_at_the_diff_line() {
+    let diff = "diff --git a/old.rs b/new.rs
+similarity index 90%
+--- a/old.rs
++++ b/new.rs
+@@ -1,2 +1,2 @@
+ fn a() {}
+-fn needle() {}
++fn other() {}
+";
+    let data = git_data("", diff);
+
+    let hit = ContentSearch::new("needle", false)
+        .unwrap()
+        .first_hit(&data)
+        .unwrap();
+    assert_eq!(
+        (hit.file, hit.line, hit.text),
+        ("new.rs", Some(6), "-fn needle() {}")
+    );
+
+    // Headers and context lines aren't changes
+    let search = ContentSearch::new("old|fn a", false).unwrap();
+    assert_eq!(search.first_hit(&data), None);
+
+    let hit = ContentSearch::new("new", true)
+        .unwrap()
+        .first_hit(&data)
+        .unwrap();
+    assert_eq!((hit.file, hit.line), ("new.rs", Some(0)));
+}
+
+#[test]
+fn file_names_come_from_git_status_too() {
+    let status = "Changes to be committed:
+\trenamed:    src/old.rs -> src/new.rs
+\tdeleted:    Cargo.lock
+
+Untracked files:
+\tnotes: draft.md
+";
+    let data = git_data(status, "");
+    let hit = |pattern| {
+        ContentSearch::new(pattern, true)
+            .unwrap()
+            .first_hit(&data)
+            .map(|hit| hit.file)
+    };
+
+    assert_eq!(hit("new"), Some("src/new.rs"));
+    assert_eq!(hit("lock"), Some("Cargo.lock"));
+    assert_eq!(hit("draft"), Some("notes: draft.md"));
+    assert_eq!(hit("deleted|old"), None);
+}
+
+#[test]
+fn grep_searches_past_the_max_size_of_diffs() {
+    let fixture = Fixture::new();
+    let lines = (1..=100).map(|i| format!("line {i}\n")).collect::<String>();
+    let repo_path = fixture.pushed("big", &[("lines.txt", &lines)]);
+    fs::write(
+        repo_path.join("lines.txt"),
+        lines.replace("line 1\n", "first\n") + "needle\n",
+    )
+    .unwrap();
+
+    let home = TempDir::new().unwrap();
+    fs::write(home.path().join("config.toml"), "[diff]\nmax_size = 200\n").unwrap();
+    let output = stdout(&mut run(&home, &fixture, &["--grep", "needle"]));
+    assert!(output.contains("big .. DIRTY"), "{output}");
+    assert!(output.contains("    lines.txt: +needle"), "{output}");
+}
+
+#[test]
+fn hits_are_found_again_in_word_diffs() {
+    let fixture = Fixture::new();
+    let lines = (1..=60).map(|i| format!("line {i}\n")).collect::<String>();
+    let repo_path = fixture.pushed("words", &[("lines.txt", &lines)]);
+    let lines = lines
+        .replace("line 5\n", "line 5 changed\n")
+        .replace("line 50\n", "line 50 needle\n");
+    fs::write(repo_path.join("lines.txt"), lines).unwrap();
+
+    let mut config = Config::default();
+    config.diff.word_diff = true;
+    let mut app = app_with(
+        fixture.scan(&fixture.path()),
+        GroupBy::None,
+        Filters::default(),
+        config,
+    );
+
+    // Word diffs have one line per changed line instead of a removed and an added one
+    press(&mut app, "zFneedle<Enter>");
+    let frame = draw(&mut app, 80, 20);
+    assert!(
+        frame.lines().nth(1).unwrap().contains("line 50 {+needle+}"),
+        "{frame}"
+    );
+    assert!(frame.contains(" /needle 1/1 "), "{frame}");
+}
@@ -0,0 +1,277 @@
+#![allow(clippy::unwrap_used)]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{Fixture, app_with, commit, draw, git, press};
+use fuzzit::{
+    config::Config,
+    filter::Filters,
+    sort::GroupBy,
+    stage::{ChangeAction, Changes, Section},
+};
+
+// Clone with two unstaged hunks in nums.txt and an untracked file
+fn fixture() -> Fixture {
+    let fixture = Fixture::new();
+    let nums = (1..=30).map(|i| format!("{i}\n")).collect::<String>();
+    let repo_path = fixture.pushed("app", &[("nums.txt", &nums)]);
+    git(&repo_path, &["config", "user.name", "fuzzit"]);
+    git(&repo_path, &["config", "user.email", "fuzzit@example.com"]);
+    git(&repo_path, &["config", "commit.gpgsign", "false"]);
+
+    let nums = nums
+        .replace("\n2\n", "\ntwo\n")
+        .replace("\n28\n", "\ntwenty-eight\n");
+    fs::write(repo_path.join("nums.txt"), nums).unwrap();
+    fs::write(repo_path.join("notes.md"), "draft\n").unwrap();
+
+    fixture
+}
+
+fn status(repo_path: &Path) -> String {
+    git(repo_path, &["status", "--short"])
+}
+
+#[test]
+fn hunks_are_staged_and_unstaged() {
+    let fixture = fixture();
+    let repo_path = fixture.join("app");
+    let mut app = fixture.app();
+
+    press(&mut app, "S");
+    let frame = draw(&mut app, 100, 40);
+    assert!(frame.contains(" Changes "), "{frame}");
+    assert!(frame.contains("Unstaged changes"), "{frame}");
+    assert!(frame.contains("Untracked files"), "{frame}");
+    assert!(
+        frame.contains(" unstaged hunk of nums.txt (1/3) "),
+        "{frame}"
+    );
+
+    press(&mut app, "a");
+    let frame = draw(&mut app, 100, 40);
+    assert!(frame.contains(" Staged a hunk of nums.txt "), "{frame}");
+    assert!(frame.contains("changes added, not committed"), "{frame}");
+    let staged = git(&repo_path, &["diff", "--cached"]);
+    assert!(
+        staged.contains("+two") && !staged.contains("+twenty-eight"),
+        "{staged}"
+    );
+
+    // The other hunk is selected now, which isn't staged yet
+    press(&mut app, "u");
+    assert!(draw(&mut app, 100, 40).contains(" nums.txt has nothing staged "));
+
+    press(&mut app, "}u");
+    assert!(draw(&mut app, 100, 40).contains(" Unstaged a hunk of nums.txt "));
+    assert_eq!(git(&repo_path, &["diff", "--cached"]), "");
+
+    // Whole files, including untracked ones
+    press(&mut app, ")A");
+    assert_eq!(status(&repo_path), "A  notes.md\n M nums.txt\n");
+    press(&mut app, "(A");
+    assert_eq!(status(&repo_path), "A  notes.md\nM  nums.txt\n");
+    press(&mut app, ")U");
+    assert_eq!(status(&repo_path), "A  notes.md\n M nums.txt\n");
+
+    // Esc stops reviewing before it quits
+    press(&mut app, "<Esc>");
+    assert!(draw(&mut app, 100, 40).contains(" Diff Preview "));
+}
+
+#[test]
+fn discards_are_confirmed() {
+    let fixture = fixture();
+    let repo_path = fixture.join("app");
+    let mut app = fixture.app();
+
+    press(&mut app, "Sd");
+    let frame = draw(&mut app, 100, 40);
+    assert!(
+        frame.contains("Discard this hunk of nums.txt? This can't be undone."),
+        "{frame}"
+    );
+
+    // Any other key keeps the changes
+    press(&mut app, "n");
+    assert!(!draw(&mut app, 100, 40).contains("Discard this hunk"));
+    assert!(
+        fs::read_to_string(repo_path.join("nums.txt"))
+            .unwrap()
+            .contains("two")
+    );
+
+    press(&mut app, "dy");
+    let nums = fs::read_to_string(repo_path.join("nums.txt")).unwrap();
+    assert!(
+        !nums.contains("\ntwo\n") && nums.contains("twenty-eight"),
+        "{nums}"
+    );
+
+    press(&mut app, ")D");
+    assert!(draw(&mut app, 100, 40).contains("Delete the untracked file notes.md?"));
+    press(&mut app, "y");
+    assert!(!repo_path.join("notes.md").exists());
+
+    // Staged changes have to be unstaged first
+    press(&mut app, "Ad");
+    assert!(draw(&mut app, 100, 40).contains("can't be discarded, unstage them first"));
+}
+
+#[test]
+fn staged_changes_are_committed_with_a_message() {
+    let fixture = fixture();
+    let repo_path = fixture.join("app");
+    let mut app = fixture.app();
+
+    // Nothing is staged yet, the message is kept to try again
+    press(&mut app, "CCount in words<Tab>");
+    let frame = draw(&mut app, 100, 40);
+    assert!(frame.contains("git commit -a"), "{frame}");
+    assert!(frame.contains("Count in words█"), "{frame}");
+
+    press(
+        &mut app,
+        "<Esc>SACCount in letters<Enter><Enter>Both hunks<Tab>",
+    );
+    let frame = draw(&mut app, 100, 40);
+    assert!(frame.contains(" Committed [main "), "{frame}");
+    assert!(frame.contains("1 commits unpushed"), "{frame}");
+    assert_eq!(
+        git(&repo_path, &["log", "-1", "--format=%B"]).trim(),
+        "Count in letters\n\nBoth hunks"
+    );
+    assert_eq!(status(&repo_path), "?? notes.md\n");
+}
+
+#[test]
+fn reviews_end_once_their_repo_is_hidden() {
+    let fixture = fixture();
+    let other_path = fixture.init("other");
+    fs::write(other_path.join("todo.md"), "later\n").unwrap();
+    let filters = Filters {
+        hide_clean: true,
+        ..Filters::default()
+    };
+    let mut app = app_with(
+        fixture.scan(&fixture.path()),
+        GroupBy::None,
+        filters,
+        Config::default(),
+    );
+
+    // Discarding everything hides the clean repo, the review doesn't move on to the next one
+    press(&mut app, "SDyDy");
+    let frame = draw(&mut app, 100, 40);
+    assert!(frame.contains(" Diff Preview "), "{frame}");
+    assert!(!frame.contains("todo.md"), "{frame}");
+    assert!(other_path.join("todo.md").exists());
+}
+
+#[test]
+fn crlf_lines_and_quoted_paths_are_staged() {
+    let fixture = Fixture::new();
+    let repo_path = fixture.init("app");
+    let nums = (1..=30).map(|i| format!("{i}\r\n")).collect::<String>();
+    commit(&repo_path, "crlf.txt", &nums);
+    commit(&repo_path, "tab\tcafé.txt", "old\n");
+    let nums = nums
+        .replace("\r\n2\r\n", "\r\ntwo\r\n")
+        .replace("\r\n28\r\n", "\r\ntwenty-eight\r\n");
+    fs::write(repo_path.join("crlf.txt"), nums).unwrap();
+    fs::write(repo_path.join("tab\tcafé.txt"), "new\n").unwrap();
+    let mut app = fixture.app();
+
+    press(&mut app, "Sa");
+    let frame = draw(&mut app, 100, 40);
+    assert!(frame.contains(" Staged a hunk of crlf.txt "), "{frame}");
+    let staged = git(&repo_path, &["diff", "--cached"]);
+    assert!(
+        staged.contains("+two\r\n") && !staged.contains("+twenty-eight"),
+        "{staged}"
+    );
+
+    // git quotes the path in diff --git for the tab
+    press(&mut app, ")A");
+    let frame = draw(&mut app, 100, 40);
+    assert!(frame.contains(" Staged tab\tcafé.txt "), "{frame}");
+    assert_eq!(
+        git(&repo_path, &["diff", "--cached", "--name-only", "-z"]),
+        "crlf.txt\0tab\tcafé.txt\0"
+    );
+}
+
+fn staged_hunk(changes: &Changes) -> usize {
+    changes
+        .hunks
+        .iter()
+        .position(|hunk| hunk.section == Section::Staged)
+        .unwrap()
+}
+
+#[test]
+fn hunks_of_files_with_a_textconv_driver_are_staged() {
+    let fixture = fixture();
+    let repo_path = fixture.join("app");
+    fs::write(repo_path.join(".gitattributes"), "*.txt diff=marked\n").unwrap();
+    git(
+        &repo_path,
+        &["config", "diff.marked.textconv", "sed 's/^/converted: /'"],
+    );
+
+    let changes = Changes::load(&repo_path, None).unwrap();
+    assert!(
+        !changes.lines.iter().any(|line| line.contains("converted")),
+        "{:#?}",
+        changes.lines
+    );
+
+    changes.apply(0, ChangeAction::Stage, false, None).unwrap();
+    let staged = git(&repo_path, &["diff", "--cached", "--no-textconv"]);
+    assert!(
+        staged.contains("+two") && !staged.contains("+twenty-eight"),
+        "{staged}"
+    );
+}
+
+#[test]
+fn files_are_unstaged_before_the_first_commit() {
+    let fixture = Fixture::new();
+    let repo_path = fixture.join("new");
+    fs::create_dir_all(&repo_path).unwrap();
+    git(&repo_path, &["init", "-q", "-b", "main"]);
+    fs::write(repo_path.join("a.txt"), "first\n").unwrap();
+    git(&repo_path, &["add", "a.txt"]);
+
+    let changes = Changes::load(&repo_path, None).unwrap();
+    let message = changes
+        .apply(staged_hunk(&changes), ChangeAction::Unstage, true, None)
+        .unwrap();
+    assert_eq!(message, "Unstaged a.txt");
+    assert_eq!(status(&repo_path), "?? a.txt\n");
+}
+
+#[test]
+fn unstaging_a_rename_restores_the_old_path() {
+    let fixture = Fixture::new();
+    let repo_path = fixture.init("app");
+    commit(&repo_path, "old name.txt", "one\ntwo\nthree\n");
+    git(&repo_path, &["mv", "old name.txt", "new\tname.txt"]);
+
+    let changes = Changes::load(&repo_path, None).unwrap();
+    let hunk_i = staged_hunk(&changes);
+    assert_eq!(
+        changes.hunks[hunk_i].renamed_from.as_deref(),
+        Some("old name.txt")
+    );
+
+    changes
+        .apply(hunk_i, ChangeAction::Unstage, true, None)
+        .unwrap();
+    assert_eq!(
+        git(&repo_path, &["status", "--short", "-z"]),
+        " D old name.txt\0?? new\tname.txt\0"
+    );
+}
@@ -0,0 +1,169 @@
+#![allow(clippy::unwrap_used)]
+
+mod common;
+
+use std::fs;
+
+use common::{Fixture, fuzzit_command, stdout, write};
+use fuzzit::{
+    RepoState,
+    config::Config,
+    theme::{Theme, ThemeName, parse_style},
+};
+use ratatui::style::{Color, Modifier, Style};
+use tempfile::TempDir;
+
+// Runs fuzzit on the fixture with piped stdout, like a script would
+fn fuzzit(fixture: &Fixture, config: &str, args: &[&str], no_color: bool) -> String {
+    let home = TempDir::new().unwrap();
+    fs::write(home.path().join("config.toml"), config).unwrap();
+
+    let mut command = fuzzit_command(home.path());
+    command.arg("--no-daemon").args(args).arg(fixture.path());
+    if no_color {
+        command.env("NO_COLOR", "1");
+    }
+
+    stdout(&mut command)
+}
+
+fn fixture() -> Fixture {
+    let fixture = Fixture::new();
+    fixture.init("clean");
+    let dirty = fixture.init("dirty");
+    write(&dirty.join("README.md"), "changed\n");
+    fixture
+}
+
+#[test]
+fn styles_are_parsed() {
+    assert_eq!(
+        parse_style("#ffb86c bold italic").unwrap(),
+        Style::new()
+            .fg(Color::Rgb(255, 184, 108))
+            .add_modifier(Modifier::BOLD | Modifier::ITALIC)
+    );
+    assert_eq!(
+        parse_style("white on red").unwrap(),
+        Style::new().fg(Color::White).bg(Color::Red)
+    );
+    assert_eq!(
+        parse_style("light-blue underline").unwrap(),
+        Style::new()
+            .fg(Color::LightBlue)
+            .add_modifier(Modifier::UNDERLINED)
+    );
+    assert!(
+        parse_style("sparkly")
+            .unwrap_err()
+            .contains("unknown color")
+    );
+    assert!(
+        parse_style("red on")
+            .unwrap_err()
+            .contains("missing background")
+    );
+}
+
+#[test]
+fn config_picks_a_base_theme_and_overrides_roles() {
+    let home = TempDir::new().unwrap();
+    let path = home.path().join("config.toml");
+    fs::write(
+        &path,
+        "[theme]\nbase = \"colorblind\"\nunstaged = \"magenta bold\"\n",
+    )
+    .unwrap();
+    let theme = Config::load(Some(path.clone())).unwrap().styles;
+
+    assert_eq!(
+        theme.state(RepoState::Unstaged),
+        Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD)
+    );
+    assert_eq!(theme.clean, Theme::builtin(ThemeName::Colorblind).clean);
+
+    fs::write(&path, "[theme]\nbase = \"sepia\"\n").unwrap();
+    let error = Config::load(Some(path.clone())).unwrap_err().to_string();
+    assert!(error.contains("could not be parsed"), "{error}");
+
+    fs::write(&path, "[theme]\ndiff_add = \"greenish\"\n").unwrap();
+    let error = Config::load(Some(path)).unwrap_err().to_string();
+    assert!(
+        error.contains("invalid [theme]: diff_add: unknown color or modifier greenish"),
+        "{error}"
+    );
+}
+
+#[test]
+fn no_color_keeps_modifiers_only() {
+    let theme = Theme::builtin(ThemeName::HighContrast).with_color(false);
+
+    assert_eq!(
+        theme.error,
+        Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+    );
+    assert_eq!(theme.paint(theme.error, "ERROR"), "ERROR");
+    assert_eq!(
+        Theme::default().paint(Theme::default().staged, "staged"),
+        "\x1b[0;31mstaged\x1b[0m"
+    );
+}
+
+#[test]
+fn status_colors_follow_the_color_flag_and_no_color() {
+    let fixture = fixture();
+
+    // Piped output is plain by default
+    let args = ["--status", "--disable-ascii"];
+
+    let output = fuzzit(&fixture, "", &args, false);
+    assert!(
+        output.contains("dirty .. DIRTY (changes not added)"),
+        "{output}"
+    );
+    assert!(!output.contains('\x1b'), "{output}");
+
+    let output = fuzzit(
+        &fixture,
+        "",
+        &[&args[..], &["--color", "always"]].concat(),
+        false,
+    );
+    assert!(
+        output.contains("\x1b[0;38;2;255;184;108mDIRTY (changes not added)\x1b[0m"),
+        "{output}"
+    );
+    // --color always wins over NO_COLOR
+    let output = fuzzit(
+        &fixture,
+        "",
+        &[&args[..], &["--color=always"]].concat(),
+        true,
+    );
+    assert!(output.contains('\x1b'), "{output}");
+
+    let output = fuzzit(
+        &fixture,
+        "[theme]\nunstaged = \"blue\"\n",
+        &[&args[..], &["--color=always"]].concat(),
+        false,
+    );
+    assert!(
+        output.contains("\x1b[0;34mDIRTY (changes not added)\x1b[0m"),
+        "{output}"
+    );
+}
+
+#[test]
+fn render_once_drops_colors_for_no_color() {
+    let fixture = fixture();
+    let args = ["--render-once", "100x20", "--render-format", "ansi"];
+
+    let output = fuzzit(&fixture, "", &args, false);
+    assert!(output.contains("\x1b[0;3;32m1 clean"), "{output}");
+
+    // The italic clean state is kept without its color
+    let output = fuzzit(&fixture, "", &args, true);
+    assert!(output.contains("\x1b[0;3m1 clean"), "{output}");
+    assert!(!output.contains(";32m"), "{output}");
+}